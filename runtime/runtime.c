@@ -1,18 +1,209 @@
 
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
 #include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+// Writers print a value without a trailing newline, as needed to print the fields of a record.
+
+void _write_int(int x) {
+    printf("%i", x);
+}
+
+void _write_bool(bool x) {
+    printf("%s", x ? "true" : "false");
+}
+
+void _write_float(double x) {
+    printf("%lf", x);
+}
+
+void _write_complex(double re, double im) {
+    printf("%lf%+lfi", re, im);
+}
+
+void _write_range(int start, int end) {
+    printf("%i..%i", start, end);
+}
+
+void _write_quantity(double x, const char *unit) {
+    printf("%lf %s", x, unit);
+}
+
+void _write_matrix(double *data, int rows, int cols) {
+    printf("[");
+    for (int i = 0; i < rows * cols; i++) {
+        if (i != 0) {
+            printf(i % cols == 0 ? "; " : ", ");
+        }
+        printf("%lf", data[i]);
+    }
+    printf("]");
+}
+
+void _write_str(const char *s) {
+    printf("%s", s);
+}
 
 void _print_int(int x) {
-    printf("%i\n", x);
+    _write_int(x);
+    putchar('\n');
+}
+
+void _print_bool(bool x) {
+    _write_bool(x);
+    putchar('\n');
 }
 
 void _print_float(double x) {
-    printf("%lf\n", x);
+    _write_float(x);
+    putchar('\n');
+}
+
+void _print_quantity(double x, const char *unit) {
+    _write_quantity(x, unit);
+    putchar('\n');
+}
+
+void _print_complex(double re, double im) {
+    _write_complex(re, im);
+    putchar('\n');
+}
+
+void _print_range(int start, int end) {
+    _write_range(start, end);
+    putchar('\n');
+}
+
+void _print_matrix(double *data, int rows, int cols) {
+    _write_matrix(data, rows, cols);
+    putchar('\n');
 }
 
+// Find the row with the largest pivot in column `col`, starting at row `col`.
+static int _mat_pivot(double *m, int n, int col) {
+    int pivot = col;
+    for (int row = col + 1; row < n; row++) {
+        if (fabs(m[row * n + col]) > fabs(m[pivot * n + col])) {
+            pivot = row;
+        }
+    }
+    return pivot;
+}
+
+static void _mat_swap_rows(double *m, int n, int a, int b) {
+    for (int j = 0; j < n; j++) {
+        double tmp = m[a * n + j];
+        m[a * n + j] = m[b * n + j];
+        m[b * n + j] = tmp;
+    }
+}
+
+// Determinant using Gaussian elimination with partial pivoting.
+double _mat_det(double *data, int n) {
+    double m[n * n];
+    memcpy(m, data, sizeof(m));
+
+    double det = 1.0;
+    for (int col = 0; col < n; col++) {
+        int pivot = _mat_pivot(m, n, col);
+        if (m[pivot * n + col] == 0.0) {
+            return 0.0;
+        }
+        if (pivot != col) {
+            _mat_swap_rows(m, n, pivot, col);
+            det = -det;
+        }
+
+        det *= m[col * n + col];
+        for (int row = col + 1; row < n; row++) {
+            double factor = m[row * n + col] / m[col * n + col];
+            for (int j = col; j < n; j++) {
+                m[row * n + j] -= factor * m[col * n + j];
+            }
+        }
+    }
+    return det;
+}
+
+// Inverse using Gauss-Jordan elimination with partial pivoting.
+void _mat_inverse(double *data, double *res, int n) {
+    double m[n * n];
+    memcpy(m, data, sizeof(m));
+    for (int i = 0; i < n * n; i++) {
+        res[i] = i % (n + 1) == 0 ? 1.0 : 0.0;
+    }
+
+    for (int col = 0; col < n; col++) {
+        int pivot = _mat_pivot(m, n, col);
+        _mat_swap_rows(m, n, pivot, col);
+        _mat_swap_rows(res, n, pivot, col);
+
+        double p = m[col * n + col];
+        for (int j = 0; j < n; j++) {
+            m[col * n + j] /= p;
+            res[col * n + j] /= p;
+        }
+
+        for (int row = 0; row < n; row++) {
+            if (row != col) {
+                double factor = m[row * n + col];
+                for (int j = 0; j < n; j++) {
+                    m[row * n + j] -= factor * m[col * n + j];
+                    res[row * n + j] -= factor * res[col * n + j];
+                }
+            }
+        }
+    }
+}
+
+
+// SplitMix64, must be kept in sync with `src/data/rng.rs`
+static uint64_t _rng_state = 0;
+
+void _seed(int seed) {
+    _rng_state = (uint64_t)(int64_t)seed;
+}
+
+static uint64_t _rng_next() {
+    _rng_state += 0x9E3779B97F4A7C15ull;
+    uint64_t z = _rng_state;
+    z = (z ^ (z >> 30)) * 0xBF58476D1CE4E5B9ull;
+    z = (z ^ (z >> 27)) * 0x94D049BB133111EBull;
+    return z ^ (z >> 31);
+}
+
+double _rand() {
+    return (double)(_rng_next() >> 11) * (1.0 / (double)(1ull << 53));
+}
+
+int _rand_int(int lo, int hi) {
+    int64_t width = (int64_t)hi - (int64_t)lo + 1;
+    if (width <= 0) {
+        return lo;
+    }
+    return (int)((int64_t)lo + (int64_t)(_rng_next() % (uint64_t)width));
+}
+
+// Exit statuses of failing programs, which must match those of `calc run` (see
+// `src/errors/mod.rs`).
+#define EXIT_ASSERTION_FAILED 3
+#define EXIT_RUNTIME_ERROR 4
+
+void _assert_fail(const char *msg, const char *file, int line, int col) {
+    fprintf(stderr, "%s:%i:%i: Assertion failed: %s\n", file, line, col, msg);
+    exit(EXIT_ASSERTION_FAILED);
+}
+
+void _runtime_error(const char *msg, const char *file, int line, int col) {
+    fprintf(stderr, "%s:%i:%i: Runtime error: %s\n", file, line, col, msg);
+    exit(EXIT_RUNTIME_ERROR);
+}
 
-extern void calc_main();
+extern int calc_main();
 
 int main() {
-    calc_main();
-    return 0;
+    return calc_main();
 }
\ No newline at end of file
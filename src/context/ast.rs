@@ -2,6 +2,7 @@ use crate::data::ast::Stmt;
 use crate::data::meta::Meta;
 use crate::data::span::Span;
 use crate::data::token_span::TokSpan;
+use crate::errors::Spanned;
 use crate::pipeline;
 use std::fmt;
 use std::fmt::{Debug, Display, Write};
@@ -27,10 +28,16 @@ impl<M: Debug + Display> Ast<M> {
     pub fn push_stmt(&mut self, stmt: Stmt<M>) {
         self.stmts.push(stmt);
     }
+}
 
+impl<M: Debug + Display + Spanned> Ast<M> {
     pub fn format<W: Write>(&self, w: &mut W) -> Result<(), fmt::Error> {
         pipeline::formatter::format(self, w)
     }
+
+    pub fn format_conversions<W: Write>(&self, w: &mut W) -> Result<(), fmt::Error> {
+        pipeline::formatter::format_conversions(self, w)
+    }
 }
 
 impl Ast<TokSpan> {
@@ -3,16 +3,19 @@
 //! In this state, all the AST nodes are decorated with type info.
 
 use crate::context::ast::Ast;
-use crate::data::ast::{BinOp, Expr, Stmt, UnaryOp, VarName};
+use crate::data::ast::{BinOp, Expr, Pattern, Stmt, UnaryOp, VarName};
 use crate::data::span::Span;
 use crate::data::token_span::TokSpan;
+use crate::data::unit::Dimension;
 use crate::errors::error_message::Spanned;
 use std::fmt;
 use std::fmt::Write;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 
 pub type CheckedStmt = Stmt<TypeInfo>;
 pub type CheckedExpr = Expr<TypeInfo>;
-pub type CheckedAst = Ast<TypeInfo>;
+pub type CheckedPattern = Pattern<TypeInfo>;
 pub type CheckedVarName = VarName<TypeInfo>;
 pub type CheckedBinOp = BinOp<TypeInfo>;
 pub type CheckedUnaryOp = UnaryOp<TypeInfo>;
@@ -27,17 +30,173 @@ impl Ast<TypeInfo> {
     }
 }
 
+/// The AST after type checking, along with the user-defined record types its nodes refer to.
+pub struct CheckedAst {
+    ast: Ast<TypeInfo>,
+
+    /// user-defined record types, in declaration order
+    types: Vec<Rc<RecordType>>,
+}
+
+impl CheckedAst {
+    pub fn new(types: Vec<Rc<RecordType>>) -> Self {
+        Self {
+            ast: Ast::new(),
+            types,
+        }
+    }
+
+    pub fn types(&self) -> &[Rc<RecordType>] {
+        &self.types
+    }
+}
+
+impl Deref for CheckedAst {
+    type Target = Ast<TypeInfo>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.ast
+    }
+}
+
+impl DerefMut for CheckedAst {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.ast
+    }
+}
+
+// the record types are shown by their declarations
+impl fmt::Debug for CheckedAst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.ast.fmt(f)
+    }
+}
+
 // Note:
 // This is a massive simplification of what a real type structure would be. In a realistic case,
 // `CheckedAst` would contain a vector of types (including user defined ones). Then, `CheckedInfo`
 // would contain a ref-counted pointer to one of the types.
 
+/// A user-defined record type, e.g. `record Point { x float, y float }`.
+#[derive(Debug, PartialEq)]
+pub struct RecordType {
+    pub name: String,
+
+    /// field names and types, in declaration order
+    pub fields: Vec<(String, Type)>,
+}
+
+impl RecordType {
+    pub fn new(name: impl Into<String>, fields: Vec<(String, Type)>) -> Self {
+        Self {
+            name: name.into(),
+            fields,
+        }
+    }
+
+    /// Index and type of a field.
+    pub fn field(&self, name: &str) -> Option<(usize, &Type)> {
+        self.fields
+            .iter()
+            .enumerate()
+            .find_map(|(i, (field, type_))| (field == name).then_some((i, type_)))
+    }
+}
+
+/// A user-defined enum type, e.g. `enum Mode { Fast, Slow }`.
+#[derive(Debug, PartialEq)]
+pub struct EnumType {
+    pub name: String,
+
+    /// variant names, in declaration order
+    pub variants: Vec<String>,
+}
+
+impl EnumType {
+    pub fn new(name: impl Into<String>, variants: Vec<String>) -> Self {
+        Self {
+            name: name.into(),
+            variants,
+        }
+    }
+
+    /// Index of a variant.
+    pub fn variant(&self, name: &str) -> Option<usize> {
+        self.variants.iter().position(|variant| variant == name)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Stmt, // stmt only
     Integer,
     Float,
-    Tuple { type_: Box<Type>, len: usize },
+    Bool,
+    Complex,
+    Quantity(Dimension),                    // float with a physical dimension
+    Tuple { type_: Box<Type>, len: usize }, // homogeneous tuple, supporting arithmetic
+    Product(Vec<Type>),                     // heterogeneous tuple
+    Matrix { rows: usize, cols: usize },    // matrix of floats, vectors have a single column
+    Range,                                  // half-open range of integers
+    Seq(Box<Type>),                         // homogeneous sequence of dynamic length
+    Record(Rc<RecordType>),
+    Enum(Rc<EnumType>),
+    Function { params: Vec<Type>, ret: Box<Type> }, // function value, e.g. a lambda
+    Optional(Box<Type>),                            // value which may be `none`
+    Symbolic,                                       // expression in a variable, e.g. a derivative
+}
+
+impl Type {
+    /// Type of a float with the given dimension, which may turn out to be dimensionless.
+    pub fn quantity(dim: Dimension) -> Self {
+        if dim.is_dimensionless() {
+            Self::Float
+        } else {
+            Self::Quantity(dim)
+        }
+    }
+
+    /// Whether values of this type can be compared with `==` and `!=`.
+    pub fn supports_equality(&self) -> bool {
+        match self {
+            Self::Integer
+            | Self::Float
+            | Self::Bool
+            | Self::Complex
+            | Self::Quantity(_)
+            | Self::Enum(_) => true,
+            Self::Tuple { type_, .. } | Self::Optional(type_) => type_.supports_equality(),
+            Self::Product(types) => types.iter().all(Self::supports_equality),
+            Self::Stmt
+            | Self::Matrix { .. }
+            | Self::Range
+            | Self::Seq(_)
+            | Self::Record(_)
+            | Self::Function { .. }
+            | Self::Symbolic => false,
+        }
+    }
+
+    /// Whether values of this type are made of floats, such that an integer literal combined
+    /// with it should be resolved to a float.
+    pub fn is_float_based(&self) -> bool {
+        match self {
+            Self::Float | Self::Quantity(_) | Self::Matrix { .. } => true,
+            Self::Tuple { type_, .. } => type_.is_float_based(),
+            Self::Stmt
+            | Self::Integer
+            | Self::Bool
+            | Self::Complex
+            | Self::Range
+            | Self::Seq(_)
+            | Self::Product(_)
+            | Self::Record(_)
+            | Self::Enum(_)
+            | Self::Function { .. }
+            | Self::Optional(_)
+            | Self::Symbolic => false,
+        }
+    }
 }
 
 impl fmt::Display for Type {
@@ -47,13 +206,45 @@ impl fmt::Display for Type {
             Stmt => write!(f, "stmt"),
             Integer => write!(f, "int"),
             Float => write!(f, "float"),
+            Bool => write!(f, "bool"),
+            Complex => write!(f, "complex"),
+            Quantity(dim) => write!(f, "float[{}]", dim),
             Tuple { type_, len } => write!(f, "({}; {})", type_, len),
+            Product(types) => {
+                write!(f, "(")?;
+                for (i, type_) in types.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", type_)?;
+                }
+                write!(f, ")")
+            }
+            Matrix { rows, cols } => write!(f, "mat[{}x{}]", rows, cols),
+            Range => write!(f, "range"),
+            Seq(type_) => write!(f, "[{}]", type_),
+            Record(record) => write!(f, "{}", record.name),
+            Enum(enum_) => write!(f, "{}", enum_.name),
+            Function { params, ret } => {
+                write!(f, "fn(")?;
+                for (i, type_) in params.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", type_)?;
+                }
+                write!(f, ") {}", ret)
+            }
+            // `fn(int) int?` returns an optional
+            Optional(type_) if matches!(**type_, Function { .. }) => write!(f, "({})?", type_),
+            Optional(type_) => write!(f, "{}?", type_),
+            Symbolic => write!(f, "expr"),
         }
     }
 }
 
 /// AST meta-data after type checking.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypeInfo {
     /// type of the node
     pub type_: Type,
@@ -78,4 +269,8 @@ impl Spanned for TypeInfo {
     fn span(&self) -> Span {
         self.tok_span.span()
     }
+
+    fn call_sites(&self) -> Vec<Span> {
+        self.tok_span.call_sites()
+    }
 }
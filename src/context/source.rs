@@ -1,11 +1,119 @@
-pub struct Source(String);
+/// Name of the module holding the standard prelude, whose variables are also visible unqualified.
+/// It is reserved, such that no file may be used as a module of that name.
+pub const PRELUDE: &str = "prelude";
+
+pub struct Source {
+    text: String,
+
+    /// name of the file the source was read from, used to locate runtime errors
+    name: String,
+
+    /// name of the module, for sources loaded with `use` whose variables are namespaced
+    module: Option<String>,
+
+    /// whether this is the standard prelude embedded into the binary, rather than a file
+    prelude: bool,
+}
 
 impl Source {
     pub fn new(source: String) -> Self {
-        Self(source)
+        Self {
+            text: source,
+            name: "<input>".to_owned(),
+            module: None,
+            prelude: false,
+        }
+    }
+
+    /// The standard prelude, embedded into the binary.
+    pub fn prelude() -> Self {
+        Self {
+            prelude: true,
+            ..Self::new(include_str!("../prelude.calc").to_owned())
+                .with_name("prelude.calc")
+                .with_module(PRELUDE)
+        }
+    }
+
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    pub fn with_module(self, module: impl Into<String>) -> Self {
+        Self {
+            module: Some(module.into()),
+            ..self
+        }
     }
 
     pub fn source(&self) -> &str {
-        &self.0
+        &self.text
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn module(&self) -> Option<&str> {
+        self.module.as_deref()
+    }
+
+    pub fn is_prelude(&self) -> bool {
+        self.prelude
+    }
+}
+
+/// The source files of a program, i.e. the main one and those it imports.
+///
+/// Locations refer to their file by its index, the main file being the first one. The files are
+/// merged into a single program in dependency order, such that imported files come before the
+/// files importing them.
+pub struct Sources {
+    sources: Vec<Source>,
+
+    /// indices of the sources, in dependency order
+    order: Vec<usize>,
+}
+
+impl Sources {
+    pub fn new(main: Source) -> Self {
+        Self {
+            sources: vec![main],
+            order: vec![0],
+        }
+    }
+
+    /// Add a source and return its index. It is only merged once ordered with [`Self::set_order`].
+    pub fn add(&mut self, source: Source) -> usize {
+        self.sources.push(source);
+        self.sources.len() - 1
+    }
+
+    pub fn set_order(&mut self, order: Vec<usize>) {
+        self.order = order;
+    }
+
+    pub fn get(&self, index: usize) -> &Source {
+        &self.sources[index]
+    }
+
+    pub fn main(&self) -> &Source {
+        &self.sources[0]
+    }
+
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Whether the program is made of several files, not counting the prelude.
+    pub fn has_imports(&self) -> bool {
+        self.sources
+            .iter()
+            .filter(|source| !source.is_prelude())
+            .count()
+            > 1
     }
 }
@@ -16,6 +16,7 @@ use std::fmt::{Debug, Display};
 pub enum UnaryOpKind {
     Pos,
     Neg,
+    BitNot,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -24,6 +25,18 @@ pub enum BinOpKind {
     Sub,
     Mul,
     Div,
+    MatMul,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -38,23 +51,227 @@ pub enum ExprKind<T: Debug + Display> {
         op: UnaryOp<T>,
         operand: Box<Expr<T>>,
     },
+    Call {
+        name: VarName<T>,
+        args: Vec<Expr<T>>,
+    },
+    /// Call of the function value of an expression, e.g. `t.0(1)` or `mk(2)(3)`. Calls of named
+    /// functions are `Call`s.
+    Apply {
+        callee: Box<Expr<T>>,
+        args: Vec<Expr<T>>,
+    },
     Tuple(Vec<Expr<T>>),
+    Matrix(Vec<Vec<Expr<T>>>),
+    /// Record construction, e.g. `Point { x: 1.0, y: 2.0 }`. Fields may be given in any order.
+    Record {
+        name: TypeName<T>,
+        fields: Vec<(VarName<T>, Expr<T>)>,
+    },
+    /// Field access, e.g. `p.x`
+    Field {
+        expr: Box<Expr<T>>,
+        field: VarName<T>,
+    },
+    /// Enum variant, e.g. `Mode::Fast`
+    Variant {
+        name: TypeName<T>,
+        variant: VarName<T>,
+    },
+    /// Tuple element access, e.g. `t.0`
+    TupleIndex {
+        expr: Box<Expr<T>>,
+        index: usize,
+    },
+    /// Half-open range of integers, e.g. `0..10`
+    Range {
+        start: Box<Expr<T>>,
+        end: Box<Expr<T>>,
+    },
     Integer(i32),
     Float(f64),
+    Bool(bool),
+    Imaginary(f64),
+    /// Absent optional value, whose type is inferred from the context
+    None,
+    /// Value of an optional, or the default if there is none, e.g. `x ?? 0`. The default is only
+    /// evaluated if needed.
+    Unwrap {
+        expr: Box<Expr<T>>,
+        default: Box<Expr<T>>,
+    },
+    Quantity {
+        value: Box<Expr<T>>,
+        unit: UnitName<T>,
+    },
+    /// Conversion of the operand to the type of this node. Never produced by the parser, only
+    /// inserted by the type checker.
+    Convert(Box<Expr<T>>),
+    /// Anonymous function, e.g. `fn(x int) int => x * x`. Types are omitted in the short form
+    /// `(a, b) => a + b`, which is only valid where they can be inferred.
+    Lambda {
+        params: Vec<(VarName<T>, Option<TypeName<T>>)>,
+        ret: Option<TypeName<T>>,
+        body: Box<Expr<T>>,
+        /// Variables of enclosing scopes used by the body, which are captured by value when the
+        /// function is created. Only filled in by the type checker.
+        captures: Vec<VarName<T>>,
+    },
+    /// Conditional expression, e.g. `if a > b then a else b`. Only the selected branch is
+    /// evaluated.
+    Conditional {
+        cond: Box<Expr<T>>,
+        then_branch: Box<Expr<T>>,
+        else_branch: Box<Expr<T>>,
+    },
+    /// Symbolic derivative of an expression with respect to a variable, e.g. `deriv(x * x, x)`.
+    /// The variable is bound by the expression, which may also be a symbolic value itself.
+    Deriv {
+        expr: Box<Expr<T>>,
+        var: VarName<T>,
+    },
+    /// Value of a symbolic expression for a value of its variable, e.g. `eval(d, x = 2.0)`
+    Eval {
+        expr: Box<Expr<T>>,
+        var: VarName<T>,
+        value: Box<Expr<T>>,
+    },
+}
+
+/// Pattern destructuring the value of a `let` statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind<T: Debug + Display> {
+    /// Binds the value to a variable
+    Name(VarName<T>),
+    /// Ignores the value, i.e. `_`
+    Wildcard,
+    /// Destructures a tuple element-wise, e.g. `(a, (b, _))`
+    Tuple(Vec<Pattern<T>>),
+}
+
+/// Type written in the source, e.g. in a declaration `x: int? = none;` or a lambda signature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeNameKind<T: Debug + Display> {
+    /// Built-in or declared type, e.g. `int` or `Point`
+    Named(String),
+    /// Function type, e.g. `fn(int, float) bool`
+    Fn {
+        params: Vec<TypeName<T>>,
+        ret: Box<TypeName<T>>,
+    },
+    /// Optional type, e.g. `int?`. As `fn(int) int?` returns an optional, an optional function is
+    /// parenthesized, e.g. `(fn(int) int)?`.
+    Optional(Box<TypeName<T>>),
+    /// Tuple type, e.g. `(int, float)`
+    Tuple(Vec<TypeName<T>>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum StmtKind<T: Debug + Display> {
-    Assign { name: VarName<T>, value: Expr<T> },
-    Print { expr: Expr<T> },
-    Expr { expr: Expr<T> },
+    Assign {
+        name: VarName<T>,
+        value: Expr<T>,
+    },
+    /// Assignment with a type annotation, e.g. `x: int? = none;`
+    Declare {
+        name: VarName<T>,
+        type_name: TypeName<T>,
+        value: Expr<T>,
+    },
+    /// Destructuring assignment, e.g. `let (a, (b, _)) = t;`
+    Let {
+        pattern: Pattern<T>,
+        value: Expr<T>,
+    },
+    /// Compound assignment, e.g. `x += 1;`
+    CompoundAssign {
+        name: VarName<T>,
+        op: BinOp<T>,
+        value: Expr<T>,
+    },
+    Print {
+        expr: Expr<T>,
+    },
+    Expr {
+        expr: Expr<T>,
+    },
+    Seed {
+        seed: Expr<T>,
+    },
+    /// Stop the program with the given exit status, e.g. `exit(1);`
+    Exit {
+        code: Expr<T>,
+    },
+    /// Runtime check, e.g. `assert x > 0, "x must be positive";`
+    Assert {
+        cond: Expr<T>,
+        message: String,
+    },
+    For {
+        var: VarName<T>,
+        iter: Expr<T>,
+        body: Vec<Stmt<T>>,
+    },
+    /// Plot of a float expression over an interval, written to an SVG file, e.g.
+    /// `plot y = x * x for x in 0..10, "square.svg";`. The name only labels the vertical axis.
+    Plot {
+        name: VarName<T>,
+        value: Expr<T>,
+        var: VarName<T>,
+        start: Expr<T>,
+        end: Expr<T>,
+        path: String,
+    },
+    /// Record type declaration, e.g. `record Point { x float, y float }`
+    Record {
+        name: TypeName<T>,
+        fields: Vec<(VarName<T>, TypeName<T>)>,
+    },
+    /// Enum type declaration, e.g. `enum Mode { Fast, Slow }`
+    Enum {
+        name: TypeName<T>,
+        variants: Vec<VarName<T>>,
+    },
+    /// Dispatch on an enum value, e.g. `match m { Mode::Fast => { ... } Mode::Slow => { ... } }`.
+    /// Each arm's pattern is a `Variant` expression, and all variants must be covered.
+    Match {
+        expr: Expr<T>,
+        arms: Vec<(Expr<T>, Vec<Stmt<T>>)>,
+    },
+    /// Import of a file, e.g. `import "consts.calc";`, whose statements are merged into the
+    /// program before those of the importing file, as loaded by the loader.
+    Import {
+        path: String,
+    },
+    /// Use of a module, e.g. `use geo;`, whose statements are namespaced in a `Module` before those
+    /// of the using file, as loaded by the loader.
+    Use {
+        module: String,
+    },
+    /// Statements of a module loaded with `use geo;`, whose variables are only visible from
+    /// other files qualified with the module name, e.g. `geo.radius`. Never produced by the
+    /// parser for the main file.
+    Module {
+        name: String,
+        body: Vec<Stmt<T>>,
+    },
+    /// Macro definition, e.g. `macro square(x) => (x) * (x);`. Calls to the macro are replaced by
+    /// its body, with the parameters replaced by the arguments, before type checking.
+    Macro {
+        name: VarName<T>,
+        params: Vec<VarName<T>>,
+        body: Expr<T>,
+    },
 }
 
 pub type BinOp<T> = Meta<BinOpKind, T>;
 pub type UnaryOp<T> = Meta<UnaryOpKind, T>;
 pub type VarName<T> = Meta<String, T>;
+pub type UnitName<T> = Meta<String, T>;
+pub type TypeName<T> = Meta<TypeNameKind<T>, T>;
 pub type Expr<T> = Meta<ExprKind<T>, T>;
 pub type Stmt<T> = Meta<StmtKind<T>, T>;
+pub type Pattern<T> = Meta<PatternKind<T>, T>;
 
 // =================================================================================================
 // FACTORIES
@@ -64,6 +281,7 @@ impl From<&TokenKind> for UnaryOpKind {
         match value {
             TokenKind::Plus => Self::Pos,
             TokenKind::Minus => Self::Neg,
+            TokenKind::Tilde => Self::BitNot,
             _ => panic!("Invalid token kind: {:?}", value),
         }
     }
@@ -78,14 +296,41 @@ impl<T> UnaryOp<T> {
     }
 }
 
+impl BinOpKind {
+    /// Binary operator corresponding to a token within an expression, if any.
+    pub fn from_token(value: &TokenKind) -> Option<Self> {
+        match value {
+            TokenKind::Plus => Some(Self::Add),
+            TokenKind::Minus => Some(Self::Sub),
+            TokenKind::Star => Some(Self::Mul),
+            TokenKind::Slash => Some(Self::Div),
+            TokenKind::At => Some(Self::MatMul),
+            TokenKind::Ampersand => Some(Self::BitAnd),
+            TokenKind::Pipe => Some(Self::BitOr),
+            TokenKind::Caret => Some(Self::BitXor),
+            TokenKind::ShiftLeft => Some(Self::Shl),
+            TokenKind::ShiftRight => Some(Self::Shr),
+            TokenKind::EqEq => Some(Self::Eq),
+            TokenKind::NotEq => Some(Self::Ne),
+            TokenKind::Lt => Some(Self::Lt),
+            TokenKind::LtEq => Some(Self::Le),
+            TokenKind::Gt => Some(Self::Gt),
+            TokenKind::GtEq => Some(Self::Ge),
+            _ => None,
+        }
+    }
+}
+
 impl From<&TokenKind> for BinOpKind {
     fn from(value: &TokenKind) -> Self {
         match value {
-            TokenKind::Plus => Self::Add,
-            TokenKind::Minus => Self::Sub,
-            TokenKind::Star => Self::Mul,
-            TokenKind::Slash => Self::Div,
-            _ => panic!("Invalid token kind: {:?}", value),
+            TokenKind::PlusAssign => Self::Add,
+            TokenKind::MinusAssign => Self::Sub,
+            TokenKind::StarAssign => Self::Mul,
+            TokenKind::SlashAssign => Self::Div,
+            _ => {
+                Self::from_token(value).unwrap_or_else(|| panic!("Invalid token kind: {:?}", value))
+            }
         }
     }
 }
@@ -137,6 +382,26 @@ impl<T: Debug + Display> Expr<T> {
         }
     }
 
+    pub fn call(name: impl Into<VarName<T>>, args: Vec<Expr<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Call {
+                name: name.into(),
+                args,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn apply(callee: Expr<T>, args: Vec<Expr<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Apply {
+                callee: Box::new(callee),
+                args,
+            },
+            meta: meta.into(),
+        }
+    }
+
     pub fn tuple(exprs: Vec<Expr<T>>, meta: impl Into<T>) -> Self {
         Self {
             kind: ExprKind::Tuple(exprs),
@@ -144,6 +409,61 @@ impl<T: Debug + Display> Expr<T> {
         }
     }
 
+    pub fn matrix(rows: Vec<Vec<Expr<T>>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Matrix(rows),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn record(
+        name: impl Into<TypeName<T>>,
+        fields: Vec<(VarName<T>, Expr<T>)>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: ExprKind::Record {
+                name: name.into(),
+                fields,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn field(expr: Expr<T>, field: impl Into<VarName<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Field {
+                expr: Box::new(expr),
+                field: field.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn variant(
+        name: impl Into<TypeName<T>>,
+        variant: impl Into<VarName<T>>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: ExprKind::Variant {
+                name: name.into(),
+                variant: variant.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn tuple_index(expr: Expr<T>, index: usize, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::TupleIndex {
+                expr: Box::new(expr),
+                index,
+            },
+            meta: meta.into(),
+        }
+    }
+
     pub fn integer(value: i32, meta: impl Into<T>) -> Self {
         Self {
             kind: ExprKind::Integer(value),
@@ -157,6 +477,124 @@ impl<T: Debug + Display> Expr<T> {
             meta: meta.into(),
         }
     }
+
+    pub fn bool(value: bool, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Bool(value),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn imaginary(value: f64, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Imaginary(value),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn none(meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::None,
+            meta: meta.into(),
+        }
+    }
+
+    pub fn unwrap(expr: Expr<T>, default: Expr<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Unwrap {
+                expr: Box::new(expr),
+                default: Box::new(default),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn quantity(value: Expr<T>, unit: impl Into<UnitName<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Quantity {
+                value: Box::new(value),
+                unit: unit.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn range(start: Expr<T>, end: Expr<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Range {
+                start: Box::new(start),
+                end: Box::new(end),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn conditional(
+        cond: Expr<T>,
+        then_branch: Expr<T>,
+        else_branch: Expr<T>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: ExprKind::Conditional {
+                cond: Box::new(cond),
+                then_branch: Box::new(then_branch),
+                else_branch: Box::new(else_branch),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn lambda(
+        params: Vec<(VarName<T>, Option<TypeName<T>>)>,
+        ret: Option<TypeName<T>>,
+        body: Expr<T>,
+        captures: Vec<VarName<T>>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: ExprKind::Lambda {
+                params,
+                ret,
+                body: Box::new(body),
+                captures,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn convert(expr: Expr<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Convert(Box::new(expr)),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn deriv(expr: Expr<T>, var: impl Into<VarName<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: ExprKind::Deriv {
+                expr: Box::new(expr),
+                var: var.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn eval(
+        expr: Expr<T>,
+        var: impl Into<VarName<T>>,
+        value: Expr<T>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: ExprKind::Eval {
+                expr: Box::new(expr),
+                var: var.into(),
+                value: Box::new(value),
+            },
+            meta: meta.into(),
+        }
+    }
 }
 
 impl<T: Debug + Display> Stmt<T> {
@@ -170,6 +608,45 @@ impl<T: Debug + Display> Stmt<T> {
         }
     }
 
+    pub fn declare(
+        name: impl Into<VarName<T>>,
+        type_name: impl Into<TypeName<T>>,
+        value: Expr<T>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::Declare {
+                name: name.into(),
+                type_name: type_name.into(),
+                value,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn let_(pattern: Pattern<T>, value: Expr<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Let { pattern, value },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn compound_assign(
+        name: impl Into<VarName<T>>,
+        op: BinOp<T>,
+        value: Expr<T>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::CompoundAssign {
+                name: name.into(),
+                op,
+                value,
+            },
+            meta: meta.into(),
+        }
+    }
+
     pub fn print(expr: Expr<T>, meta: impl Into<T>) -> Self {
         Self {
             kind: StmtKind::Print { expr },
@@ -183,11 +660,224 @@ impl<T: Debug + Display> Stmt<T> {
             meta: meta.into(),
         }
     }
+
+    pub fn seed(seed: Expr<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Seed { seed },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn exit(code: Expr<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Exit { code },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn assert(cond: Expr<T>, message: impl Into<String>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Assert {
+                cond,
+                message: message.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn for_(
+        var: impl Into<VarName<T>>,
+        iter: Expr<T>,
+        body: Vec<Stmt<T>>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::For {
+                var: var.into(),
+                iter,
+                body,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn record(
+        name: impl Into<TypeName<T>>,
+        fields: Vec<(VarName<T>, TypeName<T>)>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::Record {
+                name: name.into(),
+                fields,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn enum_(
+        name: impl Into<TypeName<T>>,
+        variants: Vec<VarName<T>>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::Enum {
+                name: name.into(),
+                variants,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn match_(expr: Expr<T>, arms: Vec<(Expr<T>, Vec<Stmt<T>>)>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Match { expr, arms },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn macro_(
+        name: impl Into<VarName<T>>,
+        params: Vec<VarName<T>>,
+        body: Expr<T>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::Macro {
+                name: name.into(),
+                params,
+                body,
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn plot(
+        name: impl Into<VarName<T>>,
+        value: Expr<T>,
+        var: impl Into<VarName<T>>,
+        start: Expr<T>,
+        end: Expr<T>,
+        path: impl Into<String>,
+        meta: impl Into<T>,
+    ) -> Self {
+        Self {
+            kind: StmtKind::Plot {
+                name: name.into(),
+                value,
+                var: var.into(),
+                start,
+                end,
+                path: path.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn import(path: impl Into<String>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Import { path: path.into() },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn use_(module: impl Into<String>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Use {
+                module: module.into(),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn module(name: impl Into<String>, body: Vec<Stmt<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: StmtKind::Module {
+                name: name.into(),
+                body,
+            },
+            meta: meta.into(),
+        }
+    }
+}
+
+impl<T: Debug + Display> Pattern<T> {
+    pub fn name(name: impl Into<VarName<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: PatternKind::Name(name.into()),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn wildcard(meta: impl Into<T>) -> Self {
+        Self {
+            kind: PatternKind::Wildcard,
+            meta: meta.into(),
+        }
+    }
+
+    pub fn tuple(patterns: Vec<Pattern<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: PatternKind::Tuple(patterns),
+            meta: meta.into(),
+        }
+    }
+}
+
+impl<T: Debug + Display> TypeName<T> {
+    pub fn named(name: impl Into<String>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: TypeNameKind::Named(name.into()),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn fn_(params: Vec<TypeName<T>>, ret: TypeName<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: TypeNameKind::Fn {
+                params,
+                ret: Box::new(ret),
+            },
+            meta: meta.into(),
+        }
+    }
+
+    pub fn optional(type_name: TypeName<T>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: TypeNameKind::Optional(Box::new(type_name)),
+            meta: meta.into(),
+        }
+    }
+
+    pub fn tuple(type_names: Vec<TypeName<T>>, meta: impl Into<T>) -> Self {
+        Self {
+            kind: TypeNameKind::Tuple(type_names),
+            meta: meta.into(),
+        }
+    }
 }
 
 // =================================================================================================
 // UTILITY TRAITS
 
+impl BinOpKind {
+    /// Whether this is a bitwise or shift operator, which only apply to integers.
+    pub const fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            Self::BitAnd | Self::BitOr | Self::BitXor | Self::Shl | Self::Shr
+        )
+    }
+
+    /// Whether this is a comparison operator, which produces a `bool`.
+    pub const fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge
+        )
+    }
+}
+
 impl fmt::Display for BinOpKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -195,6 +885,49 @@ impl fmt::Display for BinOpKind {
             Self::Sub => write!(f, "-"),
             Self::Mul => write!(f, "*"),
             Self::Div => write!(f, "/"),
+            Self::MatMul => write!(f, "@"),
+            Self::BitAnd => write!(f, "&"),
+            Self::BitOr => write!(f, "|"),
+            Self::BitXor => write!(f, "^"),
+            Self::Shl => write!(f, "<<"),
+            Self::Shr => write!(f, ">>"),
+            Self::Eq => write!(f, "=="),
+            Self::Ne => write!(f, "!="),
+            Self::Lt => write!(f, "<"),
+            Self::Le => write!(f, "<="),
+            Self::Gt => write!(f, ">"),
+            Self::Ge => write!(f, ">="),
+        }
+    }
+}
+
+impl<T: Debug + Display> fmt::Display for TypeNameKind<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_list = |f: &mut fmt::Formatter<'_>, type_names: &[TypeName<T>]| {
+            for (i, type_name) in type_names.iter().enumerate() {
+                if i != 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", type_name)?;
+            }
+            Ok(())
+        };
+        match self {
+            Self::Named(name) => write!(f, "{}", name),
+            Self::Fn { params, ret } => {
+                write!(f, "fn(")?;
+                write_list(f, params)?;
+                write!(f, ") {}", ret)
+            }
+            Self::Optional(type_name) if matches!(type_name.kind, Self::Fn { .. }) => {
+                write!(f, "({})?", type_name)
+            }
+            Self::Optional(type_name) => write!(f, "{}?", type_name),
+            Self::Tuple(type_names) => {
+                write!(f, "(")?;
+                write_list(f, type_names)?;
+                write!(f, ")")
+            }
         }
     }
 }
@@ -204,6 +937,7 @@ impl fmt::Display for UnaryOpKind {
         match self {
             Self::Neg => write!(f, "-"),
             Self::Pos => write!(f, "+"),
+            Self::BitNot => write!(f, "~"),
         }
     }
 }
@@ -0,0 +1,100 @@
+//! Built-in functions.
+//!
+//! Calls are parsed as plain names, and resolved against this list by the type checker. Later
+//! stages can safely assume that the name of a checked call is a valid built-in.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// absolute value (or modulus for complex numbers)
+    Abs,
+
+    /// real part of a complex number
+    Re,
+
+    /// imaginary part of a complex number
+    Im,
+
+    /// transpose of a matrix
+    Transpose,
+
+    /// determinant of a square matrix
+    Det,
+
+    /// inverse of a square matrix
+    Inverse,
+
+    /// random float in `[0, 1)`
+    Rand,
+
+    /// random integer in `[lo, hi]`
+    RandInt,
+
+    /// function applied to each element of a sequence, e.g. `map(t, x => x * 2)`
+    Map,
+
+    /// elements of a sequence satisfying a predicate, e.g. `filter(t, x => x > 0)`
+    Filter,
+
+    /// sequence reduced with a function and an initial value, e.g. `fold(t, 0, (a, b) => a + b)`
+    Fold,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "abs" => Some(Self::Abs),
+            "re" => Some(Self::Re),
+            "im" => Some(Self::Im),
+            "transpose" => Some(Self::Transpose),
+            "det" => Some(Self::Det),
+            "inverse" => Some(Self::Inverse),
+            "rand" => Some(Self::Rand),
+            "rand_int" => Some(Self::RandInt),
+            "map" => Some(Self::Map),
+            "filter" => Some(Self::Filter),
+            "fold" => Some(Self::Fold),
+            _ => None,
+        }
+    }
+
+    /// Number of arguments expected by the built-in.
+    pub const fn arity(&self) -> usize {
+        match self {
+            Self::Rand => 0,
+            Self::Abs | Self::Re | Self::Im | Self::Transpose | Self::Det | Self::Inverse => 1,
+            Self::RandInt | Self::Map | Self::Filter => 2,
+            Self::Fold => 3,
+        }
+    }
+
+    /// Whether calling the built-in has side effects, in which case the call must neither be
+    /// folded nor eliminated by the optimizer.
+    pub const fn has_side_effects(&self) -> bool {
+        matches!(self, Self::Rand | Self::RandInt)
+    }
+
+    /// Whether the built-in takes a function argument, whose parameter types may be inferred.
+    pub const fn is_higher_order(&self) -> bool {
+        matches!(self, Self::Map | Self::Filter | Self::Fold)
+    }
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Abs => write!(f, "abs"),
+            Self::Re => write!(f, "re"),
+            Self::Im => write!(f, "im"),
+            Self::Transpose => write!(f, "transpose"),
+            Self::Det => write!(f, "det"),
+            Self::Inverse => write!(f, "inverse"),
+            Self::Rand => write!(f, "rand"),
+            Self::RandInt => write!(f, "rand_int"),
+            Self::Map => write!(f, "map"),
+            Self::Filter => write!(f, "filter"),
+            Self::Fold => write!(f, "fold"),
+        }
+    }
+}
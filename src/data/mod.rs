@@ -1,8 +1,13 @@
 //! This module contains all the compiler's basic data structures.
 
 pub mod ast;
+pub mod builtin;
 pub mod meta;
+pub mod plot;
 pub mod precedence;
+pub mod rng;
 pub mod span;
+pub mod symbolic;
 pub mod token;
 pub mod token_span;
+pub mod unit;
@@ -0,0 +1,195 @@
+//! Plots of float expressions, e.g. `plot y = x * x for x in 0..10, "square.svg";`.
+//!
+//! The expression is sampled at evenly spaced points of the interval by the interpreter, and the
+//! plot is rendered as a standalone SVG document: the curve, framed by axes whose ticks are labeled
+//! with their values, and the names of the variables. Points whose value isn't finite, e.g.
+//! `1.0 / x` at 0, leave a gap in the curve.
+
+use std::fmt;
+
+/// Number of intervals between the sampled points.
+pub const SAMPLES: usize = 200;
+
+const WIDTH: f64 = 640.0;
+const HEIGHT: f64 = 480.0;
+const MARGIN: f64 = 60.0;
+
+/// Number of intervals between the ticks of an axis.
+const TICKS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct Plot {
+    /// plotted expression, e.g. `y = x * x`
+    pub title: String,
+
+    /// name of the variable, on the horizontal axis
+    pub x_label: String,
+
+    /// name of the value, on the vertical axis
+    pub y_label: String,
+
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Plot {
+    pub fn new(
+        title: impl Into<String>,
+        x_label: impl Into<String>,
+        y_label: impl Into<String>,
+        points: Vec<(f64, f64)>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            x_label: x_label.into(),
+            y_label: y_label.into(),
+            points,
+        }
+    }
+
+    /// Points at which an expression is sampled over an interval, both bounds included.
+    pub fn samples(start: f64, end: f64) -> impl Iterator<Item = f64> {
+        (0..=SAMPLES).map(move |i| start + (end - start) * i as f64 / SAMPLES as f64)
+    }
+
+    /// Bounds of the horizontal axis, which are the sampled interval.
+    fn x_bounds(&self) -> (f64, f64) {
+        match (self.points.first(), self.points.last()) {
+            (Some(&(start, _)), Some(&(end, _))) if start < end => (start, end),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    /// Bounds of the vertical axis, which are widened around a constant value.
+    fn y_bounds(&self) -> (f64, f64) {
+        let finite = self
+            .points
+            .iter()
+            .map(|&(_, y)| y)
+            .filter(|y| y.is_finite());
+        let (min, max) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), y| {
+            (min.min(y), max.max(y))
+        });
+        if min > max {
+            (-1.0, 1.0)
+        } else if min == max {
+            (min - 1.0, max + 1.0)
+        } else {
+            (min, max)
+        }
+    }
+}
+
+impl fmt::Display for Plot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (x_min, x_max) = self.x_bounds();
+        let (y_min, y_max) = self.y_bounds();
+        let (left, right, top, bottom) = (MARGIN, WIDTH - MARGIN, MARGIN, HEIGHT - MARGIN);
+        let sx = |x: f64| left + (x - x_min) / (x_max - x_min) * (right - left);
+        let sy = |y: f64| bottom - (y - y_min) / (y_max - y_min) * (bottom - top);
+
+        writeln!(
+            f,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" font-family="sans-serif" font-size="12">"#,
+            WIDTH, HEIGHT
+        )?;
+        writeln!(
+            f,
+            r#"<rect width="{}" height="{}" fill="white"/>"#,
+            WIDTH, HEIGHT
+        )?;
+        writeln!(
+            f,
+            r#"<text x="{}" y="{}" text-anchor="middle" font-size="16">{}</text>"#,
+            WIDTH / 2.0,
+            top / 2.0,
+            escape(&self.title)
+        )?;
+
+        // axes, with their ticks and labels
+        writeln!(
+            f,
+            r#"<path d="M{left} {top} V{bottom} H{right}" fill="none" stroke="black"/>"#
+        )?;
+        for i in 0..=TICKS {
+            let x = x_min + (x_max - x_min) * i as f64 / TICKS as f64;
+            writeln!(
+                f,
+                r#"<line x1="{0:.2}" y1="{1}" x2="{0:.2}" y2="{2}" stroke="black"/>"#,
+                sx(x),
+                bottom,
+                bottom + 5.0
+            )?;
+            writeln!(
+                f,
+                r#"<text x="{:.2}" y="{}" text-anchor="middle">{}</text>"#,
+                sx(x),
+                bottom + 20.0,
+                tick(x)
+            )?;
+
+            let y = y_min + (y_max - y_min) * i as f64 / TICKS as f64;
+            writeln!(
+                f,
+                r#"<line x1="{}" y1="{2:.2}" x2="{1}" y2="{2:.2}" stroke="black"/>"#,
+                left - 5.0,
+                left,
+                sy(y)
+            )?;
+            writeln!(
+                f,
+                r#"<text x="{}" y="{:.2}" text-anchor="end" dominant-baseline="middle">{}</text>"#,
+                left - 8.0,
+                sy(y),
+                tick(y)
+            )?;
+        }
+        writeln!(
+            f,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            (left + right) / 2.0,
+            HEIGHT - 15.0,
+            escape(&self.x_label)
+        )?;
+        writeln!(
+            f,
+            r#"<text x="15" y="{0}" text-anchor="middle" transform="rotate(-90 15 {0})">{1}</text>"#,
+            (top + bottom) / 2.0,
+            escape(&self.y_label)
+        )?;
+
+        // the curve, which is interrupted where the value isn't finite
+        let mut path = String::new();
+        let mut drawing = false;
+        for &(x, y) in &self.points {
+            if !y.is_finite() {
+                drawing = false;
+                continue;
+            }
+            let command = if drawing { 'L' } else { 'M' };
+            path.push_str(&format!("{}{:.2} {:.2} ", command, sx(x), sy(y)));
+            drawing = true;
+        }
+        writeln!(
+            f,
+            r#"<path d="{}" fill="none" stroke="steelblue" stroke-width="2"/>"#,
+            path.trim_end()
+        )?;
+        writeln!(f, "</svg>")
+    }
+}
+
+/// Label of a tick, with at most two decimals.
+fn tick(value: f64) -> String {
+    let label = format!("{:.2}", value);
+    let label = label.trim_end_matches('0').trim_end_matches('.');
+    match label {
+        "-0" => "0".to_owned(),
+        label => label.to_owned(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -1,6 +1,6 @@
 //! Central place for precedence values.
 //!
-//! Values are taken from my original Wabbit implementation, which explains the "holes".
+//! Values follow C's operator precedence, with "holes" left for logical (3, 2) operators.
 
 use crate::data::ast::{BinOpKind, ExprKind, UnaryOpKind};
 use std::fmt::{Debug, Display};
@@ -8,24 +8,51 @@ use std::fmt::{Debug, Display};
 impl BinOpKind {
     pub const fn precedence(&self) -> u8 {
         match self {
-            Self::Add | BinOpKind::Sub => 4,
-            Self::Mul | BinOpKind::Div => 5,
+            Self::Mul | BinOpKind::Div | BinOpKind::MatMul => 11,
+            Self::Add | BinOpKind::Sub => 10,
+            Self::Shl | BinOpKind::Shr => 9,
+            Self::Lt | BinOpKind::Le | BinOpKind::Gt | BinOpKind::Ge => 8,
+            Self::Eq | BinOpKind::Ne => 7,
+            Self::BitAnd => 6,
+            Self::BitXor => 5,
+            Self::BitOr => 4,
         }
     }
 }
 
 impl UnaryOpKind {
     pub const fn precedence(&self) -> u8 {
-        6
+        12
     }
 }
 
 impl<T: Debug + Display> ExprKind<T> {
     pub fn precedence(&self) -> u8 {
         match self {
+            Self::Range { .. } => 1,
+            Self::Unwrap { .. } => 2,
+            Self::Conditional { .. } | Self::Lambda { .. } => 0,
             Self::BinOp { op, .. } => op.kind.precedence(),
             Self::UnaryOp { op, .. } => op.kind.precedence(),
-            Self::Variable(_) | Self::Integer(_) | Self::Float(_) | Self::Tuple(_) => 255,
+            // conversions are transparent unless explicitly displayed
+            Self::Convert(expr) => expr.kind.precedence(),
+            Self::Variable(_)
+            | Self::Call { .. }
+            | Self::Apply { .. }
+            | Self::Integer(_)
+            | Self::Float(_)
+            | Self::Bool(_)
+            | Self::Imaginary(_)
+            | Self::None
+            | Self::Quantity { .. }
+            | Self::Tuple(_)
+            | Self::Matrix(_)
+            | Self::Record { .. }
+            | Self::Field { .. }
+            | Self::Variant { .. }
+            | Self::TupleIndex { .. }
+            | Self::Deriv { .. }
+            | Self::Eval { .. } => 255,
         }
     }
 }
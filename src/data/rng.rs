@@ -0,0 +1,41 @@
+//! Deterministic pseudo-random number generator.
+//!
+//! This is the SplitMix64 algorithm, which is also implemented by `runtime/runtime.c`, such that
+//! interpreted and compiled programs produce the exact same sequence for a given seed. Any change
+//! made here must be mirrored in the runtime.
+
+#[derive(Debug, Clone, Default)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: i32) -> Self {
+        Self {
+            state: seed as i64 as u64,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed float in `[0, 1)`.
+    pub fn next_float(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+
+    /// Uniformly distributed integer in `[lo, hi]`, or `lo` if the range is empty.
+    pub fn next_int(&mut self, lo: i32, hi: i32) -> i32 {
+        let width = hi as i64 - lo as i64 + 1;
+        if width <= 0 {
+            return lo;
+        }
+
+        (lo as i64 + (self.next_u64() % width as u64) as i64) as i32
+    }
+}
@@ -5,19 +5,29 @@ use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Loc {
+    /// index of the source file, see [`Sources`](crate::context::source::Sources)
+    pub source: usize,
     pub line: usize,
     pub col: usize,
 }
 
 impl Default for Loc {
     fn default() -> Self {
-        Self { line: 1, col: 0 }
+        Self {
+            source: 0,
+            line: 1,
+            col: 0,
+        }
     }
 }
 
 impl Loc {
     pub fn new(line: usize, col: usize) -> Self {
-        Self { line, col }
+        Self {
+            source: 0,
+            line,
+            col,
+        }
     }
 }
 
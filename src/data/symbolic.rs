@@ -0,0 +1,150 @@
+//! Symbolic expressions, e.g. `deriv(x * x, x)`.
+//!
+//! A symbolic expression is a checked float expression in a single variable, whose other
+//! variables have been replaced by their values. It is differentiated with the usual rules, and
+//! the result is simplified by folding its constants with the optimizer, then removing the neutral
+//! elements of the operators.
+
+use crate::context::checked_ast::{CheckedExpr, Type, TypeInfo};
+use crate::data::ast::{BinOp, BinOpKind, Expr, ExprKind, UnaryOp, UnaryOpKind};
+use crate::pipeline::formatter::format_expr;
+use crate::pipeline::optimizer::fold;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct Symbolic {
+    /// name of the variable
+    pub var: String,
+
+    /// expression in the variable, of type float
+    pub expr: CheckedExpr,
+}
+
+impl Symbolic {
+    pub fn new(var: impl Into<String>, expr: CheckedExpr) -> Self {
+        Self {
+            var: var.into(),
+            expr,
+        }
+    }
+
+    /// Derivative with respect to a variable, which is constant if it isn't the variable of the
+    /// expression.
+    pub fn derivative(&self, var: &str) -> Self {
+        Self::new(var, simplify(derivative(&self.expr, var)))
+    }
+}
+
+impl fmt::Display for Symbolic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_expr(&self.expr, f)
+    }
+}
+
+fn derivative(expr: &CheckedExpr, var: &str) -> CheckedExpr {
+    let meta = || TypeInfo::new(Type::Float, expr.meta.tok_span.clone());
+    let bin_op = |kind: BinOpKind, left: CheckedExpr, right: CheckedExpr| {
+        Expr::bin_op(BinOp::new(kind, meta()), left, right, meta())
+    };
+
+    // integer sub-expressions, e.g. converted ones, can't depend on the variable
+    if expr.meta.type_ != Type::Float {
+        return Expr::float(0.0, meta());
+    }
+    match &expr.kind {
+        ExprKind::Variable(name) if name.kind == var => Expr::float(1.0, meta()),
+        ExprKind::Variable(_)
+        | ExprKind::Integer(_)
+        | ExprKind::Float(_)
+        | ExprKind::Convert(_) => Expr::float(0.0, meta()),
+        ExprKind::UnaryOp { op, operand } => {
+            Expr::unary_op(op.clone(), derivative(operand, var), meta())
+        }
+        ExprKind::BinOp { op, left, right } => {
+            let (d_left, d_right) = (derivative(left, var), derivative(right, var));
+            let (left, right) = ((**left).clone(), (**right).clone());
+            match op.kind {
+                BinOpKind::Add | BinOpKind::Sub => bin_op(op.kind, d_left, d_right),
+                BinOpKind::Mul => bin_op(
+                    BinOpKind::Add,
+                    bin_op(BinOpKind::Mul, d_left, right),
+                    bin_op(BinOpKind::Mul, left, d_right),
+                ),
+                BinOpKind::Div => bin_op(
+                    BinOpKind::Div,
+                    bin_op(
+                        BinOpKind::Sub,
+                        bin_op(BinOpKind::Mul, d_left, right.clone()),
+                        bin_op(BinOpKind::Mul, left, d_right),
+                    ),
+                    bin_op(BinOpKind::Mul, right.clone(), right),
+                ),
+                _ => unreachable!("type checker should have checked this"),
+            }
+        }
+        _ => unreachable!("type checker should have checked this"),
+    }
+}
+
+/// Simplify an expression bottom-up, such that the neutral elements removed from an operand can
+/// make its parent foldable.
+fn simplify(expr: CheckedExpr) -> CheckedExpr {
+    let expr = match expr.kind {
+        ExprKind::BinOp { op, left, right } => {
+            Expr::bin_op(op, simplify(*left), simplify(*right), expr.meta)
+        }
+        ExprKind::UnaryOp { op, operand } => Expr::unary_op(op, simplify(*operand), expr.meta),
+        kind => Expr {
+            kind,
+            meta: expr.meta,
+        },
+    };
+
+    let expr = fold(expr);
+    match expr.kind {
+        ExprKind::BinOp { op, left, right } => match op.kind {
+            BinOpKind::Add if is_const(&left, 0.0) => *right,
+            BinOpKind::Add | BinOpKind::Sub if is_const(&right, 0.0) => *left,
+            BinOpKind::Sub if is_const(&left, 0.0) => simplify(Expr::unary_op(
+                UnaryOp::new(UnaryOpKind::Neg, op.meta),
+                *right,
+                expr.meta,
+            )),
+            BinOpKind::Mul if is_const(&left, 0.0) || is_const(&right, 0.0) => {
+                Expr::float(0.0, expr.meta)
+            }
+            BinOpKind::Div if is_const(&left, 0.0) => Expr::float(0.0, expr.meta),
+            BinOpKind::Mul if is_const(&left, 1.0) => *right,
+            BinOpKind::Mul | BinOpKind::Div if is_const(&right, 1.0) => *left,
+            _ => Expr::bin_op(op, *left, *right, expr.meta),
+        },
+        ExprKind::UnaryOp { op, operand } => match (op.kind, operand.kind) {
+            (UnaryOpKind::Pos, kind) => Expr {
+                kind,
+                meta: operand.meta,
+            },
+            (UnaryOpKind::Neg, ExprKind::Float(fl)) => Expr::float(-fl, expr.meta),
+            (UnaryOpKind::Neg, ExprKind::UnaryOp { op: inner, operand })
+                if inner.kind == UnaryOpKind::Neg =>
+            {
+                *operand
+            }
+            (_, kind) => Expr::unary_op(
+                op,
+                Expr {
+                    kind,
+                    meta: operand.meta,
+                },
+                expr.meta,
+            ),
+        },
+        kind => Expr {
+            kind,
+            meta: expr.meta,
+        },
+    }
+}
+
+fn is_const(expr: &CheckedExpr, value: f64) -> bool {
+    matches!(expr.kind, ExprKind::Float(fl) if fl == value)
+}
@@ -9,22 +9,72 @@ pub enum TokenKind {
     Name(String),
     Int(i32),
     Float(f64),
+    Imaginary(f64),
+    Str(String),
 
     // misc
     Semi,
     Assign,
+    PlusAssign,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
     LParen,
     RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
     Comma,
+    Colon,
+    ColonColon,
+    FatArrow,
+    Dot,
+    DotDot,
+    Question,
+    QuestionQuestion,
 
     // operators
     Plus,
     Minus,
     Star,
     Slash,
+    At,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    EqEq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
 
     // keywords
     Print,
+    Seed,
+    For,
+    In,
+    If,
+    Then,
+    Else,
+    True,
+    False,
+    Assert,
+    Exit,
+    Record,
+    Enum,
+    Match,
+    Fn,
+    None,
+    Import,
+    Use,
+    Macro,
+    Let,
+    Plot,
 }
 
 impl fmt::Display for TokenKind {
@@ -34,16 +84,66 @@ impl fmt::Display for TokenKind {
             Name(ref s) => write!(f, "'{}'", s),
             Int(i) => write!(f, "'{}'", i),
             Float(fl) => write!(f, "'{:?}'", fl),
+            Imaginary(fl) => write!(f, "'{:?}i'", fl),
+            Str(s) => write!(f, "'\"{}\"'", s),
             Semi => write!(f, "';'"),
             Assign => write!(f, "'='"),
+            PlusAssign => write!(f, "'+='"),
+            MinusAssign => write!(f, "'-='"),
+            StarAssign => write!(f, "'*='"),
+            SlashAssign => write!(f, "'/='"),
             LParen => write!(f, "'('"),
             RParen => write!(f, "')'"),
+            LBracket => write!(f, "'['"),
+            RBracket => write!(f, "']'"),
+            LBrace => write!(f, "'{{'"),
+            RBrace => write!(f, "'}}'"),
             Comma => write!(f, "','"),
+            Colon => write!(f, "':'"),
+            ColonColon => write!(f, "'::'"),
+            FatArrow => write!(f, "'=>'"),
+            Dot => write!(f, "'.'"),
+            DotDot => write!(f, "'..'"),
+            Question => write!(f, "'?'"),
+            QuestionQuestion => write!(f, "'??'"),
             Plus => write!(f, "'+'"),
             Minus => write!(f, "'-'"),
             Star => write!(f, "'*'"),
             Slash => write!(f, "'/'"),
+            At => write!(f, "'@'"),
+            Ampersand => write!(f, "'&'"),
+            Pipe => write!(f, "'|'"),
+            Caret => write!(f, "'^'"),
+            Tilde => write!(f, "'~'"),
+            ShiftLeft => write!(f, "'<<'"),
+            ShiftRight => write!(f, "'>>'"),
+            EqEq => write!(f, "'=='"),
+            NotEq => write!(f, "'!='"),
+            Lt => write!(f, "'<'"),
+            LtEq => write!(f, "'<='"),
+            Gt => write!(f, "'>'"),
+            GtEq => write!(f, "'>='"),
             Print => write!(f, "'print'"),
+            Seed => write!(f, "'seed'"),
+            For => write!(f, "'for'"),
+            In => write!(f, "'in'"),
+            If => write!(f, "'if'"),
+            Then => write!(f, "'then'"),
+            Else => write!(f, "'else'"),
+            True => write!(f, "'true'"),
+            False => write!(f, "'false'"),
+            Assert => write!(f, "'assert'"),
+            Exit => write!(f, "'exit'"),
+            Record => write!(f, "'record'"),
+            Enum => write!(f, "'enum'"),
+            Match => write!(f, "'match'"),
+            Fn => write!(f, "'fn'"),
+            Import => write!(f, "'import'"),
+            Use => write!(f, "'use'"),
+            Macro => write!(f, "'macro'"),
+            Let => write!(f, "'let'"),
+            Plot => write!(f, "'plot'"),
+            None => write!(f, "'none'"),
         }
     }
 }
@@ -8,16 +8,37 @@ use std::rc::Rc;
 pub struct TokSpan {
     pub start: Rc<Token>,
     pub end: Rc<Token>,
+
+    /// span of the macro call that this node was expanded from, if it comes from the body of a
+    /// macro definition
+    pub call_site: Option<Rc<TokSpan>>,
 }
 
 impl TokSpan {
     pub fn new(start: Rc<Token>, end: Rc<Token>) -> Self {
-        Self { start, end }
+        Self {
+            start,
+            end,
+            call_site: None,
+        }
     }
 
     pub fn span(&self) -> Span {
         Span::new(self.start.span().start, self.end.span().end)
     }
+
+    /// Span of this node once expanded from a macro called at the given span. A node already
+    /// expanded from a macro called by that macro keeps its call site, followed by the new one.
+    pub fn expanded_at(&self, call_site: &TokSpan) -> Self {
+        let call_site = match &self.call_site {
+            Some(inner) => inner.expanded_at(call_site),
+            None => call_site.clone(),
+        };
+        Self {
+            call_site: Some(Rc::new(call_site)),
+            ..self.clone()
+        }
+    }
 }
 
 impl fmt::Display for TokSpan {
@@ -30,4 +51,14 @@ impl Spanned for TokSpan {
     fn span(&self) -> Span {
         self.span()
     }
+
+    fn call_sites(&self) -> Vec<Span> {
+        let mut call_sites = Vec::new();
+        let mut tok_span = self;
+        while let Some(call_site) = &tok_span.call_site {
+            call_sites.push(call_site.span());
+            tok_span = call_site;
+        }
+        call_sites
+    }
 }
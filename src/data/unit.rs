@@ -0,0 +1,139 @@
+//! Physical units and dimensions.
+//!
+//! A [`Dimension`] is a vector of exponents over the SI base units supported by _calc_. Units are
+//! looked up by name in a static table, which provides their dimension and the scale factor to
+//! convert a value to the corresponding base unit (e.g. `km` is 1000 `m`).
+
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+
+/// Names of the base units, in the order used by [`Dimension`] (and for display).
+const BASE_UNITS: [&str; 3] = ["kg", "m", "s"];
+
+/// Exponents of each base unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Dimension([i8; 3]);
+
+impl Dimension {
+    pub const MASS: Self = Self([1, 0, 0]);
+    pub const LENGTH: Self = Self([0, 1, 0]);
+    pub const TIME: Self = Self([0, 0, 1]);
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.0.iter().all(|&e| e == 0)
+    }
+
+    /// Name of the base unit if this dimension is exactly one of them.
+    pub fn base_unit(&self) -> Option<&'static str> {
+        BASE_UNITS
+            .iter()
+            .enumerate()
+            .find(|(i, _)| {
+                self.0
+                    .iter()
+                    .enumerate()
+                    .all(|(j, &e)| e == if *i == j { 1 } else { 0 })
+            })
+            .map(|(_, name)| *name)
+    }
+
+    fn zip_with(self, other: Self, f: impl Fn(i8, i8) -> i8) -> Self {
+        let mut res = [0; 3];
+        for (i, r) in res.iter_mut().enumerate() {
+            *r = f(self.0[i], other.0[i]);
+        }
+        Self(res)
+    }
+}
+
+// Multiplying quantities adds their dimensions, dividing them subtracts them.
+impl Add for Dimension {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.zip_with(other, |a, b| a + b)
+    }
+}
+
+impl Sub for Dimension {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.zip_with(other, |a, b| a - b)
+    }
+}
+
+impl Neg for Dimension {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::default() - self
+    }
+}
+
+/// Displays the dimension as a unit expression, e.g. `kg*m/s^2`.
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let part = |name: &str, exp: i8| {
+            if exp == 1 {
+                name.to_string()
+            } else {
+                format!("{}^{}", name, exp)
+            }
+        };
+
+        let num: Vec<_> = BASE_UNITS
+            .iter()
+            .zip(self.0)
+            .filter(|(_, e)| *e > 0)
+            .map(|(name, e)| part(name, e))
+            .collect();
+        let den: Vec<_> = BASE_UNITS
+            .iter()
+            .zip(self.0)
+            .filter(|(_, e)| *e < 0)
+            .map(|(name, e)| part(name, -e))
+            .collect();
+
+        if num.is_empty() {
+            write!(f, "1")?;
+        } else {
+            write!(f, "{}", num.join("*"))?;
+        }
+
+        match den.len() {
+            0 => Ok(()),
+            1 => write!(f, "/{}", den[0]),
+            _ => write!(f, "/({})", den.join("*")),
+        }
+    }
+}
+
+/// A unit that can be attached to a literal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub dim: Dimension,
+
+    /// factor to convert a value in this unit to the base unit
+    pub scale: f64,
+}
+
+impl Unit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (dim, scale) = match name {
+            "kg" => (Dimension::MASS, 1.0),
+            "g" => (Dimension::MASS, 1e-3),
+            "m" => (Dimension::LENGTH, 1.0),
+            "km" => (Dimension::LENGTH, 1e3),
+            "cm" => (Dimension::LENGTH, 1e-2),
+            "mm" => (Dimension::LENGTH, 1e-3),
+            "s" => (Dimension::TIME, 1.0),
+            "ms" => (Dimension::TIME, 1e-3),
+            "min" => (Dimension::TIME, 60.0),
+            "h" => (Dimension::TIME, 3600.0),
+            _ => return None,
+        };
+
+        Some(Self { dim, scale })
+    }
+}
@@ -5,7 +5,7 @@
 //!
 //! Built by [`Source::error_message`].
 
-use crate::context::source::Source;
+use crate::context::source::Sources;
 use crate::data::meta::Meta;
 use crate::data::span::Span;
 use std::fmt;
@@ -41,43 +41,76 @@ impl From<String> for ErrorSpan {
 pub trait Spanned {
     fn span(&self) -> Span;
 
-    fn to_error(&self, source: &Source) -> ErrorSpan {
-        if source.source().is_empty() {
-            return "".into();
-        }
+    /// Spans of the macro calls that the object was expanded from, innermost first.
+    fn call_sites(&self) -> Vec<Span> {
+        Vec::new()
+    }
 
+    fn to_error(&self, sources: &Sources) -> ErrorSpan {
         let span = self.span();
+        if sources.get(span.start.source).source().is_empty() {
+            return "".into();
+        }
 
-        let extract: String = source
-            .source()
-            .split('\n')
-            .skip(span.start.line - 1)
-            .take(span.end.line - span.start.line + 1)
-            .enumerate()
-            .map(|(i, line)| {
-                let cur_line = i + span.start.line;
-                let start = if cur_line == span.start.line {
-                    span.start.col
-                } else {
-                    1
-                };
-                let end = if cur_line == span.end.line {
-                    span.end.col
-                } else {
-                    line.len()
-                };
-
-                let underline = " ".repeat(start - 1) + &"^".repeat(end - start + 1);
-                format!("{:>4} | {}\n     | {}\n", cur_line, line, underline)
-            })
-            .collect();
-
-        extract.into()
+        // code expanded from a macro is shown in its definition, then where it is called
+        let mut message = extract(sources, span);
+        for call_site in self.call_sites() {
+            message += "     = expanded from the macro call\n";
+            message += &extract(sources, call_site);
+        }
+        message.into()
     }
 }
 
+/// The source extract of a span, with its underline.
+fn extract(sources: &Sources, span: Span) -> String {
+    let source = sources.get(span.start.source);
+
+    // the file is only named if the program has several, or if it isn't the main one
+    let header = if span.start.source != 0 || sources.has_imports() {
+        format!(
+            "  --> {}:{}:{}\n",
+            source.name(),
+            span.start.line,
+            span.start.col
+        )
+    } else {
+        String::new()
+    };
+
+    let extract: String = source
+        .source()
+        .split('\n')
+        .skip(span.start.line - 1)
+        .take(span.end.line - span.start.line + 1)
+        .enumerate()
+        .map(|(i, line)| {
+            let cur_line = i + span.start.line;
+            let start = if cur_line == span.start.line {
+                span.start.col
+            } else {
+                1
+            };
+            let end = if cur_line == span.end.line {
+                span.end.col
+            } else {
+                line.len()
+            };
+
+            let underline = " ".repeat(start - 1) + &"^".repeat(end - start + 1);
+            format!("{:>4} | {}\n     | {}\n", cur_line, line, underline)
+        })
+        .collect();
+
+    header + &extract
+}
+
 impl<K, M: Spanned> Spanned for Meta<K, M> {
     fn span(&self) -> Span {
         self.meta.span()
     }
+
+    fn call_sites(&self) -> Vec<Span> {
+        self.meta.call_sites()
+    }
 }
@@ -1,6 +1,7 @@
 pub mod error_message;
 
 use crate::context::checked_ast::Type;
+use crate::data::ast::BinOpKind;
 use crate::data::token::TokenKind;
 use error_message::ErrorSpan;
 use thiserror::Error;
@@ -25,8 +26,29 @@ pub enum SyntaxError {
     #[error("unknown variable {0}")]
     UnknownVariable(String),
 
+    #[error("unknown function {0}")]
+    UnknownFunction(String),
+
+    #[error("unknown unit {0}")]
+    UnknownUnit(String),
+
+    #[error("unknown type {0}")]
+    UnknownType(String),
+
+    #[error("module {0} has no member {1}")]
+    UnknownMember(String, String),
+
+    #[error("module {0} has no member {1}, did you mean {2}?")]
+    UnknownMemberSuggestion(String, String, String),
+
     #[error("empty tuple")]
     EmptyTuple,
+
+    #[error("unterminated string")]
+    UnterminatedString,
+
+    #[error("expected an assignment to plot, e.g. y = x * x")]
+    NothingToPlot,
 }
 
 /// Type errors
@@ -38,13 +60,230 @@ pub enum TypeError {
     #[error("mismatched types {0} and {1} for binary operator")]
     MismatchedTypesForBinaryOp(Type, Type),
 
-    #[error("heterogeneous tuple")]
-    HeterogeneousTuple,
+    #[error("type {0} does not support operator {1}")]
+    UnsupportedComparison(Type, BinOpKind),
+
+    #[error("matrix elements must be float, found {0}")]
+    InvalidMatrixElement(Type),
+
+    #[error("matrix rows must all have the same length")]
+    RaggedMatrix,
+
+    #[error("non-conforming matrix shapes {1} and {2} for operator {0}")]
+    NonConformingShapes(BinOpKind, Type, Type),
+
+    #[error("expected a square matrix, found {0}")]
+    NonSquareMatrix(Type),
+
+    #[error("units can only be applied to float literals, found {0}")]
+    InvalidTypeForUnit(Type),
+
+    #[error("function {0} expects {1} argument(s), got {2}")]
+    WrongArgumentCount(String, usize, usize),
+
+    #[error("invalid type {1} for function {0}")]
+    InvalidArgumentType(String, Type),
+
+    #[error("range bounds must be int, found {0}")]
+    InvalidRangeBound(Type),
+
+    #[error("cannot iterate over {0}")]
+    NotIterable(Type),
+
+    #[error("cannot assign a value of type {2} to variable {0} of type {1}")]
+    InvalidCompoundAssignment(String, Type, Type),
+
+    #[error("cannot change the type of variable {0} from {1} to {2} in an inner scope")]
+    VariableTypeChange(String, Type, Type),
+
+    #[error("operator {0} requires int operands, found {1}")]
+    NonIntegerOperand(String, Type),
+
+    #[error("condition must be bool, found {0}")]
+    InvalidCondition(Type),
+
+    #[error("mismatched types {0} and {1} for conditional branches")]
+    MismatchedBranches(Type, Type),
+
+    #[error("type {0} is already defined")]
+    DuplicateType(String),
+
+    #[error("duplicate field {1} in record {0}")]
+    DuplicateField(String, String),
+
+    #[error("cannot access element {1} of {0}")]
+    InvalidTupleIndex(Type, usize),
+
+    #[error("{0} is not a record type")]
+    NotARecord(Type),
+
+    #[error("type {0} has no field {1}")]
+    UnknownField(Type, String),
+
+    #[error("missing field {1} in record {0}")]
+    MissingField(String, String),
+
+    #[error("field {0} has type {1}, found {2}")]
+    MismatchedFieldType(String, Type, Type),
+
+    #[error("duplicate variant {1} in enum {0}")]
+    DuplicateVariant(String, String),
+
+    #[error("{0} is not an enum type")]
+    NotAnEnum(Type),
+
+    #[error("enum {0} has no variant {1}")]
+    UnknownVariant(String, String),
+
+    #[error("expected a variant of {0}, found {1}")]
+    MismatchedPattern(Type, Type),
+
+    #[error("variant {0}::{1} is already matched")]
+    UnreachableArm(String, String),
+
+    #[error("non-exhaustive match on {0}, missing {1}")]
+    NonExhaustiveMatch(Type, String),
+
+    #[error("duplicate parameter {0}")]
+    DuplicateParameter(String),
+
+    #[error("function should return {0}, found {1}")]
+    MismatchedReturnType(Type, Type),
+
+    #[error("{0} is not a function")]
+    NotAFunction(Type),
+
+    #[error("cannot infer the type of parameter {0}")]
+    UntypedParameter(String),
+
+    #[error("expected a function of {0} parameters, found {1}")]
+    WrongParameterCount(usize, usize),
+
+    #[error("expected a value, found {0} which may be none; unwrap it with `?? default`")]
+    UnwrapRequired(Type),
+
+    #[error("{0} is not optional")]
+    NotOptional(Type),
+
+    #[error("cannot infer the type of none; declare it, e.g. `x: int? = none;`")]
+    UntypedNone,
+
+    #[error("{0} is declared as {1}, found {2}")]
+    MismatchedDeclaration(String, Type, Type),
+
+    #[error("default value should be {0}, found {1}")]
+    MismatchedDefault(Type, Type),
+
+    #[error("cannot destructure {0} with a tuple pattern")]
+    NotATuple(Type),
+
+    #[error("pattern of {0} elements cannot match {1}")]
+    WrongPatternLength(usize, Type),
+
+    #[error("variable {0} is bound more than once in the pattern")]
+    DuplicateBinding(String),
+
+    #[error("cannot differentiate {0}, only float expressions")]
+    NotDifferentiable(Type),
+
+    #[error("only +, -, * and / can be differentiated")]
+    UnsupportedDerivative,
+
+    #[error("{0} is not a symbolic expression")]
+    NotSymbolic(Type),
+
+    #[error("symbolic expressions are evaluated at a float, found {0}")]
+    InvalidEvalValue(Type),
+
+    #[error("only float expressions can be plotted, found {0}")]
+    NotPlottable(Type),
+
+    #[error("plots are drawn between float bounds, found {0}")]
+    InvalidPlotBound(Type),
+
+    #[error("exit status must be between 0 and 255 and not be 1, 3 or 4, found {0}")]
+    InvalidExitCode(i32),
+}
+
+/// Errors resolving the imports of a program
+#[derive(Error, Debug, PartialEq)]
+pub enum ImportError {
+    #[error("cannot read {0}: {1}")]
+    UnreadableFile(String, String),
+
+    #[error("import cycle {0}")]
+    ImportCycle(String),
+
+    #[error("module name {0} is reserved for the standard prelude")]
+    ReservedModule(String),
+}
+
+/// Errors expanding macros
+#[derive(Error, Debug, PartialEq)]
+pub enum MacroError {
+    #[error("macro {0} is already defined")]
+    DuplicateMacro(String),
+
+    #[error("duplicate parameter {0}")]
+    DuplicateParameter(String),
+
+    #[error("macro {0} expects {1} argument(s), got {2}")]
+    WrongArgumentCount(String, usize, usize),
+
+    #[error("macro {0} uses {1}, which is shadowed where it is called")]
+    ShadowedVariable(String, String),
+
+    #[error("macro {0} calls its argument {1}, which must be a function name")]
+    UncallableArgument(String, String),
+
+    #[error("macro {0} binds its argument {1}, which must be a variable name")]
+    UnboundArgument(String, String),
+}
+
+/// Runtime errors
+#[derive(Error, Debug, PartialEq)]
+pub enum RuntimeError {
+    #[error("division by zero")]
+    DivisionByZero,
+
+    #[error("integer overflow")]
+    IntegerOverflow,
+
+    #[error("expression in {0} cannot be evaluated for {1}")]
+    WrongVariable(String, String),
+
+    #[error("cannot plot over the empty interval {0}..{1}")]
+    EmptyPlotInterval(f64, f64),
+
+    #[error("cannot write plot to {0}: {1}")]
+    UnwritablePlot(String, String),
 }
 
 // =================================================================================================
 // PIPELINE ERRORS
 
+/// Errors generated while loading the source files of a program
+#[allow(clippy::enum_variant_names)]
+#[derive(Error, Debug, PartialEq)]
+pub enum LoaderError {
+    #[error("{1}Syntax error: {0}")]
+    SyntaxError(SyntaxError, ErrorSpan),
+
+    #[error("{1}Import error: {0}")]
+    ImportError(ImportError, ErrorSpan),
+
+    #[error("cannot read {0}: {1}")]
+    IOError(String, String),
+}
+
+impl From<TokenizerError> for LoaderError {
+    fn from(err: TokenizerError) -> Self {
+        match err {
+            TokenizerError::SyntaxError(err, span) => Self::SyntaxError(err, span),
+        }
+    }
+}
+
 /// Errors generated by the tokenizer
 #[derive(Error, Debug, PartialEq)]
 pub enum TokenizerError {
@@ -62,6 +301,13 @@ pub enum ParserError {
     InternalError,
 }
 
+/// Errors generated by the macro expander
+#[derive(Error, Debug, PartialEq)]
+pub enum ExpanderError {
+    #[error("{1}Macro error: {0}")]
+    MacroError(MacroError, ErrorSpan),
+}
+
 /// Errors generated by the type checker
 #[derive(Error, Debug, PartialEq)]
 pub enum CheckerError {
@@ -89,6 +335,55 @@ pub enum InterpreterError {
     #[error("{1}Type error: {0}")]
     TypeError(TypeError, ErrorSpan),
 
+    #[error("{1}Runtime error: {0}")]
+    RuntimeError(RuntimeError, ErrorSpan),
+
+    #[error("{1}Assertion failed: {0}")]
+    AssertionFailed(String, ErrorSpan),
+
+    #[error("I/O error")]
+    IOError(#[from] std::fmt::Error),
+}
+
+/// Errors generated by the LLVM code generator
+#[derive(Error, Debug, PartialEq)]
+pub enum LlvmError {
+    #[error("{1}Unsupported by the LLVM backend: {0}")]
+    Unsupported(String, ErrorSpan),
+
     #[error("I/O error")]
     IOError(#[from] std::fmt::Error),
 }
+
+// =================================================================================================
+// EXIT CODES
+//
+// Statuses of failing programs, shared by `calc run` and compiled binaries (see
+// `runtime/runtime.c`). Programs may exit with any other status using `exit(n);`.
+
+/// The program could not be compiled, or `calc` itself failed.
+pub const EXIT_ERROR: i32 = 1;
+
+/// An `assert` failed.
+pub const EXIT_ASSERTION_FAILED: i32 = 3;
+
+/// A runtime error occurred, e.g. a division by zero.
+pub const EXIT_RUNTIME_ERROR: i32 = 4;
+
+/// Whether a program may exit with a status using `exit(n);`, i.e. the status can be reported by
+/// the operating system and isn't mistaken for a failure.
+pub fn is_valid_exit_code(code: i32) -> bool {
+    (0..=255).contains(&code)
+        && ![EXIT_ERROR, EXIT_ASSERTION_FAILED, EXIT_RUNTIME_ERROR].contains(&code)
+}
+
+impl InterpreterError {
+    /// Exit status of a program stopped by this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::AssertionFailed(..) => EXIT_ASSERTION_FAILED,
+            Self::RuntimeError(..) => EXIT_RUNTIME_ERROR,
+            Self::SyntaxError(..) | Self::TypeError(..) | Self::IOError(_) => EXIT_ERROR,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(dead_code)]
 
-use crate::states::InputState;
+use crate::states::{CheckedState, InputState, Load, ParsedState};
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -20,6 +20,18 @@ struct Cli {
     /// Run the optimizer
     #[arg(short, long)]
     optimize: bool,
+
+    /// Implicitly promote int to float when mixed in binary operations
+    #[arg(short, long)]
+    promote: bool,
+
+    /// Show the conversions inserted by --promote when formatting
+    #[arg(long)]
+    show_conversions: bool,
+
+    /// Don't implicitly import the standard prelude (PI, E, TAU, X_AXIS, ...)
+    #[arg(long)]
+    no_prelude: bool,
 }
 
 #[derive(Subcommand)]
@@ -89,37 +101,96 @@ enum Commands {
         #[arg(short)]
         code: Option<String>,
     },
+
+    /// Plot the expression assigned last, e.g. `y = x * x`, to an SVG file
+    Plot {
+        /// Path to source file (or stdin if not present)
+        path: Option<PathBuf>,
+
+        /// Source code
+        #[arg(short)]
+        code: Option<String>,
+
+        /// Interval of x, e.g. 0..10
+        #[arg(long, value_name = "START..END", value_parser = parse_interval, allow_hyphen_values = true)]
+        x: (f64, f64),
+
+        /// Path to the SVG file
+        #[arg(long, default_value = "plot.svg")]
+        output: PathBuf,
+    },
 }
 
-fn get_input(path: Option<PathBuf>, code: Option<String>) -> anyhow::Result<InputState> {
+/// Parse an interval of floats, e.g. `-1.5..2`.
+fn parse_interval(interval: &str) -> Result<(f64, f64), String> {
+    let (start, end) = interval
+        .split_once("..")
+        .ok_or_else(|| format!("expected START..END, found {}", interval))?;
+    let bound = |bound: &str| {
+        bound
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("invalid bound {}", bound))
+    };
+    Ok((bound(start)?, bound(end)?))
+}
+
+/// Read the input, along with the files it imports and the prelude as requested.
+fn get_input(
+    path: Option<PathBuf>,
+    code: Option<String>,
+    load: Load,
+) -> anyhow::Result<InputState> {
     if let Some(code) = code {
-        Ok(InputState::from(code))
+        Ok(InputState::from_code(code, load)?)
     } else if let Some(path) = path {
-        Ok(InputState::from_file(path)?)
+        Ok(InputState::from_file(path, load)?)
     } else {
-        Ok(InputState::from_stdin()?)
+        Ok(InputState::from_stdin(load)?)
     }
 }
 
+/// Expand the macros of the parsed input, then type check it.
+fn check(parsed: ParsedState, promote: bool) -> anyhow::Result<CheckedState> {
+    let expanded = parsed.expand()?;
+    if promote {
+        expanded.check_promote().map_err(message)
+    } else {
+        expanded.check().map_err(message)
+    }
+}
+
+/// Turn an error into its message, for errors mentioning types: as user-defined types are shared
+/// with `Rc`, these can't be sent between threads, which `anyhow` requires.
+fn message(err: impl std::fmt::Display) -> anyhow::Error {
+    anyhow::anyhow!("{}", err)
+}
+
 fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
+    // the prelude is only needed to check the input
+    let load = if cli.no_prelude {
+        Load::Imports
+    } else {
+        Load::Prelude
+    };
 
     let mut dump = String::new();
 
     match cli.command {
         Commands::Tokenize { path, code } => {
-            let input = get_input(path, code)?;
+            let input = get_input(path, code, Load::MainOnly)?;
             let tokenized_input = input.tokenize()?;
             tokenized_input.dump(&mut dump)?;
         }
 
         Commands::Parse { path, code } => {
-            let input = get_input(path, code)?;
+            let input = get_input(path, code, if cli.optimize { load } else { Load::Imports })?;
             let tokenized_input = input.tokenize()?;
             let ast = tokenized_input.parse()?;
 
             if cli.optimize {
-                let checked = ast.check()?;
+                let checked = check(ast, cli.promote)?;
                 let optimized = checked.optimize();
                 optimized.ast.dump(&mut dump)?;
             } else {
@@ -127,23 +198,32 @@ fn main() -> anyhow::Result<()> {
             }
         }
         Commands::Format { path, code } => {
-            let input = get_input(path, code)?;
+            // only the main file is formatted, so the other files are only needed to check it
+            let checking = cli.optimize || cli.promote;
+            let input = get_input(path, code, if checking { load } else { Load::MainOnly })?;
             let tokenized_input = input.tokenize()?;
             let ast = tokenized_input.parse()?;
 
-            if cli.optimize {
-                let checked = ast.check()?;
-                let optimized = checked.optimize();
-                optimized.ast.format(&mut dump)?;
+            if checking {
+                let mut checked = check(ast, cli.promote)?;
+                if cli.optimize {
+                    checked = checked.optimize();
+                }
+
+                if cli.show_conversions {
+                    checked.ast.format_conversions(&mut dump)?;
+                } else {
+                    checked.ast.format(&mut dump)?;
+                }
             } else {
                 ast.raw_ast.format(&mut dump)?;
             }
         }
         Commands::Check { path, code } => {
-            let input = get_input(path, code)?;
+            let input = get_input(path, code, load)?;
             let tokenized_input = input.tokenize()?;
             let parsed = tokenized_input.parse()?;
-            let checked = parsed.check()?;
+            let checked = check(parsed, cli.promote)?;
 
             if cli.optimize {
                 let optimized = checked.optimize();
@@ -153,23 +233,33 @@ fn main() -> anyhow::Result<()> {
             }
         }
         Commands::Run { path, code } => {
-            let input = get_input(path, code)?;
+            let input = get_input(path, code, load)?;
             let tokenized_input = input.tokenize()?;
             let parsed = tokenized_input.parse()?;
-            let checked = parsed.check()?;
+            let checked = check(parsed, cli.promote)?;
 
-            if cli.optimize {
-                let optimized = checked.optimize();
-                optimized.interpret(&mut dump)?;
+            let res = if cli.optimize {
+                checked.optimize().interpret(&mut dump)
             } else {
-                checked.interpret(&mut dump)?;
-            }
+                checked.interpret(&mut dump)
+            };
+
+            // the output of a program is shown even if it fails, and its status is forwarded
+            println!("{}", dump);
+            let status = match res {
+                Ok(status) => status,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    err.exit_code()
+                }
+            };
+            std::process::exit(status);
         }
         Commands::Llvm { path, code } => {
-            let input = get_input(path, code)?;
+            let input = get_input(path, code, load)?;
             let tokenized_input = input.tokenize()?;
             let parsed = tokenized_input.parse()?;
-            let checked = parsed.check()?;
+            let checked = check(parsed, cli.promote)?;
 
             if cli.optimize {
                 let optimized = checked.optimize();
@@ -178,6 +268,33 @@ fn main() -> anyhow::Result<()> {
                 checked.llvm_codegen(&mut dump)?;
             }
         }
+        Commands::Plot {
+            path,
+            code,
+            x,
+            output,
+        } => {
+            // the semicolon of a single assignment may be omitted, e.g. `-c "y = x * x"`
+            let code = code.map(|code| {
+                if code.trim_end().ends_with(';') {
+                    code
+                } else {
+                    code + ";"
+                }
+            });
+            let input = get_input(path, code, load)?;
+            let tokenized_input = input.tokenize()?;
+            let parsed = tokenized_input.parse()?;
+            let plotted = parsed.plot("x", x, &output.display().to_string())?;
+            let checked = check(plotted, cli.promote)?;
+
+            // only the output of the statements preceding the plot is shown
+            if cli.optimize {
+                checked.optimize().interpret(&mut dump).map_err(message)?;
+            } else {
+                checked.interpret(&mut dump).map_err(message)?;
+            }
+        }
     }
 
     println!("{}", dump);
@@ -1,19 +1,37 @@
-use crate::context::checked_ast::{CheckedAst, CheckedExpr, CheckedStmt, Type, TypeInfo};
-use crate::data::ast::{BinOp, BinOpKind, Expr, ExprKind, Stmt, StmtKind, UnaryOp, VarName};
+use crate::context::checked_ast::{
+    CheckedAst, CheckedExpr, CheckedPattern, CheckedStmt, EnumType, RecordType, Type, TypeInfo,
+};
+use crate::context::source::PRELUDE;
+use crate::data::ast::{
+    BinOp, BinOpKind, Expr, ExprKind, Pattern, PatternKind, Stmt, StmtKind, TypeName, TypeNameKind,
+    UnaryOp, UnaryOpKind, UnitName, VarName,
+};
+use crate::data::builtin::Builtin;
 use crate::data::meta::Meta;
 use crate::data::token_span::TokSpan;
+use crate::data::unit::Unit;
 use crate::errors::error_message::Spanned;
-use crate::errors::{CheckerError, SyntaxError, TypeError};
-use crate::states::{CheckedState, ParsedState};
-use std::collections::HashMap;
+use crate::errors::{is_valid_exit_code, CheckerError, SyntaxError, TypeError};
+use crate::states::{CheckedState, ExpandedState};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 type Result<T> = std::result::Result<T, CheckerError>;
 
-pub(crate) fn check(input: ParsedState) -> Result<CheckedState> {
-    let mut checker = Checker::new(&input);
+pub(crate) fn check(input: ExpandedState) -> Result<CheckedState> {
+    run_checker(input, false)
+}
+
+/// Type check the input, inserting implicit int-to-float conversions where needed.
+pub(crate) fn check_promote(input: ExpandedState) -> Result<CheckedState> {
+    run_checker(input, true)
+}
+
+fn run_checker(input: ExpandedState, promote: bool) -> Result<CheckedState> {
+    let mut checker = Checker::new(&input, promote);
     let checked_ast = checker.run()?;
     Ok(CheckedState {
-        source: input.source,
+        sources: input.sources,
         token_stream: input.token_stream,
         raw_ast: input.raw_ast,
         ast: checked_ast,
@@ -21,24 +39,62 @@ pub(crate) fn check(input: ParsedState) -> Result<CheckedState> {
 }
 
 struct Checker<'a> {
-    input: &'a ParsedState,
+    input: &'a ExpandedState,
+
+    /// insert conversion nodes when an `int` meets a `float`
+    promote: bool,
 
     // state
-    vars: HashMap<String, Type>, //TODO: custom types may be duplicated there
+    /// variable scopes, innermost last
+    vars: Vec<HashMap<String, Type>>,
+    /// user-defined record types, in declaration order
+    types: Vec<Rc<RecordType>>,
+    /// user-defined enum types
+    enums: Vec<Rc<EnumType>>,
+    /// enclosing lambdas, innermost last: the number of variable scopes outside of each, and the
+    /// variables it captures
+    lambdas: Vec<(usize, Vec<VarName<TypeInfo>>)>,
+    /// module whose statements are being checked, whose global variables are qualified with its
+    /// name
+    module: Option<String>,
+    /// global variables of the modules checked so far, by module name
+    modules: HashMap<String, HashMap<String, Type>>,
+    /// variables of the prelude used by the program, or by the statement of the prelude being
+    /// checked
+    prelude_uses: HashSet<String>,
+    /// variables of the prelude used by each of its statements
+    prelude_deps: Vec<HashSet<String>>,
 }
 
 impl<'a> Checker<'a> {
-    fn new(input: &'a ParsedState) -> Self {
+    fn new(input: &'a ExpandedState, promote: bool) -> Self {
         Self {
             input,
-            vars: HashMap::new(),
+            promote,
+            vars: vec![HashMap::new()],
+            types: Vec::new(),
+            enums: Vec::new(),
+            lambdas: Vec::new(),
+            module: None,
+            modules: HashMap::new(),
+            prelude_uses: HashSet::new(),
+            prelude_deps: Vec::new(),
         }
     }
 
     fn run(&mut self) -> Result<CheckedAst> {
-        let mut checked_ast = CheckedAst::new();
-        for stmt in self.input.raw_ast.stmts() {
-            checked_ast.push_stmt(self.check_stmt(stmt)?);
+        let mut checked_stmts = self
+            .input
+            .raw_ast
+            .stmts()
+            .iter()
+            .map(|stmt| self.check_stmt(stmt))
+            .collect::<Result<Vec<_>>>()?;
+        self.prune_prelude(&mut checked_stmts);
+
+        let mut checked_ast = CheckedAst::new(std::mem::take(&mut self.types));
+        for stmt in checked_stmts {
+            checked_ast.push_stmt(stmt);
         }
 
         Ok(checked_ast)
@@ -47,11 +103,108 @@ impl<'a> Checker<'a> {
     fn check_stmt(&mut self, stmt: &Stmt<TokSpan>) -> Result<CheckedStmt> {
         match &stmt.kind {
             StmtKind::Assign { name, value } => {
-                let checked_value = self.check_expr(value)?;
+                // an optional variable may be assigned a plain value, or none, but keeps its type
+                let checked_value = match self.lookup_var(&name.kind).cloned() {
+                    Some(var_type @ Type::Optional(_)) => {
+                        let checked_value = self.check_expr_as(value, &var_type)?;
+                        if checked_value.meta.type_ != var_type {
+                            return Err(self.mismatch_err(
+                                &var_type,
+                                &checked_value,
+                                TypeError::MismatchedDeclaration(
+                                    name.kind.clone(),
+                                    var_type.clone(),
+                                    checked_value.meta.type_.clone(),
+                                ),
+                            ));
+                        }
+                        checked_value
+                    }
+                    _ => self.check_expr(value)?,
+                };
                 let type_ = checked_value.meta.type_.clone();
-                self.vars.insert(name.kind.clone(), type_.clone());
+                self.assign_var(name, type_.clone())?;
                 Ok(Stmt::assign(
-                    VarName::new(&name.kind, TypeInfo::new(type_, name.tok_span())),
+                    VarName::new(
+                        self.var_name(&name.kind),
+                        TypeInfo::new(type_, name.tok_span()),
+                    ),
+                    checked_value,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Let { pattern, value } => {
+                let checked_value = self.check_expr(value)?;
+                let checked_pattern = self.check_pattern(
+                    pattern,
+                    checked_value.meta.type_.clone(),
+                    &mut HashSet::new(),
+                )?;
+                Ok(Stmt::let_(
+                    checked_pattern,
+                    checked_value,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Declare {
+                name,
+                type_name,
+                value,
+            } => {
+                let checked_type_name = self.check_type_name(type_name)?;
+                let type_ = checked_type_name.meta.type_.clone();
+                let checked_value = self.check_expr_as(value, &type_)?;
+                if checked_value.meta.type_ != type_ {
+                    return Err(self.mismatch_err(
+                        &type_,
+                        &checked_value,
+                        TypeError::MismatchedDeclaration(
+                            name.kind.clone(),
+                            type_.clone(),
+                            checked_value.meta.type_.clone(),
+                        ),
+                    ));
+                }
+                self.assign_var(name, type_.clone())?;
+                Ok(Stmt::declare(
+                    VarName::new(
+                        self.var_name(&name.kind),
+                        TypeInfo::new(type_.clone(), name.tok_span()),
+                    ),
+                    checked_type_name,
+                    checked_value,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::CompoundAssign { name, op, value } => {
+                // the variable must already exist, and keep its type
+                let var_type = self.lookup_var(&name.kind).cloned().ok_or_else(|| {
+                    self.syntax_err(SyntaxError::UnknownVariable(name.kind.clone()), name)
+                })?;
+                let var_name = self.var_name(&name.kind);
+                let checked_name =
+                    || VarName::new(&var_name, TypeInfo::new(var_type.clone(), name.tok_span()));
+
+                let mut checked_var = Expr::variable(
+                    checked_name(),
+                    TypeInfo::new(var_type.clone(), name.tok_span()),
+                );
+                let mut checked_value = self.check_value(value)?;
+                if var_type.is_float_based() {
+                    resolve_float_literal(&mut checked_value);
+                }
+
+                let res_type = self.check_bin_op_type(op, &mut checked_var, &mut checked_value)?;
+                if res_type != var_type {
+                    return Err(self.type_err(
+                        TypeError::InvalidCompoundAssignment(name.kind.clone(), var_type, res_type),
+                        stmt,
+                    ));
+                }
+
+                Ok(Stmt::compound_assign(
+                    checked_name(),
+                    BinOp::new(op.kind, TypeInfo::new(res_type, op.tok_span())),
                     checked_value,
                     TypeInfo::new(Type::Stmt, stmt.tok_span()),
                 ))
@@ -70,28 +223,354 @@ impl<'a> Checker<'a> {
                     TypeInfo::new(Type::Stmt, stmt.tok_span()),
                 ))
             }
+            StmtKind::Seed { seed } => {
+                let checked_seed = self.check_value(seed)?;
+                if checked_seed.meta.type_ != Type::Integer {
+                    return Err(self.type_err(
+                        TypeError::InvalidArgumentType("seed".to_owned(), checked_seed.meta.type_),
+                        seed,
+                    ));
+                }
+                Ok(Stmt::seed(
+                    checked_seed,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Exit { code } => {
+                let checked_code = self.check_value(code)?;
+                if checked_code.meta.type_ != Type::Integer {
+                    return Err(self.type_err(
+                        TypeError::InvalidArgumentType("exit".to_owned(), checked_code.meta.type_),
+                        code,
+                    ));
+                }
+                // only a constant status can be checked, a computed one is truncated when exiting
+                let constant = match &checked_code.kind {
+                    ExprKind::Integer(n) => Some(*n),
+                    ExprKind::UnaryOp { op, operand } if op.kind == UnaryOpKind::Neg => {
+                        match operand.kind {
+                            ExprKind::Integer(n) => Some(-n),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                if let Some(n) = constant.filter(|n| !is_valid_exit_code(*n)) {
+                    return Err(self.type_err(TypeError::InvalidExitCode(n), code));
+                }
+                Ok(Stmt::exit(
+                    checked_code,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Assert { cond, message } => {
+                let checked_cond = self.check_value(cond)?;
+                if checked_cond.meta.type_ != Type::Bool {
+                    return Err(
+                        self.type_err(TypeError::InvalidCondition(checked_cond.meta.type_), cond)
+                    );
+                }
+                Ok(Stmt::assert(
+                    checked_cond,
+                    message,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::For { var, iter, body } => {
+                let checked_iter = self.check_value(iter)?;
+                let var_type = element_type(&checked_iter.meta.type_).ok_or_else(|| {
+                    self.type_err(
+                        TypeError::NotIterable(checked_iter.meta.type_.clone()),
+                        iter,
+                    )
+                })?;
+
+                // the loop variable, as well as any variable defined in the body, are scoped to
+                // the body
+                let mut scope = HashMap::new();
+                scope.insert(var.kind.clone(), var_type.clone());
+                let checked_body = self.check_block(body, scope);
+
+                Ok(Stmt::for_(
+                    VarName::new(&var.kind, TypeInfo::new(var_type, var.tok_span())),
+                    checked_iter,
+                    checked_body?,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Plot {
+                name,
+                value,
+                var,
+                start,
+                end,
+                path,
+            } => {
+                let mut bounds = vec![];
+                for bound in [start, end] {
+                    let checked_bound = self.check_expr_as(bound, &Type::Float)?;
+                    if checked_bound.meta.type_ != Type::Float {
+                        return Err(self.type_err(
+                            TypeError::InvalidPlotBound(checked_bound.meta.type_),
+                            bound,
+                        ));
+                    }
+                    bounds.push(checked_bound);
+                }
+
+                // as for a derivative, the variable is only bound by the plotted expression
+                self.vars
+                    .push(HashMap::from([(var.kind.clone(), Type::Float)]));
+                let checked_value = self.check_value(value);
+                self.vars.pop();
+                let mut checked_value = checked_value?;
+                self.coerce(&mut checked_value, &Type::Float);
+                if checked_value.meta.type_ != Type::Float {
+                    return Err(
+                        self.type_err(TypeError::NotPlottable(checked_value.meta.type_), value)
+                    );
+                }
+
+                let checked_end = bounds.pop().unwrap();
+                let checked_start = bounds.pop().unwrap();
+                Ok(Stmt::plot(
+                    VarName::new(&name.kind, TypeInfo::new(Type::Float, name.tok_span())),
+                    checked_value,
+                    VarName::new(&var.kind, TypeInfo::new(Type::Float, var.tok_span())),
+                    checked_start,
+                    checked_end,
+                    path,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Record { name, fields } => {
+                // types are global, and built-in types may not be redefined
+                let record_name = name.kind.to_string();
+                if self.lookup_type(&record_name).is_some() {
+                    return Err(self.type_err(TypeError::DuplicateType(record_name), name));
+                }
+
+                let mut checked_fields: Vec<(VarName<TypeInfo>, TypeName<TypeInfo>)> = Vec::new();
+                for (field, type_name) in fields {
+                    if checked_fields.iter().any(|(f, _)| f.kind == field.kind) {
+                        return Err(self.type_err(
+                            TypeError::DuplicateField(record_name, field.kind.clone()),
+                            field,
+                        ));
+                    }
+
+                    let checked_type_name = self.check_type_name(type_name)?;
+                    let type_ = checked_type_name.meta.type_.clone();
+                    checked_fields.push((
+                        VarName::new(&field.kind, TypeInfo::new(type_, field.tok_span())),
+                        checked_type_name,
+                    ));
+                }
+
+                let record = Rc::new(RecordType::new(
+                    &record_name,
+                    checked_fields
+                        .iter()
+                        .map(|(field, type_name)| {
+                            (field.kind.clone(), type_name.meta.type_.clone())
+                        })
+                        .collect(),
+                ));
+                self.types.push(record.clone());
+
+                Ok(Stmt::record(
+                    TypeName::named(
+                        record_name,
+                        TypeInfo::new(Type::Record(record), name.tok_span()),
+                    ),
+                    checked_fields,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Enum { name, variants } => {
+                let enum_name = name.kind.to_string();
+                if self.lookup_type(&enum_name).is_some() {
+                    return Err(self.type_err(TypeError::DuplicateType(enum_name), name));
+                }
+
+                let mut variant_names: Vec<String> = Vec::new();
+                for variant in variants {
+                    if variant_names.contains(&variant.kind) {
+                        return Err(self.type_err(
+                            TypeError::DuplicateVariant(enum_name, variant.kind.clone()),
+                            variant,
+                        ));
+                    }
+                    variant_names.push(variant.kind.clone());
+                }
+
+                let enum_ = Rc::new(EnumType::new(&enum_name, variant_names));
+                self.enums.push(enum_.clone());
+                let type_ = Type::Enum(enum_);
+
+                Ok(Stmt::enum_(
+                    TypeName::named(enum_name, TypeInfo::new(type_.clone(), name.tok_span())),
+                    variants
+                        .iter()
+                        .map(|v| VarName::new(&v.kind, TypeInfo::new(type_.clone(), v.tok_span())))
+                        .collect(),
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Match { expr, arms } => {
+                let checked_expr = self.check_value(expr)?;
+                let enum_ = match &checked_expr.meta.type_ {
+                    Type::Enum(enum_) => enum_.clone(),
+                    type_ => return Err(self.type_err(TypeError::NotAnEnum(type_.clone()), expr)),
+                };
+
+                let mut matched = vec![false; enum_.variants.len()];
+                let mut checked_arms = Vec::new();
+                for (pattern, body) in arms {
+                    let checked_pattern = self.check_expr(pattern)?;
+                    let index = match &checked_pattern.kind {
+                        ExprKind::Variant { variant, .. }
+                            if checked_pattern.meta.type_ == checked_expr.meta.type_ =>
+                        {
+                            enum_.variant(&variant.kind).expect("variant was checked")
+                        }
+                        _ => {
+                            return Err(self.type_err(
+                                TypeError::MismatchedPattern(
+                                    checked_expr.meta.type_.clone(),
+                                    checked_pattern.meta.type_,
+                                ),
+                                pattern,
+                            ))
+                        }
+                    };
+                    if matched[index] {
+                        return Err(self.type_err(
+                            TypeError::UnreachableArm(
+                                enum_.name.clone(),
+                                enum_.variants[index].clone(),
+                            ),
+                            pattern,
+                        ));
+                    }
+                    matched[index] = true;
+
+                    // as for loops, variables defined in an arm are scoped to it
+                    let checked_body = self.check_block(body, HashMap::new())?;
+                    checked_arms.push((checked_pattern, checked_body));
+                }
+
+                let missing: Vec<_> = enum_
+                    .variants
+                    .iter()
+                    .zip(matched)
+                    .filter(|(_, matched)| !matched)
+                    .map(|(variant, _)| format!("{}::{}", enum_.name, variant))
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(self.type_err(
+                        TypeError::NonExhaustiveMatch(
+                            checked_expr.meta.type_.clone(),
+                            missing.join(", "),
+                        ),
+                        expr,
+                    ));
+                }
+
+                Ok(Stmt::match_(
+                    checked_expr,
+                    checked_arms,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            // the imported file and the used module were merged before this one
+            StmtKind::Import { path } => Ok(Stmt::import(
+                path,
+                TypeInfo::new(Type::Stmt, stmt.tok_span()),
+            )),
+            StmtKind::Use { module } => Ok(Stmt::use_(
+                module,
+                TypeInfo::new(Type::Stmt, stmt.tok_span()),
+            )),
+            StmtKind::Module { name, body } => {
+                // the variables of a module live in a global scope of their own, kept to check
+                // its members used by the files after it
+                let outer_vars = std::mem::replace(&mut self.vars, vec![HashMap::new()]);
+                self.module = Some(name.clone());
+                let is_prelude = self.is_prelude(stmt);
+                let mut checked_body = Vec::new();
+                for stmt in body {
+                    checked_body.push(self.check_stmt(stmt)?);
+                    if is_prelude {
+                        let uses = std::mem::take(&mut self.prelude_uses);
+                        self.prelude_deps.push(uses);
+                    }
+                }
+                self.module = None;
+                let module_vars = std::mem::replace(&mut self.vars, outer_vars);
+                self.modules
+                    .insert(name.clone(), module_vars.into_iter().next().unwrap());
+
+                Ok(Stmt::module(
+                    name,
+                    checked_body,
+                    TypeInfo::new(Type::Stmt, stmt.tok_span()),
+                ))
+            }
+            StmtKind::Macro { .. } => unreachable!("macros are expanded before type checking"),
         }
     }
 
+    /// Check the statements of a block, within a new scope holding the given variables.
+    fn check_block(
+        &mut self,
+        body: &[Stmt<TokSpan>],
+        scope: HashMap<String, Type>,
+    ) -> Result<Vec<CheckedStmt>> {
+        self.vars.push(scope);
+        let checked_body = body
+            .iter()
+            .map(|stmt| self.check_stmt(stmt))
+            .collect::<Result<Vec<_>>>();
+        self.vars.pop();
+        checked_body
+    }
+
     fn check_expr(&mut self, expr: &Expr<TokSpan>) -> Result<CheckedExpr> {
         match &expr.kind {
             ExprKind::Variable(name) => {
-                if let Some(type_) = self.vars.get(&name.kind) {
+                if let Some(type_) = self.use_var(name) {
                     Ok(Expr::variable(
-                        VarName::new(&name.kind, TypeInfo::new(type_.clone(), name.tok_span())),
-                        TypeInfo::new(type_.clone(), expr.tok_span()),
+                        VarName::new(
+                            self.var_name(&name.kind),
+                            TypeInfo::new(type_.clone(), name.tok_span()),
+                        ),
+                        TypeInfo::new(type_, expr.tok_span()),
                     ))
                 } else {
                     Err(self.syntax_err(SyntaxError::UnknownVariable(name.kind.clone()), expr))
                 }
             }
             ExprKind::BinOp { op, left, right } => {
-                let checked_left = self.check_expr(left)?;
-                let checked_right = self.check_expr(right)?;
-                let left_type = checked_left.meta.type_.clone();
-                let right_type = checked_right.meta.type_.clone();
+                let (mut checked_left, mut checked_right) =
+                    if matches!(op.kind, BinOpKind::Eq | BinOpKind::Ne) {
+                        self.check_equality_operands(left, right)?
+                    } else {
+                        (self.check_value(left)?, self.check_value(right)?)
+                    };
+
+                // integer literals adapt to a float-based operand, except for bitwise operators
+                // which are int-only
+                if !op.kind.is_bitwise() {
+                    if checked_right.meta.type_.is_float_based() {
+                        resolve_float_literal(&mut checked_left);
+                    }
+                    if checked_left.meta.type_.is_float_based() {
+                        resolve_float_literal(&mut checked_right);
+                    }
+                }
 
-                let res_type = self.check_bin_op_type(op, &left_type, &right_type)?;
+                let res_type = self.check_bin_op_type(op, &mut checked_left, &mut checked_right)?;
 
                 Ok(Expr::bin_op(
                     BinOp::new(op.kind, TypeInfo::new(res_type.clone(), op.tok_span())),
@@ -101,10 +580,24 @@ impl<'a> Checker<'a> {
                 ))
             }
             ExprKind::UnaryOp { op, operand } => {
-                let checked_expr = self.check_expr(operand)?;
+                let checked_expr = self.check_value(operand)?;
                 let type_ = checked_expr.meta.type_.clone();
 
-                if !matches!(type_, Type::Integer | Type::Float) {
+                if op.kind == UnaryOpKind::BitNot && type_ != Type::Integer {
+                    return Err(self.type_err(
+                        TypeError::NonIntegerOperand(op.kind.to_string(), type_),
+                        &checked_expr,
+                    ));
+                }
+
+                if !matches!(
+                    type_,
+                    Type::Integer
+                        | Type::Float
+                        | Type::Complex
+                        | Type::Quantity(_)
+                        | Type::Matrix { .. }
+                ) {
                     return Err(self.type_err(TypeError::InvalidTypeForUnaryOp(type_), expr));
                 }
 
@@ -114,38 +607,211 @@ impl<'a> Checker<'a> {
                     TypeInfo::new(type_, expr.tok_span()),
                 ))
             }
+            ExprKind::Call { name, args } => self.check_call(expr, name, args),
+            ExprKind::Apply { callee, args } => self.check_apply(expr, callee, args),
             ExprKind::Tuple(exprs) => {
-                // check homogeneous
-
                 if exprs.is_empty() {
                     return Err(self.syntax_err(SyntaxError::EmptyTuple, expr));
                 }
 
-                let mut checked_exprs = Vec::new();
-                let ref_expr = self.check_expr(&exprs[0])?;
-                let type_ = ref_expr.meta.type_.clone();
-                checked_exprs.push(ref_expr);
+                let checked_exprs = exprs
+                    .iter()
+                    .map(|expr| self.check_expr(expr))
+                    .collect::<Result<Vec<_>>>()?;
 
-                for expr in &exprs[1..] {
-                    let checked_expr = self.check_expr(expr)?;
-                    if checked_expr.meta.type_ != type_ {
-                        return Err(self.type_err(TypeError::HeterogeneousTuple, expr));
-                    } else {
-                        checked_exprs.push(checked_expr);
+                // homogeneous tuples are vectors, whereas heterogeneous ones are product types
+                let type_ = checked_exprs[0].meta.type_.clone();
+                let type_ = if checked_exprs.iter().all(|e| e.meta.type_ == type_) {
+                    Type::Tuple {
+                        type_: Box::new(type_),
+                        len: exprs.len(),
                     }
-                }
+                } else {
+                    Type::Product(checked_exprs.iter().map(|e| e.meta.type_.clone()).collect())
+                };
 
                 Ok(Expr::tuple(
                     checked_exprs,
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
+            ExprKind::TupleIndex { expr: tuple, index } => {
+                let checked_tuple = self.check_value(tuple)?;
+                let type_ = match &checked_tuple.meta.type_ {
+                    Type::Tuple { type_, len } if index < len => Some((**type_).clone()),
+                    Type::Product(types) => types.get(*index).cloned(),
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    self.type_err(
+                        TypeError::InvalidTupleIndex(checked_tuple.meta.type_.clone(), *index),
+                        expr,
+                    )
+                })?;
+
+                Ok(Expr::tuple_index(
+                    checked_tuple,
+                    *index,
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
+            ExprKind::Matrix(rows) => {
+                let cols = rows[0].len();
+                let mut checked_rows = Vec::new();
+                for row in rows {
+                    if row.len() != cols {
+                        return Err(self.type_err(TypeError::RaggedMatrix, &row[0]));
+                    }
+
+                    let mut checked_row = Vec::new();
+                    for elem in row {
+                        let mut checked_elem = self.check_value(elem)?;
+                        resolve_float_literal(&mut checked_elem);
+                        if checked_elem.meta.type_ != Type::Float {
+                            return Err(self.type_err(
+                                TypeError::InvalidMatrixElement(checked_elem.meta.type_),
+                                elem,
+                            ));
+                        }
+                        checked_row.push(checked_elem);
+                    }
+                    checked_rows.push(checked_row);
+                }
+
+                Ok(Expr::matrix(
+                    checked_rows,
                     TypeInfo::new(
-                        Type::Tuple {
-                            type_: Box::new(type_),
-                            len: exprs.len(),
+                        Type::Matrix {
+                            rows: rows.len(),
+                            cols,
                         },
                         expr.tok_span(),
                     ),
                 ))
             }
+            ExprKind::Record { name, fields } => self.check_record(expr, name, fields),
+            ExprKind::Field {
+                expr: record,
+                field,
+            } => {
+                // a module member, unless shadowed by a variable
+                if let ExprKind::Variable(module) = &record.kind {
+                    if self.lookup_var(&module.kind).is_none()
+                        && self.modules.contains_key(&module.kind)
+                    {
+                        let name = VarName::new(
+                            format!("{}.{}", module.kind, field.kind),
+                            expr.tok_span(),
+                        );
+                        let type_ = self.use_member(&name)?;
+                        return Ok(Expr::variable(
+                            VarName::new(&name.kind, TypeInfo::new(type_.clone(), name.tok_span())),
+                            TypeInfo::new(type_, expr.tok_span()),
+                        ));
+                    }
+                }
+
+                let checked_record = self.check_value(record)?;
+                let type_ = match &checked_record.meta.type_ {
+                    Type::Record(record_type) => record_type
+                        .field(&field.kind)
+                        .map(|(_, type_)| type_.clone()),
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    self.type_err(
+                        TypeError::UnknownField(
+                            checked_record.meta.type_.clone(),
+                            field.kind.clone(),
+                        ),
+                        field,
+                    )
+                })?;
+
+                Ok(Expr::field(
+                    checked_record,
+                    VarName::new(&field.kind, TypeInfo::new(type_.clone(), field.tok_span())),
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
+            ExprKind::Lambda {
+                params, ret, body, ..
+            } => self.check_lambda(expr, params, ret.as_ref(), body, &[], None),
+            ExprKind::Deriv { expr: body, var } => {
+                // the variable is bound by the expression, while the other variables are read
+                // when the derivative is computed
+                self.vars
+                    .push(HashMap::from([(var.kind.clone(), Type::Float)]));
+                let checked_body = self.check_value(body);
+                self.vars.pop();
+                let mut checked_body = checked_body?;
+                self.coerce(&mut checked_body, &Type::Float);
+
+                match &checked_body.meta.type_ {
+                    Type::Float => self.check_differentiable(&checked_body)?,
+                    // higher derivatives, e.g. `deriv(deriv(x * x, x), x)`
+                    Type::Symbolic => {}
+                    type_ => {
+                        return Err(
+                            self.type_err(TypeError::NotDifferentiable(type_.clone()), &**body)
+                        )
+                    }
+                }
+                Ok(Expr::deriv(
+                    checked_body,
+                    VarName::new(&var.kind, TypeInfo::new(Type::Float, var.tok_span())),
+                    TypeInfo::new(Type::Symbolic, expr.tok_span()),
+                ))
+            }
+            ExprKind::Eval {
+                expr: symbolic,
+                var,
+                value,
+            } => {
+                let checked_symbolic = self.check_expr(symbolic)?;
+                if checked_symbolic.meta.type_ != Type::Symbolic {
+                    return Err(self.type_err(
+                        TypeError::NotSymbolic(checked_symbolic.meta.type_.clone()),
+                        &**symbolic,
+                    ));
+                }
+                let checked_value = self.check_expr_as(value, &Type::Float)?;
+                if checked_value.meta.type_ != Type::Float {
+                    return Err(self.type_err(
+                        TypeError::InvalidEvalValue(checked_value.meta.type_.clone()),
+                        &**value,
+                    ));
+                }
+                Ok(Expr::eval(
+                    checked_symbolic,
+                    VarName::new(&var.kind, TypeInfo::new(Type::Float, var.tok_span())),
+                    checked_value,
+                    TypeInfo::new(Type::Float, expr.tok_span()),
+                ))
+            }
+            ExprKind::Variant { name, variant } => {
+                let checked_name = self.check_type_name(name)?;
+                let enum_ = match &checked_name.meta.type_ {
+                    Type::Enum(enum_) => enum_.clone(),
+                    type_ => return Err(self.type_err(TypeError::NotAnEnum(type_.clone()), name)),
+                };
+                if enum_.variant(&variant.kind).is_none() {
+                    return Err(self.type_err(
+                        TypeError::UnknownVariant(enum_.name.clone(), variant.kind.clone()),
+                        variant,
+                    ));
+                }
+
+                let type_ = Type::Enum(enum_);
+                Ok(Expr::variant(
+                    checked_name,
+                    VarName::new(
+                        &variant.kind,
+                        TypeInfo::new(type_.clone(), variant.tok_span()),
+                    ),
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
             ExprKind::Integer(i) => Ok(Expr::integer(
                 *i,
                 TypeInfo::new(Type::Integer, expr.tok_span()),
@@ -154,82 +820,1801 @@ impl<'a> Checker<'a> {
                 *fl,
                 TypeInfo::new(Type::Float, expr.tok_span()),
             )),
+            ExprKind::Bool(b) => Ok(Expr::bool(*b, TypeInfo::new(Type::Bool, expr.tok_span()))),
+            ExprKind::Imaginary(fl) => Ok(Expr::imaginary(
+                *fl,
+                TypeInfo::new(Type::Complex, expr.tok_span()),
+            )),
+            ExprKind::Quantity { value, unit } => {
+                let mut checked_value = self.check_value(value)?;
+                resolve_float_literal(&mut checked_value);
+                if checked_value.meta.type_ != Type::Float {
+                    return Err(self.type_err(
+                        TypeError::InvalidTypeForUnit(checked_value.meta.type_),
+                        value.as_ref(),
+                    ));
+                }
+
+                let dim = Unit::from_name(&unit.kind)
+                    .ok_or_else(|| {
+                        self.syntax_err(SyntaxError::UnknownUnit(unit.kind.clone()), unit)
+                    })?
+                    .dim;
+                let type_ = Type::Quantity(dim);
+
+                Ok(Expr::quantity(
+                    checked_value,
+                    UnitName::new(&unit.kind, TypeInfo::new(type_.clone(), unit.tok_span())),
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
+            ExprKind::Range { start, end } => {
+                let checked_start = self.check_value(start)?;
+                let checked_end = self.check_value(end)?;
+                for (checked, raw) in [(&checked_start, start), (&checked_end, end)] {
+                    if checked.meta.type_ != Type::Integer {
+                        return Err(self.type_err(
+                            TypeError::InvalidRangeBound(checked.meta.type_.clone()),
+                            raw.as_ref(),
+                        ));
+                    }
+                }
+
+                Ok(Expr::range(
+                    checked_start,
+                    checked_end,
+                    TypeInfo::new(Type::Range, expr.tok_span()),
+                ))
+            }
+            ExprKind::Conditional {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                let checked_cond = self.check_value(cond)?;
+                if checked_cond.meta.type_ != Type::Bool {
+                    return Err(self.type_err(
+                        TypeError::InvalidCondition(checked_cond.meta.type_),
+                        cond.as_ref(),
+                    ));
+                }
+
+                // a none branch makes the result optional
+                let (mut checked_then, mut checked_else) =
+                    match (&then_branch.kind, &else_branch.kind) {
+                        (ExprKind::None, _) => {
+                            let checked_else = self.check_expr(else_branch)?;
+                            let type_ = optional(&checked_else.meta.type_);
+                            (self.check_expr_as(then_branch, &type_)?, checked_else)
+                        }
+                        (_, ExprKind::None) => {
+                            let checked_then = self.check_expr(then_branch)?;
+                            let type_ = optional(&checked_then.meta.type_);
+                            (checked_then, self.check_expr_as(else_branch, &type_)?)
+                        }
+                        _ => (self.check_expr(then_branch)?, self.check_expr(else_branch)?),
+                    };
+                if let Type::Optional(_) = checked_then.meta.type_ {
+                    self.coerce(&mut checked_else, &checked_then.meta.type_.clone());
+                }
+                if let Type::Optional(_) = checked_else.meta.type_ {
+                    self.coerce(&mut checked_then, &checked_else.meta.type_.clone());
+                }
+
+                // as for binary operators, an integer branch adapts to a float-based one
+                if checked_else.meta.type_.is_float_based() {
+                    resolve_float_literal(&mut checked_then);
+                    if self.promote {
+                        convert_to_float(&mut checked_then);
+                    }
+                }
+                if checked_then.meta.type_.is_float_based() {
+                    resolve_float_literal(&mut checked_else);
+                    if self.promote {
+                        convert_to_float(&mut checked_else);
+                    }
+                }
+
+                let type_ = checked_then.meta.type_.clone();
+                if checked_else.meta.type_ != type_ {
+                    return Err(self.type_err(
+                        TypeError::MismatchedBranches(type_, checked_else.meta.type_),
+                        expr,
+                    ));
+                }
+
+                Ok(Expr::conditional(
+                    checked_cond,
+                    checked_then,
+                    checked_else,
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
+            ExprKind::None => Err(self.type_err(TypeError::UntypedNone, expr)),
+            ExprKind::Unwrap {
+                expr: optional,
+                default,
+            } => {
+                let checked_optional = self.check_expr(optional)?;
+                let Type::Optional(type_) = &checked_optional.meta.type_ else {
+                    return Err(self.type_err(
+                        TypeError::NotOptional(checked_optional.meta.type_),
+                        optional.as_ref(),
+                    ));
+                };
+                let type_ = (**type_).clone();
+
+                let checked_default = self.check_expr_as(default, &type_)?;
+                if checked_default.meta.type_ != type_ {
+                    return Err(self.mismatch_err(
+                        &type_,
+                        &checked_default,
+                        TypeError::MismatchedDefault(
+                            type_.clone(),
+                            checked_default.meta.type_.clone(),
+                        ),
+                    ));
+                }
+
+                Ok(Expr::unwrap(
+                    checked_optional,
+                    checked_default,
+                    TypeInfo::new(type_, expr.tok_span()),
+                ))
+            }
+            ExprKind::Convert(_) => {
+                unreachable!("conversions are only inserted by the type checker")
+            }
         }
     }
 
-    fn check_bin_op_type(
+    /// Check a record construction. Every field must be given exactly once, in any order.
+    fn check_record(
         &mut self,
-        op: &BinOp<TokSpan>,
-        left: &Type,
-        right: &Type,
-    ) -> Result<Type> {
-        let res_type = match (left, right) {
-            (Type::Integer, Type::Integer) => Some(Type::Integer),
-            (Type::Float, Type::Float) => Some(Type::Float),
-            // Element-wise addition/subtraction
-            (Type::Tuple { type_: t1, len: l1 }, Type::Tuple { type_: t2, len: l2 }) => {
-                if matches!(op.kind, BinOpKind::Add | BinOpKind::Sub) && t1 == t2 && l1 == l2 {
-                    Some(left.clone())
-                } else {
-                    None
-                }
+        expr: &Expr<TokSpan>,
+        name: &TypeName<TokSpan>,
+        fields: &[(VarName<TokSpan>, Expr<TokSpan>)],
+    ) -> Result<CheckedExpr> {
+        let checked_name = self.check_type_name(name)?;
+        let record = match &checked_name.meta.type_ {
+            Type::Record(record) => record.clone(),
+            type_ => return Err(self.type_err(TypeError::NotARecord(type_.clone()), name)),
+        };
+
+        let mut checked_fields: Vec<(VarName<TypeInfo>, CheckedExpr)> = Vec::new();
+        for (field, value) in fields {
+            let (_, field_type) = record.field(&field.kind).ok_or_else(|| {
+                self.type_err(
+                    TypeError::UnknownField(Type::Record(record.clone()), field.kind.clone()),
+                    field,
+                )
+            })?;
+            if checked_fields.iter().any(|(f, _)| f.kind == field.kind) {
+                return Err(self.type_err(
+                    TypeError::DuplicateField(record.name.clone(), field.kind.clone()),
+                    field,
+                ));
             }
-            // Scalar multiplication/division
-            //TODO: ugly duplication
-            (Type::Tuple { type_, len }, Type::Integer | Type::Float) => {
-                if matches!(op.kind, BinOpKind::Mul | BinOpKind::Div) {
-                    let new_type = self.check_bin_op_type(op, type_, right)?;
-                    Some(Type::Tuple {
-                        type_: Box::new(new_type),
-                        len: *len,
-                    })
-                } else {
-                    None
-                }
+
+            let checked_value = self.check_expr_as(value, field_type)?;
+            if checked_value.meta.type_ != *field_type {
+                return Err(self.mismatch_err(
+                    field_type,
+                    &checked_value,
+                    TypeError::MismatchedFieldType(
+                        field.kind.clone(),
+                        field_type.clone(),
+                        checked_value.meta.type_.clone(),
+                    ),
+                ));
             }
-            (Type::Integer | Type::Float, Type::Tuple { type_, len }) => {
-                if matches!(op.kind, BinOpKind::Mul | BinOpKind::Div) {
-                    let new_type = self.check_bin_op_type(op, left, type_)?;
-                    Some(Type::Tuple {
-                        type_: Box::new(new_type),
-                        len: *len,
-                    })
-                } else {
-                    None
+
+            checked_fields.push((
+                VarName::new(
+                    &field.kind,
+                    TypeInfo::new(field_type.clone(), field.tok_span()),
+                ),
+                checked_value,
+            ));
+        }
+
+        if let Some((missing, _)) = record
+            .fields
+            .iter()
+            .find(|(f, _)| !checked_fields.iter().any(|(c, _)| c.kind == *f))
+        {
+            return Err(self.type_err(
+                TypeError::MissingField(record.name.clone(), missing.clone()),
+                expr,
+            ));
+        }
+
+        let type_ = Type::Record(record);
+        Ok(Expr::record(
+            checked_name,
+            checked_fields,
+            TypeInfo::new(type_, expr.tok_span()),
+        ))
+    }
+
+    /// Check an anonymous function, whose body may use the variables of enclosing scopes.
+    ///
+    /// Omitted parameter types are taken from `expected_params`, and an omitted return type from
+    /// `expected_ret` or else from the body.
+    fn check_lambda(
+        &mut self,
+        expr: &Expr<TokSpan>,
+        params: &[(VarName<TokSpan>, Option<TypeName<TokSpan>>)],
+        ret: Option<&TypeName<TokSpan>>,
+        body: &Expr<TokSpan>,
+        expected_params: &[Type],
+        expected_ret: Option<&Type>,
+    ) -> Result<CheckedExpr> {
+        let mut checked_params: Vec<(VarName<TypeInfo>, Option<TypeName<TypeInfo>>)> = Vec::new();
+        for (i, (param, type_name)) in params.iter().enumerate() {
+            if checked_params.iter().any(|(p, _)| p.kind == param.kind) {
+                return Err(self.type_err(TypeError::DuplicateParameter(param.kind.clone()), param));
+            }
+            let (type_, checked_type_name) = match type_name {
+                Some(type_name) => {
+                    let checked_type_name = self.check_type_name(type_name)?;
+                    (
+                        checked_type_name.meta.type_.clone(),
+                        Some(checked_type_name),
+                    )
+                }
+                None => {
+                    let type_ = expected_params.get(i).cloned().ok_or_else(|| {
+                        self.type_err(TypeError::UntypedParameter(param.kind.clone()), param)
+                    })?;
+                    (type_, None)
                 }
+            };
+            checked_params.push((
+                VarName::new(&param.kind, TypeInfo::new(type_, param.tok_span())),
+                checked_type_name,
+            ));
+        }
+        let checked_ret = ret.map(|ret| self.check_type_name(ret)).transpose()?;
+        let ret_type = match &checked_ret {
+            Some(ret) => Some(ret.meta.type_.clone()),
+            None => expected_ret.cloned(),
+        };
+
+        // the body sees the parameters in a new scope, and any variable it uses from an outer
+        // scope is captured
+        let scope = checked_params
+            .iter()
+            .map(|(param, _)| (param.kind.clone(), param.meta.type_.clone()))
+            .collect();
+        self.lambdas.push((self.vars.len(), Vec::new()));
+        self.vars.push(scope);
+        let checked_body = match &ret_type {
+            Some(ret_type) => self.check_expr_as(body, ret_type),
+            None => self.check_expr(body),
+        };
+        self.vars.pop();
+        let (_, captures) = self.lambdas.pop().expect("pushed above");
+
+        let checked_body = checked_body?;
+        let ret_type = match ret_type {
+            Some(ret_type) => {
+                if checked_body.meta.type_ != ret_type {
+                    return Err(self.mismatch_err(
+                        &ret_type,
+                        &checked_body,
+                        TypeError::MismatchedReturnType(
+                            ret_type.clone(),
+                            checked_body.meta.type_.clone(),
+                        ),
+                    ));
+                }
+                ret_type
             }
-            _ => None,
+            None => checked_body.meta.type_.clone(),
         };
 
-        res_type.ok_or(self.type_err(
-            TypeError::MismatchedTypesForBinaryOp(left.clone(), right.clone()),
-            op,
+        let type_ = Type::Function {
+            params: checked_params
+                .iter()
+                .map(|(param, _)| param.meta.type_.clone())
+                .collect(),
+            ret: Box::new(ret_type.clone()),
+        };
+        Ok(Expr::lambda(
+            checked_params,
+            checked_ret,
+            checked_body,
+            captures,
+            TypeInfo::new(type_, expr.tok_span()),
         ))
     }
 
-    fn type_err<K>(&self, err: TypeError, node: &Meta<K, TokSpan>) -> CheckerError {
-        CheckerError::TypeError(err, node.to_error(&self.input.source))
+    /// Check a call to a function value, i.e. a variable holding a lambda.
+    fn check_closure_call(
+        &mut self,
+        expr: &Expr<TokSpan>,
+        name: &VarName<TokSpan>,
+        args: &[Expr<TokSpan>],
+        type_: Type,
+    ) -> Result<CheckedExpr> {
+        let Type::Function { params, ret } = &type_ else {
+            return Err(self.type_err(TypeError::NotAFunction(type_), name));
+        };
+        let checked_args = self.check_args(expr, &name.kind, args, params)?;
+
+        let ret = (**ret).clone();
+        Ok(Expr::call(
+            VarName::new(
+                self.var_name(&name.kind),
+                TypeInfo::new(type_, name.tok_span()),
+            ),
+            checked_args,
+            TypeInfo::new(ret, expr.tok_span()),
+        ))
     }
 
-    fn syntax_err<K>(&self, err: SyntaxError, node: &Meta<K, TokSpan>) -> CheckerError {
-        CheckerError::SyntaxError(err, node.to_error(&self.input.source))
+    /// Check a call of the function value of an expression, e.g. `t.0(1)`. The function is named
+    /// after its type in errors.
+    fn check_apply(
+        &mut self,
+        expr: &Expr<TokSpan>,
+        callee: &Expr<TokSpan>,
+        args: &[Expr<TokSpan>],
+    ) -> Result<CheckedExpr> {
+        let checked_callee = self.check_value(callee)?;
+        let Type::Function { params, ret } = &checked_callee.meta.type_ else {
+            return Err(self.type_err(
+                TypeError::NotAFunction(checked_callee.meta.type_.clone()),
+                callee,
+            ));
+        };
+        let name = checked_callee.meta.type_.to_string();
+        let checked_args = self.check_args(expr, &name, args, params)?;
+
+        let ret = (**ret).clone();
+        Ok(Expr::apply(
+            checked_callee,
+            checked_args,
+            TypeInfo::new(ret, expr.tok_span()),
+        ))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::pipeline::checker::check;
-    use crate::pipeline::parser::parse;
-    use crate::pipeline::tokenizer::tokenize;
-    use crate::states::InputState;
+    /// Check the arguments of a call of a function value against the types of its parameters.
+    fn check_args(
+        &mut self,
+        expr: &Expr<TokSpan>,
+        name: &str,
+        args: &[Expr<TokSpan>],
+        params: &[Type],
+    ) -> Result<Vec<CheckedExpr>> {
+        if args.len() != params.len() {
+            return Err(self.type_err(
+                TypeError::WrongArgumentCount(name.to_owned(), params.len(), args.len()),
+                expr,
+            ));
+        }
+
+        let mut checked_args = Vec::new();
+        for (arg, param_type) in args.iter().zip(params) {
+            let checked_arg = self.check_expr_as(arg, param_type)?;
+            if checked_arg.meta.type_ != *param_type {
+                return Err(self.mismatch_err(
+                    param_type,
+                    &checked_arg,
+                    TypeError::InvalidArgumentType(name.to_owned(), checked_arg.meta.type_.clone()),
+                ));
+            }
+            checked_args.push(checked_arg);
+        }
+        Ok(checked_args)
+    }
+
+    /// Prepare a value to be used where the given type is expected: integer literals are resolved
+    /// to floats and, in promote mode, integers are converted. A plain value is wrapped into an
+    /// optional.
+    fn coerce(&self, expr: &mut CheckedExpr, type_: &Type) {
+        if let Type::Optional(inner) = type_ {
+            self.coerce(expr, inner);
+            if expr.meta.type_ == **inner {
+                wrap_optional(expr);
+            }
+        } else if type_.is_float_based() {
+            resolve_float_literal(expr);
+            if self.promote {
+                convert_to_float(expr);
+            }
+        }
+    }
+
+    /// Check an expression used where the given type is expected, which gives its type to a none
+    /// literal.
+    fn check_expr_as(&mut self, expr: &Expr<TokSpan>, type_: &Type) -> Result<CheckedExpr> {
+        if let ExprKind::None = expr.kind {
+            return match type_ {
+                Type::Optional(_) => Ok(Expr::none(TypeInfo::new(type_.clone(), expr.tok_span()))),
+                _ => Err(self.type_err(TypeError::NotOptional(type_.clone()), expr)),
+            };
+        }
+
+        // the parameter types of a lambda may be inferred from the expected function type
+        if let (
+            ExprKind::Lambda {
+                params: lambda_params,
+                ret: lambda_ret,
+                body,
+                ..
+            },
+            Type::Function { params, ret },
+        ) = (&expr.kind, type_)
+        {
+            if lambda_params.len() == params.len() {
+                return self.check_lambda(
+                    expr,
+                    lambda_params,
+                    lambda_ret.as_ref(),
+                    body,
+                    params,
+                    Some(ret),
+                );
+            }
+        }
+
+        let mut checked_expr = self.check_expr(expr)?;
+        self.coerce(&mut checked_expr, type_);
+        Ok(checked_expr)
+    }
+
+    /// Check an expression used where a plain value is needed, which an optional isn't.
+    fn check_value(&mut self, expr: &Expr<TokSpan>) -> Result<CheckedExpr> {
+        let checked_expr = self.check_expr(expr)?;
+        if let Type::Optional(_) = checked_expr.meta.type_ {
+            return Err(self.type_err(
+                TypeError::UnwrapRequired(checked_expr.meta.type_.clone()),
+                expr,
+            ));
+        }
+        Ok(checked_expr)
+    }
+
+    /// Check the operands of an equality test: none may be compared to an optional, and an
+    /// optional to a plain value of its inner type.
+    fn check_equality_operands(
+        &mut self,
+        left: &Expr<TokSpan>,
+        right: &Expr<TokSpan>,
+    ) -> Result<(CheckedExpr, CheckedExpr)> {
+        match (&left.kind, &right.kind) {
+            (ExprKind::None, _) => {
+                let checked_right = self.check_expr(right)?;
+                let checked_left = self.check_expr_as(left, &checked_right.meta.type_)?;
+                Ok((checked_left, checked_right))
+            }
+            (_, ExprKind::None) => {
+                let checked_left = self.check_expr(left)?;
+                let checked_right = self.check_expr_as(right, &checked_left.meta.type_)?;
+                Ok((checked_left, checked_right))
+            }
+            _ => {
+                let mut checked_left = self.check_expr(left)?;
+                let mut checked_right = self.check_expr(right)?;
+                if let Type::Optional(_) = checked_left.meta.type_ {
+                    self.coerce(&mut checked_right, &checked_left.meta.type_.clone());
+                }
+                if let Type::Optional(_) = checked_right.meta.type_ {
+                    self.coerce(&mut checked_left, &checked_right.meta.type_.clone());
+                }
+                Ok((checked_left, checked_right))
+            }
+        }
+    }
+
+    /// Error for a value which doesn't have the expected type, suggesting to unwrap an optional
+    /// of that type.
+    fn mismatch_err(&self, expected: &Type, found: &CheckedExpr, err: TypeError) -> CheckerError {
+        match &found.meta.type_ {
+            Type::Optional(inner) if **inner == *expected => {
+                self.type_err(TypeError::UnwrapRequired(found.meta.type_.clone()), found)
+            }
+            _ => self.type_err(err, found),
+        }
+    }
+
+    /// Resolve a type written in the source, annotating each of its parts with its type.
+    fn check_type_name(&self, type_name: &TypeName<TokSpan>) -> Result<TypeName<TypeInfo>> {
+        let (kind, type_) = match &type_name.kind {
+            TypeNameKind::Named(name) => {
+                let type_ = self.lookup_type(name).ok_or_else(|| {
+                    self.syntax_err(SyntaxError::UnknownType(name.clone()), type_name)
+                })?;
+                (TypeNameKind::Named(name.clone()), type_)
+            }
+            TypeNameKind::Fn { params, ret } => {
+                let params = params
+                    .iter()
+                    .map(|param| self.check_type_name(param))
+                    .collect::<Result<Vec<_>>>()?;
+                let ret = self.check_type_name(ret)?;
+                let type_ = Type::Function {
+                    params: params
+                        .iter()
+                        .map(|param| param.meta.type_.clone())
+                        .collect(),
+                    ret: Box::new(ret.meta.type_.clone()),
+                };
+                (
+                    TypeNameKind::Fn {
+                        params,
+                        ret: Box::new(ret),
+                    },
+                    type_,
+                )
+            }
+            TypeNameKind::Optional(inner) => {
+                let inner = self.check_type_name(inner)?;
+                let type_ = Type::Optional(Box::new(inner.meta.type_.clone()));
+                (TypeNameKind::Optional(Box::new(inner)), type_)
+            }
+            // like tuple values, homogeneous tuple types are vectors
+            TypeNameKind::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| self.check_type_name(element))
+                    .collect::<Result<Vec<_>>>()?;
+                let first = elements[0].meta.type_.clone();
+                let type_ = if elements.iter().all(|e| e.meta.type_ == first) {
+                    Type::Tuple {
+                        type_: Box::new(first),
+                        len: elements.len(),
+                    }
+                } else {
+                    Type::Product(elements.iter().map(|e| e.meta.type_.clone()).collect())
+                };
+                (TypeNameKind::Tuple(elements), type_)
+            }
+        };
+        Ok(Meta {
+            kind,
+            meta: TypeInfo::new(type_, type_name.tok_span()),
+        })
+    }
+
+    /// Resolve the name of a type, which is either built-in or a declared record or enum.
+    fn lookup_type(&self, name: &str) -> Option<Type> {
+        match name {
+            "int" => Some(Type::Integer),
+            "float" => Some(Type::Float),
+            "bool" => Some(Type::Bool),
+            "complex" => Some(Type::Complex),
+            "range" => Some(Type::Range),
+            _ => self
+                .types
+                .iter()
+                .find(|record| record.name == name)
+                .map(|record| Type::Record(record.clone()))
+                .or_else(|| {
+                    self.enums
+                        .iter()
+                        .find(|enum_| enum_.name == name)
+                        .map(|enum_| Type::Enum(enum_.clone()))
+                }),
+        }
+    }
+
+    fn lookup_var(&self, name: &str) -> Option<&Type> {
+        self.vars.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// Look up the type of a variable used in an expression, recording it as captured by the
+    /// enclosing lambdas it is defined outside of.
+    fn use_var(&mut self, name: &VarName<TokSpan>) -> Option<Type> {
+        let scoped = self
+            .vars
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| scope.get(&name.kind).map(|type_| (depth, type_.clone())));
+
+        // the variables of the prelude are visible everywhere, unless shadowed
+        let (depth, type_, from_prelude) = match scoped {
+            Some((depth, type_)) => {
+                let from_prelude = depth == 0 && self.module.as_deref() == Some(PRELUDE);
+                (depth, type_, from_prelude)
+            }
+            None => (0, self.modules.get(PRELUDE)?.get(&name.kind)?.clone(), true),
+        };
+        let var_name = VarName::new(self.var_name(&name.kind), name.meta.clone());
+        if from_prelude {
+            self.prelude_uses.insert(var_name.kind.clone());
+        }
+
+        self.capture(depth, &var_name, &type_);
+        Some(type_)
+    }
+
+    /// Look up the type of a module member used in an expression, e.g. `geo.radius`, which is
+    /// captured by the enclosing lambdas like any global variable.
+    fn use_member(&mut self, name: &VarName<TokSpan>) -> Result<Type> {
+        let (module, member) = name.kind.split_once('.').expect("member is qualified");
+        let Some(module_vars) = self.modules.get(module) else {
+            return Err(self.syntax_err(SyntaxError::UnknownVariable(module.to_owned()), name));
+        };
+        let Some(type_) = module_vars.get(member).cloned() else {
+            let err = match suggestion(member, module_vars.keys()) {
+                Some(suggested) => SyntaxError::UnknownMemberSuggestion(
+                    module.to_owned(),
+                    member.to_owned(),
+                    suggested.to_owned(),
+                ),
+                None => SyntaxError::UnknownMember(module.to_owned(), member.to_owned()),
+            };
+            return Err(self.syntax_err(err, name));
+        };
+
+        self.capture(0, name, &type_);
+        Ok(type_)
+    }
+
+    /// Record a variable defined in the scope at the given depth as captured by the enclosing
+    /// lambdas it is defined outside of.
+    fn capture(&mut self, depth: usize, name: &VarName<TokSpan>, type_: &Type) {
+        for (outer_scopes, captures) in self.lambdas.iter_mut().rev() {
+            if depth >= *outer_scopes {
+                break;
+            }
+            if !captures.iter().any(|capture| capture.kind == name.kind) {
+                captures.push(VarName::new(
+                    &name.kind,
+                    TypeInfo::new(type_.clone(), name.tok_span()),
+                ));
+            }
+        }
+    }
+
+    /// Name of a variable in the checked AST, where the global variables of a module are
+    /// qualified with its name, e.g. `geo.radius`, so that they don't collide with those of other
+    /// files.
+    fn var_name(&self, name: &str) -> String {
+        let depth = self.vars.iter().rposition(|scope| scope.contains_key(name));
+        match (&self.module, depth) {
+            (Some(module), Some(0)) => format!("{}.{}", module, name),
+            (_, None)
+                if self
+                    .modules
+                    .get(PRELUDE)
+                    .is_some_and(|vars| vars.contains_key(name)) =>
+            {
+                format!("{}.{}", PRELUDE, name)
+            }
+            _ => name.to_owned(),
+        }
+    }
+
+    /// Remove the assignments of the prelude to variables which the program doesn't use, directly
+    /// or through other variables of the prelude, so as not to burden it with them.
+    fn prune_prelude(&mut self, stmts: &mut Vec<CheckedStmt>) {
+        let Some(index) = stmts.iter().position(|stmt| self.is_prelude(stmt)) else {
+            return;
+        };
+        let StmtKind::Module { body, .. } = &mut stmts[index].kind else {
+            unreachable!()
+        };
+
+        // the last assignment to a variable comes first, so that the variables it uses are kept
+        let mut needed = std::mem::take(&mut self.prelude_uses);
+        let mut keep = vec![true; body.len()];
+        for (i, stmt) in body.iter().enumerate().rev() {
+            if let StmtKind::Assign { name, .. } | StmtKind::Declare { name, .. } = &stmt.kind {
+                keep[i] = needed.contains(&name.kind);
+            }
+            if keep[i] {
+                needed.extend(self.prelude_deps[i].iter().cloned());
+            }
+        }
+
+        let mut keep = keep.into_iter();
+        body.retain(|_| keep.next().unwrap());
+        if body.is_empty() {
+            stmts.remove(index);
+        }
+    }
+
+    /// Whether a node comes from the standard prelude, whose module name the loader reserves.
+    fn is_prelude(&self, node: &impl Spanned) -> bool {
+        self.input
+            .sources
+            .get(node.span().start.source)
+            .is_prelude()
+    }
+
+    /// Check that an expression is made of operations which can be differentiated.
+    fn check_differentiable(&self, expr: &CheckedExpr) -> Result<()> {
+        match &expr.kind {
+            ExprKind::Variable(_) | ExprKind::Integer(_) | ExprKind::Float(_) => Ok(()),
+            ExprKind::Convert(operand) => self.check_differentiable(operand),
+            ExprKind::UnaryOp { op, operand } if op.kind != UnaryOpKind::BitNot => {
+                self.check_differentiable(operand)
+            }
+            ExprKind::BinOp { op, left, right }
+                if matches!(
+                    op.kind,
+                    BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul | BinOpKind::Div
+                ) =>
+            {
+                self.check_differentiable(left)?;
+                self.check_differentiable(right)
+            }
+            _ => Err(self.type_err(TypeError::UnsupportedDerivative, expr)),
+        }
+    }
+
+    /// Check that a pattern can destructure a value of the given type, and assign its variables.
+    fn check_pattern(
+        &mut self,
+        pattern: &Pattern<TokSpan>,
+        type_: Type,
+        bound: &mut HashSet<String>,
+    ) -> Result<CheckedPattern> {
+        let kind = match &pattern.kind {
+            PatternKind::Name(name) => {
+                if !bound.insert(name.kind.clone()) {
+                    return Err(self.type_err(TypeError::DuplicateBinding(name.kind.clone()), name));
+                }
+                self.assign_var(name, type_.clone())?;
+                PatternKind::Name(VarName::new(
+                    self.var_name(&name.kind),
+                    TypeInfo::new(type_.clone(), name.tok_span()),
+                ))
+            }
+            PatternKind::Wildcard => PatternKind::Wildcard,
+            PatternKind::Tuple(patterns) => {
+                let types = match &type_ {
+                    Type::Tuple { type_, len } => vec![(**type_).clone(); *len],
+                    Type::Product(types) => types.clone(),
+                    _ => return Err(self.type_err(TypeError::NotATuple(type_), pattern)),
+                };
+                if types.len() != patterns.len() {
+                    return Err(self.type_err(
+                        TypeError::WrongPatternLength(patterns.len(), type_),
+                        pattern,
+                    ));
+                }
+                PatternKind::Tuple(
+                    patterns
+                        .iter()
+                        .zip(types)
+                        .map(|(pattern, type_)| self.check_pattern(pattern, type_, bound))
+                        .collect::<Result<_>>()?,
+                )
+            }
+        };
+        Ok(Meta {
+            kind,
+            meta: TypeInfo::new(type_, pattern.tok_span()),
+        })
+    }
+
+    /// Record the type of an assigned variable.
+    ///
+    /// Variables are created in the innermost scope. Those of an outer scope may be assigned to,
+    /// but must keep their type, as the inner scope may be executed repeatedly.
+    fn assign_var(&mut self, name: &VarName<TokSpan>, type_: Type) -> Result<()> {
+        let outer = self.vars[..self.vars.len() - 1]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name.kind));
+        let is_local = self.vars.last().unwrap().contains_key(&name.kind);
+
+        match outer {
+            Some(outer_type) if !is_local => {
+                if *outer_type != type_ {
+                    return Err(self.type_err(
+                        TypeError::VariableTypeChange(name.kind.clone(), outer_type.clone(), type_),
+                        name,
+                    ));
+                }
+            }
+            _ => {
+                self.vars
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.kind.clone(), type_);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_call(
+        &mut self,
+        expr: &Expr<TokSpan>,
+        name: &VarName<TokSpan>,
+        args: &[Expr<TokSpan>],
+    ) -> Result<CheckedExpr> {
+        // variables shadow built-in functions
+        if let Some(type_) = self.use_var(name) {
+            return self.check_closure_call(expr, name, args, type_);
+        }
+        if let Some((record, field)) = name.kind.split_once('.') {
+            // the function held by a field of a record, unless it is a module member
+            if self.lookup_var(record).is_some() || !self.modules.contains_key(record) {
+                let callee = Expr::field(
+                    Expr::variable(VarName::new(record, name.meta.clone()), name.meta.clone()),
+                    VarName::new(field, name.meta.clone()),
+                    name.meta.clone(),
+                );
+                return self.check_apply(expr, &callee, args);
+            }
+            let type_ = self.use_member(name)?;
+            return self.check_closure_call(expr, name, args, type_);
+        }
+
+        let builtin = Builtin::from_name(&name.kind).ok_or_else(|| {
+            self.syntax_err(SyntaxError::UnknownFunction(name.kind.clone()), name)
+        })?;
+
+        if args.len() != builtin.arity() {
+            return Err(self.type_err(
+                TypeError::WrongArgumentCount(name.kind.clone(), builtin.arity(), args.len()),
+                expr,
+            ));
+        }
+        if builtin.is_higher_order() {
+            return self.check_higher_order_call(expr, name, builtin, args);
+        }
+
+        let mut checked_args = Vec::new();
+        for arg in args {
+            checked_args.push(self.check_value(arg)?);
+        }
+
+        let arg_types: Vec<_> = checked_args.iter().map(|a| a.meta.type_.clone()).collect();
+        let res_type = match (builtin, arg_types.as_slice()) {
+            (Builtin::Abs, [Type::Integer]) => Type::Integer,
+            (Builtin::Abs, [Type::Float | Type::Complex]) => Type::Float,
+            (Builtin::Abs, [Type::Quantity(dim)]) => Type::Quantity(*dim),
+            (Builtin::Re | Builtin::Im, [Type::Complex]) => Type::Float,
+            (Builtin::Transpose, [Type::Matrix { rows, cols }]) => Type::Matrix {
+                rows: *cols,
+                cols: *rows,
+            },
+            (Builtin::Det | Builtin::Inverse, [arg_type @ Type::Matrix { rows, cols }])
+                if rows != cols =>
+            {
+                return Err(self.type_err(TypeError::NonSquareMatrix(arg_type.clone()), &args[0]))
+            }
+            (Builtin::Det, [Type::Matrix { .. }]) => Type::Float,
+            (Builtin::Inverse, [arg_type @ Type::Matrix { .. }]) => arg_type.clone(),
+            (Builtin::Rand, []) => Type::Float,
+            (Builtin::RandInt, [Type::Integer, Type::Integer]) => Type::Integer,
+            (Builtin::RandInt, [Type::Integer, arg_type]) => {
+                return Err(self.type_err(
+                    TypeError::InvalidArgumentType(name.kind.clone(), arg_type.clone()),
+                    &args[1],
+                ))
+            }
+            (_, [arg_type, ..]) => {
+                return Err(self.type_err(
+                    TypeError::InvalidArgumentType(name.kind.clone(), arg_type.clone()),
+                    &args[0],
+                ))
+            }
+            (_, []) => unreachable!("built-ins without arguments always type check"),
+        };
+
+        Ok(Expr::call(
+            VarName::new(&name.kind, TypeInfo::new(res_type.clone(), name.tok_span())),
+            checked_args,
+            TypeInfo::new(res_type, expr.tok_span()),
+        ))
+    }
+
+    /// Check a call to `map`, `filter` or `fold`, whose first argument is a sequence. The types
+    /// of the parameters of an inline lambda are inferred from the element type.
+    fn check_higher_order_call(
+        &mut self,
+        expr: &Expr<TokSpan>,
+        name: &VarName<TokSpan>,
+        builtin: Builtin,
+        args: &[Expr<TokSpan>],
+    ) -> Result<CheckedExpr> {
+        let checked_seq = self.check_value(&args[0])?;
+        let seq_type = checked_seq.meta.type_.clone();
+        let elem_type = element_type(&seq_type).ok_or_else(|| {
+            self.type_err(
+                TypeError::InvalidArgumentType(name.kind.clone(), seq_type.clone()),
+                &args[0],
+            )
+        })?;
+
+        let mut checked_args = vec![checked_seq];
+        let res_type = match builtin {
+            Builtin::Map => {
+                let (checked_fn, ret) = self.check_fn_arg(name, &args[1], &[elem_type], None)?;
+                checked_args.push(checked_fn);
+                match seq_type {
+                    // mapping a tuple preserves its length
+                    Type::Tuple { len, .. } => Type::Tuple {
+                        type_: Box::new(ret),
+                        len,
+                    },
+                    _ => Type::Seq(Box::new(ret)),
+                }
+            }
+            Builtin::Filter => {
+                let (checked_fn, _) = self.check_fn_arg(
+                    name,
+                    &args[1],
+                    std::slice::from_ref(&elem_type),
+                    Some(&Type::Bool),
+                )?;
+                checked_args.push(checked_fn);
+                Type::Seq(Box::new(elem_type))
+            }
+            Builtin::Fold => {
+                let mut checked_init = self.check_expr(&args[1])?;
+                self.coerce(&mut checked_init, &elem_type);
+                let acc_type = checked_init.meta.type_.clone();
+                if matches!(acc_type, Type::Function { .. }) {
+                    return Err(self.type_err(
+                        TypeError::InvalidArgumentType(name.kind.clone(), acc_type),
+                        &args[1],
+                    ));
+                }
+                let (checked_fn, _) = self.check_fn_arg(
+                    name,
+                    &args[2],
+                    &[acc_type.clone(), elem_type],
+                    Some(&acc_type),
+                )?;
+                checked_args.push(checked_init);
+                checked_args.push(checked_fn);
+                acc_type
+            }
+            _ => unreachable!("only higher-order built-ins are checked here"),
+        };
+
+        Ok(Expr::call(
+            VarName::new(&name.kind, TypeInfo::new(res_type.clone(), name.tok_span())),
+            checked_args,
+            TypeInfo::new(res_type, expr.tok_span()),
+        ))
+    }
+
+    /// Check the function argument of a higher-order built-in, which must take the given
+    /// parameters and, if given, return the given type. Return the checked argument along with
+    /// its return type.
+    fn check_fn_arg(
+        &mut self,
+        name: &VarName<TokSpan>,
+        arg: &Expr<TokSpan>,
+        params: &[Type],
+        ret: Option<&Type>,
+    ) -> Result<(CheckedExpr, Type)> {
+        let checked_arg = match &arg.kind {
+            ExprKind::Lambda {
+                params: lambda_params,
+                ret: lambda_ret,
+                body,
+                ..
+            } => {
+                if lambda_params.len() != params.len() {
+                    return Err(self.type_err(
+                        TypeError::WrongParameterCount(params.len(), lambda_params.len()),
+                        arg,
+                    ));
+                }
+                self.check_lambda(arg, lambda_params, lambda_ret.as_ref(), body, params, ret)?
+            }
+            _ => self.check_expr(arg)?,
+        };
+
+        match &checked_arg.meta.type_ {
+            Type::Function {
+                params: arg_params,
+                ret: arg_ret,
+            } if arg_params == params && ret.is_none_or(|ret| **arg_ret == *ret) => {
+                let arg_ret = (**arg_ret).clone();
+                Ok((checked_arg, arg_ret))
+            }
+            type_ => Err(self.type_err(
+                TypeError::InvalidArgumentType(name.kind.clone(), type_.clone()),
+                arg,
+            )),
+        }
+    }
+
+    /// Check the operands of a binary operator and return the type of the result.
+    ///
+    /// In promote mode, an `int` operand meeting a float-based operand is wrapped in a conversion
+    /// node, such that later stages don't need to handle mixed types.
+    fn check_bin_op_type(
+        &mut self,
+        op: &BinOp<TokSpan>,
+        left: &mut CheckedExpr,
+        right: &mut CheckedExpr,
+    ) -> Result<Type> {
+        // bitwise operators only apply to integers, the first offending operand is blamed
+        if op.kind.is_bitwise() {
+            for operand in [&*left, &*right] {
+                if operand.meta.type_ != Type::Integer {
+                    return Err(self.type_err(
+                        TypeError::NonIntegerOperand(
+                            op.kind.to_string(),
+                            operand.meta.type_.clone(),
+                        ),
+                        operand,
+                    ));
+                }
+            }
+            return Ok(Type::Integer);
+        }
+
+        if self.promote {
+            if right.meta.type_.is_float_based() {
+                convert_to_float(left);
+            }
+            if left.meta.type_.is_float_based() {
+                convert_to_float(right);
+            }
+        }
+
+        // any optional can be tested against the `none` literal, which only compares the flags
+        let is_equality = matches!(op.kind, BinOpKind::Eq | BinOpKind::Ne);
+        let none_literal = [&*left, &*right]
+            .iter()
+            .any(|operand| matches!(operand.kind, ExprKind::None));
+        if is_equality && none_literal && matches!(left.meta.type_, Type::Optional(_)) {
+            return Ok(Type::Bool);
+        }
+
+        self.check_bin_op_types(op, &left.meta.type_, &right.meta.type_)
+    }
+
+    fn check_bin_op_types(
+        &mut self,
+        op: &BinOp<TokSpan>,
+        left: &Type,
+        right: &Type,
+    ) -> Result<Type> {
+        let res_type = match (left, right) {
+            // Comparisons apply to scalars of the same type. Real numbers are promoted to complex
+            // numbers, which as bools can only be tested for equality.
+            _ if op.kind.is_comparison() => {
+                let is_equality = matches!(op.kind, BinOpKind::Eq | BinOpKind::Ne);
+                let comparable = match (left, right) {
+                    (Type::Complex, Type::Integer | Type::Float | Type::Complex)
+                    | (Type::Integer | Type::Float, Type::Complex)
+                    | (Type::Bool, Type::Bool) => is_equality,
+                    (Type::Enum(_), _) => is_equality && left == right,
+                    // tuples are equal if all of their elements are
+                    (Type::Tuple { .. } | Type::Product(_) | Type::Optional(_), _) => {
+                        is_equality && left == right && left.supports_equality()
+                    }
+                    (Type::Integer | Type::Float | Type::Quantity(_), _) => left == right,
+                    _ => false,
+                };
+                comparable.then_some(Type::Bool)
+            }
+            // Matrix multiplication and element-wise operations
+            (Type::Matrix { rows: r1, cols: c1 }, Type::Matrix { rows: r2, cols: c2 }) => {
+                match op.kind {
+                    BinOpKind::Add | BinOpKind::Sub if (r1, c1) == (r2, c2) => Some(left.clone()),
+                    BinOpKind::MatMul if c1 == r2 => Some(Type::Matrix {
+                        rows: *r1,
+                        cols: *c2,
+                    }),
+                    BinOpKind::Mul | BinOpKind::Div => None,
+                    _ => {
+                        return Err(self.type_err(
+                            TypeError::NonConformingShapes(op.kind, left.clone(), right.clone()),
+                            op,
+                        ))
+                    }
+                }
+            }
+            (Type::Matrix { .. }, Type::Float) => {
+                matches!(op.kind, BinOpKind::Mul | BinOpKind::Div).then_some(left.clone())
+            }
+            (Type::Float, Type::Matrix { .. }) => {
+                matches!(op.kind, BinOpKind::Mul).then_some(right.clone())
+            }
+            // Only matrices can be multiplied with `@`
+            _ if op.kind == BinOpKind::MatMul => None,
+            (Type::Integer, Type::Integer) => Some(Type::Integer),
+            (Type::Float, Type::Float) => Some(Type::Float),
+            // Real numbers are promoted to complex numbers
+            (Type::Complex, Type::Integer | Type::Float | Type::Complex)
+            | (Type::Integer | Type::Float, Type::Complex) => Some(Type::Complex),
+            // Dimensional analysis: only quantities of the same dimension can be added or
+            // subtracted, whereas multiplication and division combine dimensions.
+            (Type::Quantity(d1), Type::Quantity(d2)) => match op.kind {
+                BinOpKind::Add | BinOpKind::Sub => (d1 == d2).then_some(Type::Quantity(*d1)),
+                BinOpKind::Mul => Some(Type::quantity(*d1 + *d2)),
+                BinOpKind::Div => Some(Type::quantity(*d1 - *d2)),
+                _ => None,
+            },
+            (Type::Quantity(dim), Type::Float) => match op.kind {
+                BinOpKind::Mul | BinOpKind::Div => Some(Type::Quantity(*dim)),
+                _ => None,
+            },
+            (Type::Float, Type::Quantity(dim)) => match op.kind {
+                BinOpKind::Mul => Some(Type::Quantity(*dim)),
+                BinOpKind::Div => Some(Type::Quantity(-*dim)),
+                _ => None,
+            },
+            // Element-wise addition/subtraction
+            (Type::Tuple { type_: t1, len: l1 }, Type::Tuple { type_: t2, len: l2 }) => {
+                if matches!(op.kind, BinOpKind::Add | BinOpKind::Sub) && t1 == t2 && l1 == l2 {
+                    Some(left.clone())
+                } else {
+                    None
+                }
+            }
+            // Scalar multiplication/division
+            //TODO: ugly duplication
+            (
+                Type::Tuple { type_, len },
+                Type::Integer | Type::Float | Type::Complex | Type::Quantity(_),
+            ) => {
+                if matches!(op.kind, BinOpKind::Mul | BinOpKind::Div) {
+                    let new_type = self.check_bin_op_types(op, type_, right)?;
+                    Some(Type::Tuple {
+                        type_: Box::new(new_type),
+                        len: *len,
+                    })
+                } else {
+                    None
+                }
+            }
+            (
+                Type::Integer | Type::Float | Type::Complex | Type::Quantity(_),
+                Type::Tuple { type_, len },
+            ) => {
+                if matches!(op.kind, BinOpKind::Mul | BinOpKind::Div) {
+                    let new_type = self.check_bin_op_types(op, left, type_)?;
+                    Some(Type::Tuple {
+                        type_: Box::new(new_type),
+                        len: *len,
+                    })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        // identical operand types can't be the reason of the mismatch
+        let err = if op.kind.is_comparison() && left == right {
+            TypeError::UnsupportedComparison(left.clone(), op.kind)
+        } else {
+            TypeError::MismatchedTypesForBinaryOp(left.clone(), right.clone())
+        };
+        res_type.ok_or(self.type_err(err, op))
+    }
+
+    fn type_err(&self, err: TypeError, node: &impl Spanned) -> CheckerError {
+        CheckerError::TypeError(err, node.to_error(&self.input.sources))
+    }
+
+    fn syntax_err<K>(&self, err: SyntaxError, node: &Meta<K, TokSpan>) -> CheckerError {
+        CheckerError::SyntaxError(err, node.to_error(&self.input.sources))
+    }
+}
+
+/// The candidate closest to a misspelled name, if any is close enough to be a likely typo.
+fn suggestion<'a>(name: &str, candidates: impl Iterator<Item = &'a String>) -> Option<&'a str> {
+    let max_distance = name.chars().count() / 3;
+    candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Edit distance between two strings, i.e. the number of inserted, deleted or replaced characters,
+/// or swapped adjacent ones, to go from one to the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // distances[i][j] is the distance between the first i characters of a and j characters of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            distances[i][j] = match (i, j) {
+                (0, _) => j,
+                (_, 0) => i,
+                _ => {
+                    let mut distance = (distances[i - 1][j] + 1)
+                        .min(distances[i][j - 1] + 1)
+                        .min(distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]));
+                    if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                        distance = distance.min(distances[i - 2][j - 2] + 1);
+                    }
+                    distance
+                }
+            };
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// Type of the elements of an iterable value.
+fn element_type(type_: &Type) -> Option<Type> {
+    match type_ {
+        Type::Range => Some(Type::Integer),
+        Type::Tuple { type_, .. } | Type::Seq(type_) => Some((**type_).clone()),
+        _ => None,
+    }
+}
+
+/// Make a type optional, unless it already is.
+fn optional(type_: &Type) -> Type {
+    match type_ {
+        Type::Optional(_) => type_.clone(),
+        _ => Type::Optional(Box::new(type_.clone())),
+    }
+}
+
+/// Literal-type inference step: integer literals are untyped, and take on the float type when
+/// used in a float context.
+///
+/// Only literals and constant expressions of them are affected, variables remain strictly typed.
+/// The resolved type is recorded in the checked AST, and later stages must honor it. Expressions
+/// which are not integer constants are left untouched.
+fn resolve_float_literal(expr: &mut CheckedExpr) {
+    if is_int_constant(expr) {
+        set_float_type(expr);
+    }
+}
+
+/// Wrap a plain value into a conversion to the optional of its type.
+fn wrap_optional(expr: &mut CheckedExpr) {
+    let tok_span = expr.meta.tok_span.clone();
+    let type_ = Type::Optional(Box::new(expr.meta.type_.clone()));
+    let operand = std::mem::replace(
+        expr,
+        Expr::bool(false, TypeInfo::new(Type::Bool, tok_span.clone())),
+    );
+    *expr = Expr::convert(operand, TypeInfo::new(type_, tok_span));
+}
+
+/// Wrap an `int` expression into a conversion to `float`, leaving other expressions untouched.
+///
+/// Tuples of integers are converted element-wise: the elements of a tuple literal are converted in
+/// place, other tuples are wrapped into a conversion to a tuple of floats.
+fn convert_to_float(expr: &mut CheckedExpr) {
+    let type_ = match &expr.meta.type_ {
+        Type::Integer => Type::Float,
+        Type::Tuple { type_, len } if **type_ == Type::Integer => {
+            let len = *len;
+            if let ExprKind::Tuple(elements) = &mut expr.kind {
+                elements.iter_mut().for_each(convert_to_float);
+                expr.meta.type_ = Type::Tuple {
+                    type_: Box::new(Type::Float),
+                    len,
+                };
+                return;
+            }
+            Type::Tuple {
+                type_: Box::new(Type::Float),
+                len,
+            }
+        }
+        _ => return,
+    };
+
+    let tok_span = expr.meta.tok_span.clone();
+    let operand = std::mem::replace(
+        expr,
+        Expr::float(0.0, TypeInfo::new(Type::Float, tok_span.clone())),
+    );
+    *expr = Expr::convert(operand, TypeInfo::new(type_, tok_span));
+}
+
+/// Whether an expression is an integer constant, i.e. made of integer literals, which can be
+/// computed as a float instead. As in Go, an integer division of constants truncates, so it is
+/// left out, e.g. `x * (1 / 2)` isn't `x * 0.5`.
+fn is_int_constant(expr: &CheckedExpr) -> bool {
+    if expr.meta.type_ != Type::Integer {
+        return false;
+    }
+    match &expr.kind {
+        ExprKind::Integer(_) => true,
+        ExprKind::UnaryOp { op, operand } => {
+            op.kind != UnaryOpKind::BitNot && is_int_constant(operand)
+        }
+        ExprKind::BinOp { op, left, right } => {
+            matches!(op.kind, BinOpKind::Add | BinOpKind::Sub | BinOpKind::Mul)
+                && is_int_constant(left)
+                && is_int_constant(right)
+        }
+        _ => false,
+    }
+}
+
+fn set_float_type(expr: &mut CheckedExpr) {
+    match &mut expr.kind {
+        ExprKind::UnaryOp { op, operand } => {
+            op.meta.type_ = Type::Float;
+            set_float_type(operand);
+        }
+        ExprKind::BinOp { op, left, right } => {
+            op.meta.type_ = Type::Float;
+            set_float_type(left);
+            set_float_type(right);
+        }
+        _ => {}
+    }
+    expr.meta.type_ = Type::Float;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::ast::StmtKind;
+    use crate::pipeline::checker::{check, check_promote};
+    use crate::pipeline::expander::expand;
+    use crate::pipeline::parser::parse;
+    use crate::pipeline::tokenizer::tokenize;
+    use crate::states::{InputState, Load};
+
+    /// Check an input which is expected to be rejected, returning the error message.
+    fn check_err(input: &str) -> String {
+        let tokenized = tokenize(InputState::from(input)).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        check(expand(parsed).unwrap()).err().unwrap().to_string()
+    }
 
     #[test]
     fn test_checker() {
         let input = InputState::from("a = (1.3 + 3.2) * 45.1; b = a * 3.2; print 1 + 2 * 3;");
         let tokenized = tokenize(input).unwrap();
         let parsed = parse(tokenized).unwrap();
-        let checked = check(parsed).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        insta::assert_debug_snapshot!(checked.ast);
+    }
+
+    #[test]
+    fn test_checker_units() {
+        let input = InputState::from("d = 3.0 m; t = 2.0 s; print d / t; print d + t;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let err = check(expand(parsed).unwrap()).err().unwrap();
+
+        insta::assert_snapshot!(err.to_string());
+    }
+
+    #[test]
+    fn test_checker_matrix_shapes() {
+        let input = InputState::from("a = [1.0, 2.0, 3.0; 4.0, 5.0, 6.0]; print a @ a;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let err = check(expand(parsed).unwrap()).err().unwrap();
+
+        insta::assert_snapshot!(err.to_string());
+    }
+
+    #[test]
+    fn test_checker_untyped_literals() {
+        let input = InputState::from("x = 2.5; print x * -2;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        insta::assert_debug_snapshot!(checked.ast);
+    }
+
+    #[test]
+    fn test_checker_typed_variables() {
+        let input = InputState::from("x = 2.5; n = 2; print x * 2; print x * n;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let err = check(expand(parsed).unwrap()).err().unwrap();
+
+        insta::assert_snapshot!(err.to_string());
+    }
+
+    #[test]
+    fn test_checker_promote() {
+        let input = InputState::from("n = 2; print 2.5 * n;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check_promote(expand(parsed).unwrap()).unwrap();
+
+        insta::assert_debug_snapshot!(checked.ast);
+    }
+
+    #[test]
+    fn test_checker_promote_tuple() {
+        let input = InputState::from("t = (1, 2); print t * 2.5; print (1, 2) * 2.5;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check_promote(expand(parsed).unwrap()).unwrap();
+
+        insta::assert_debug_snapshot!(checked.ast);
+    }
+
+    #[test]
+    fn test_checker_for_scope() {
+        insta::assert_snapshot!(
+            "loop variable",
+            check_err("for i in 0..3 { print i; } print i;")
+        );
+        insta::assert_snapshot!(
+            "type change",
+            check_err("s = 0; for i in 0..3 { s = 2.5; }")
+        );
+        insta::assert_snapshot!("not iterable", check_err("for x in 1.5 { print x; }"));
+        insta::assert_snapshot!("range bound", check_err("r = 0..2.5;"));
+    }
+
+    #[test]
+    fn test_checker_compound_assign() {
+        insta::assert_snapshot!("unknown variable", check_err("k += 1;"));
+        insta::assert_snapshot!("mismatched types", check_err("n = 1; n += 2.5;"));
+        insta::assert_snapshot!("dimension change", check_err("d = 2.0 m; d *= d;"));
+    }
+
+    #[test]
+    fn test_checker_bitwise() {
+        insta::assert_snapshot!("float operand", check_err("x = 1.5; print 3 & x;"));
+        insta::assert_snapshot!("float shift", check_err("print 2.0 << 1;"));
+        insta::assert_snapshot!("float complement", check_err("print ~(1 + 0.5);"));
+    }
+
+    #[test]
+    fn test_checker_conditional() {
+        let input = InputState::from("x = 1.5; y = if x > 0 then x else 0; b = 1 < 2 == true;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        insta::assert_snapshot!("invalid condition", check_err("print if 1 then 2 else 3;"));
+        insta::assert_snapshot!(
+            "mismatched branches",
+            check_err("print if true then 1 else 2.0 m;")
+        );
+        insta::assert_snapshot!("complex ordering", check_err("print 1i < 2i;"));
+    }
+
+    #[test]
+    fn test_checker_assert() {
+        let tokenized = tokenize(InputState::from(r#"assert 1, "not a bool";"#)).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let err = check(expand(parsed).unwrap()).err().unwrap();
+
+        insta::assert_snapshot!(err.to_string());
+    }
+
+    #[test]
+    fn test_checker_exit() {
+        let tokenized = tokenize(InputState::from("exit(1.5);")).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let err = check(expand(parsed).unwrap()).err().unwrap();
+
+        insta::assert_snapshot!(err.to_string());
+
+        insta::assert_snapshot!("exit status out of range", check_err("exit(300);"));
+        insta::assert_snapshot!("negative exit status", check_err("exit(-1);"));
+        insta::assert_snapshot!("reserved exit status", check_err("exit(4);"));
+    }
+
+    #[test]
+    fn test_checker_record() {
+        let input = InputState::from(
+            "record Point { x float, y float } record Segment { start Point, end Point } p = Point { y: 2, x: 1.0 }; print Segment { start: p, end: p }.end.y;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+        insta::assert_debug_snapshot!("record types", checked.ast.types());
+
+        let record = "record P { x float, y float } ";
+        insta::assert_snapshot!(
+            "duplicate type",
+            check_err(&format!("{record}record P {{ z int }}"))
+        );
+        insta::assert_snapshot!("unknown type", check_err("record Q { v vec }"));
+        insta::assert_snapshot!(
+            "missing field",
+            check_err(&format!("{record}p = P {{ x: 1.0 }};"))
+        );
+        insta::assert_snapshot!(
+            "unknown field",
+            check_err(&format!("{record}p = P {{ x: 1.0, y: 2.0 }}; print p.z;"))
+        );
+        insta::assert_snapshot!(
+            "mismatched field type",
+            check_err(&format!("{record}p = P {{ x: 1.0, y: 2i }};"))
+        );
+    }
+
+    #[test]
+    fn test_checker_product() {
+        let input = InputState::from("t = (1, 2.5, (true, 3)); print t.2.1; print t == t;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        insta::assert_snapshot!("invalid tuple index", check_err("t = (1, 2.5); print t.2;"));
+        insta::assert_snapshot!(
+            "product arithmetic",
+            check_err("t = (1, 2.5); print t + t;")
+        );
+        insta::assert_snapshot!("product ordering", check_err("t = (1, 2.5); print t < t;"));
+    }
+
+    #[test]
+    fn test_checker_match() {
+        let input = InputState::from(
+            "enum Mode { Fast, Slow } m = Mode::Slow; match m { Mode::Slow => { x = 1; } Mode::Fast => { x = 2.0; } } print m == Mode::Fast;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        let enum_ = "enum Mode { Fast, Slow, Off } m = Mode::Fast; ";
+        insta::assert_snapshot!(
+            "non-exhaustive match",
+            check_err(&format!("{enum_}match m {{ Mode::Fast => {{}} }}"))
+        );
+        insta::assert_snapshot!(
+            "unreachable arm",
+            check_err(&format!(
+                "{enum_}match m {{ Mode::Fast => {{}} Mode::Slow => {{}} Mode::Fast => {{}} }}"
+            ))
+        );
+        insta::assert_snapshot!(
+            "unknown variant",
+            check_err(&format!("{enum_}print Mode::Medium;"))
+        );
+        insta::assert_snapshot!("duplicate variant", check_err("enum E { A, B, A }"));
+        insta::assert_snapshot!(
+            "not an enum",
+            check_err(&format!("{enum_}match 1 {{ Mode::Fast => {{}} }}"))
+        );
+        insta::assert_snapshot!(
+            "mismatched pattern",
+            check_err(&format!("{enum_}enum E {{ A }} match m {{ E::A => {{}} }}"))
+        );
+    }
+
+    #[test]
+    fn test_checker_type_name() {
+        // types are resolved part by part, homogeneous tuples being vectors
+        let input = InputState::from(
+            "record P { x int } a: (int, int) = (1, 2); b: (int, P)? = none; c: (fn(P) int)? = none; d: fn() (int, float) = fn() (int, float) => (1, 2.0);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let types: Vec<_> = checked
+            .ast
+            .stmts()
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::Declare { type_name, .. } => Some(type_name.meta.type_.to_string()),
+                _ => None,
+            })
+            .collect();
+        insta::assert_debug_snapshot!(types);
+
+        insta::assert_snapshot!(
+            "unknown parameter type",
+            check_err("f: fn(int, Q) int = none;")
+        );
+    }
+
+    #[test]
+    fn test_checker_lambda() {
+        // `k` and `sq` are captured, but not the parameter `x`
+        let input = InputState::from(
+            "k = 2.0; sq = fn(x int) int => x * x; f = fn(x int, y float) float => if sq(x) > 1 then y * k else y; print f(1, 2);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        insta::assert_snapshot!(
+            "duplicate parameter",
+            check_err("f = fn(x int, x int) int => x;")
+        );
+        insta::assert_snapshot!(
+            "mismatched return type",
+            check_err("f = fn(x int) float => x;")
+        );
+        insta::assert_snapshot!(
+            "wrong argument count",
+            check_err("f = fn(x int) int => x; print f(1, 2);")
+        );
+        insta::assert_snapshot!(
+            "invalid argument type",
+            check_err("f = fn(x int) int => x; print f(2.5);")
+        );
+        insta::assert_snapshot!("not a function", check_err("x = 3; print x(2);"));
+    }
+
+    #[test]
+    fn test_checker_higher_order() {
+        // parameter types are inferred from the elements, and the initial value of `fold` is
+        // resolved to a float
+        let input = InputState::from(
+            "t = (1.5, 2.5); print map(t, x => x * 2); print filter(0..10, i => i > 5); print fold(t, 0, (a, b) => a + b);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        insta::assert_snapshot!("untyped parameter", check_err("f = x => x;"));
+        insta::assert_snapshot!("invalid sequence", check_err("print map(3, x => x);"));
+        insta::assert_snapshot!(
+            "invalid predicate",
+            check_err("print filter(0..3, x => x);")
+        );
+        insta::assert_snapshot!(
+            "wrong parameter count",
+            check_err("print fold(0..3, 0, a => a);")
+        );
+        insta::assert_snapshot!(
+            "mismatched function",
+            check_err("f = fn(x float) float => x; print map(0..3, f);")
+        );
+    }
+
+    #[test]
+    fn test_checker_optional() {
+        // plain values are wrapped where an optional is expected, and none takes its type from
+        // the context
+        let input = InputState::from(
+            "x: float? = none; x = 1; print x ?? 0; print x == none; y = if x == 1.0 then none else 2;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        insta::assert_snapshot!("unwrap required", check_err("x: int? = 1; print x + 1;"));
+        insta::assert_snapshot!(
+            "unwrap required for declaration",
+            check_err("x: int? = 1; y: int = x;")
+        );
+        insta::assert_snapshot!("not optional", check_err("x = 1; print x ?? 0;"));
+        insta::assert_snapshot!("untyped none", check_err("print none;"));
+        insta::assert_snapshot!("mismatched declaration", check_err("x: bool = 1;"));
+        insta::assert_snapshot!(
+            "mismatched default",
+            check_err("x: int? = 1; print x ?? true;")
+        );
+        insta::assert_snapshot!("mismatched assignment", check_err("x: int? = 5; x = 2.5;"));
+
+        // any optional can be tested against none, even if its values can't be compared
+        let input = InputState::from(
+            "record P { x int } p: P? = none; r: range? = 1..3; print p == none; print none != r;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        assert!(check(expand(parsed).unwrap()).is_ok());
+        insta::assert_snapshot!(
+            "unsupported comparison",
+            check_err("record P { x int } p: P? = none; q: P? = none; print p == q;")
+        );
+    }
+
+    #[test]
+    fn test_checker_module() {
+        // the global variables of a module are qualified with its name
+        let input =
+            InputState::from_file("testdata/modules/main.calc".into(), Load::Imports).unwrap();
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        let check_file_err = |path: &str| {
+            let input = InputState::from_file(path.into(), Load::Imports).unwrap();
+            let parsed = parse(tokenize(input).unwrap()).unwrap();
+            check(expand(parsed).unwrap()).err().unwrap().to_string()
+        };
+
+        insta::assert_snapshot!(
+            "unknown member with suggestion",
+            check_file_err("testdata/modules/typo.calc")
+        );
+        insta::assert_snapshot!(
+            "unknown member",
+            check_file_err("testdata/modules/unknown_member.calc")
+        );
+    }
+
+    #[test]
+    fn test_checker_prelude() {
+        // only the variables of the prelude used by the program are kept, along with those they
+        // depend on
+        let input = InputState::from_code(
+            "f = fn(r float) float => TAU * r; print E;".to_owned(),
+            Load::Prelude,
+        )
+        .unwrap();
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+    }
+
+    #[test]
+    fn test_checker_let() {
+        // the variables of a pattern take the types of the elements they match
+        let input =
+            InputState::from("t = (1, (2.5, true)); let (a, (_, c)) = t; let (x, y) = (a, a);");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
+
+        // errors are located on the pattern that doesn't match
+        insta::assert_snapshot!(
+            "wrong pattern length",
+            check_err("t = (1, (2.5, true)); let (a, (b, c, d)) = t;")
+        );
+        insta::assert_snapshot!(
+            "not a tuple",
+            check_err("t = (1, 2.5); let ((a, b), c) = t;")
+        );
+        insta::assert_snapshot!(
+            "duplicate binding",
+            check_err("let (a, (b, a)) = (1, (2, 3));")
+        );
+        insta::assert_snapshot!(
+            "pattern variable type change",
+            check_err("x = 1; for i in 0..2 { let (x, y) = (true, i); }")
+        );
+    }
+
+    #[test]
+    fn test_checker_symbolic() {
+        // the variable of a derivative is only bound in its body, as a float
+        let input = InputState::from("d = deriv(x * x + 3 * x, x); y = eval(d, x = 2.0);");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        insta::assert_debug_snapshot!(checked.ast);
 
+        insta::assert_snapshot!("not differentiable", check_err("d = deriv(x > 1.0, x);"));
+        insta::assert_snapshot!(
+            "unsupported derivative",
+            check_err("d = deriv(if x > 0.0 then x else -x, x);")
+        );
+        insta::assert_snapshot!("not symbolic", check_err("print eval(2.0, x = 1.0);"));
+        insta::assert_snapshot!(
+            "invalid eval value",
+            check_err("d = deriv(x * x, x); print eval(d, x = true);")
+        );
+    }
+
+    #[test]
+    fn test_checker_plot() {
+        // the variable is only bound in the plotted expression, and integer literals are floats
+        let input = InputState::from(r#"plot y = x * 2 for x in 0..1, "line.svg";"#);
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
         insta::assert_debug_snapshot!(checked.ast);
+
+        insta::assert_snapshot!(
+            "not plottable",
+            check_err(r#"plot y = (x, x) for x in 0..1, "line.svg";"#)
+        );
+        insta::assert_snapshot!(
+            "invalid plot bound",
+            check_err(r#"plot y = x for x in 0..true, "line.svg";"#)
+        );
     }
 }
@@ -0,0 +1,698 @@
+//! The macro expander stage.
+//!
+//! Runs between the parser and the type checker, removing macro definitions from the AST and
+//! replacing calls to them by their body, with the parameters replaced by the arguments. As this
+//! operates on the AST, an argument always keeps its meaning, e.g. `square(1 + 2)` is `(1 + 2) * (1
+//! + 2)` with `macro square(x) => x * x;`.
+//!
+//! Macros are hygienic: the variables they bind, i.e. the parameters of their lambdas, are renamed
+//! so that they can't capture those of the arguments, and using a macro where one of the variables
+//! it refers to is shadowed is an error.
+//!
+//! The nodes of an expanded body keep the span of the definition, along with that of the call, so
+//! that errors show both.
+
+use crate::context::ast::Ast;
+use crate::data::ast::{Expr, ExprKind, Stmt, StmtKind, TypeName, TypeNameKind, VarName};
+use crate::data::meta::Meta;
+use crate::data::token_span::TokSpan;
+use crate::errors::{ExpanderError, MacroError, Spanned};
+use crate::states::{ExpandedState, ParsedState};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+type Result<T> = std::result::Result<T, ExpanderError>;
+
+pub(crate) fn expand(input: ParsedState) -> Result<ExpandedState> {
+    let stmts = Expander::new(&input).expand_stmts(input.raw_ast.stmts().to_vec())?;
+
+    let mut raw_ast = Ast::new();
+    for stmt in stmts {
+        raw_ast.push_stmt(stmt);
+    }
+    Ok(ExpandedState {
+        sources: input.sources,
+        token_stream: input.token_stream,
+        raw_ast,
+    })
+}
+
+/// A macro, whose body has had the calls to previously defined macros expanded.
+struct Macro {
+    params: Vec<String>,
+    body: Expr<TokSpan>,
+}
+
+/// A call to a macro being expanded.
+struct Expansion<'a> {
+    name: &'a str,
+
+    /// arguments, by parameter name
+    args: HashMap<String, Expr<TokSpan>>,
+
+    /// new names of the variables bound by the body, innermost scope last
+    renames: Vec<HashMap<String, String>>,
+}
+
+impl Expansion<'_> {
+    fn renamed(&self, name: &str) -> Option<&String> {
+        self.renames.iter().rev().find_map(|scope| scope.get(name))
+    }
+}
+
+struct Expander<'a> {
+    input: &'a ParsedState,
+
+    // state
+    /// macros defined so far, by name
+    macros: HashMap<String, Rc<Macro>>,
+    /// variables bound by the lambdas and loops enclosing the code being expanded, innermost last
+    locals: Vec<Vec<String>>,
+    /// number of variables renamed so far, to make new names unique
+    renamed: usize,
+}
+
+impl<'a> Expander<'a> {
+    fn new(input: &'a ParsedState) -> Self {
+        Self {
+            input,
+            macros: HashMap::new(),
+            locals: Vec::new(),
+            renamed: 0,
+        }
+    }
+
+    fn expand_stmts(&mut self, stmts: Vec<Stmt<TokSpan>>) -> Result<Vec<Stmt<TokSpan>>> {
+        let mut expanded = Vec::new();
+        for stmt in stmts {
+            if let Some(stmt) = self.expand_stmt(stmt)? {
+                expanded.push(stmt);
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Expand the macro calls of a statement, or return `None` for a macro definition.
+    fn expand_stmt(&mut self, stmt: Stmt<TokSpan>) -> Result<Option<Stmt<TokSpan>>> {
+        let meta = stmt.meta;
+        let stmt = match stmt.kind {
+            StmtKind::Assign { name, value } => Stmt::assign(name, self.expand_expr(value)?, meta),
+            StmtKind::Let { pattern, value } => Stmt::let_(pattern, self.expand_expr(value)?, meta),
+            StmtKind::Declare {
+                name,
+                type_name,
+                value,
+            } => Stmt::declare(name, type_name, self.expand_expr(value)?, meta),
+            StmtKind::CompoundAssign { name, op, value } => {
+                Stmt::compound_assign(name, op, self.expand_expr(value)?, meta)
+            }
+            StmtKind::Print { expr } => Stmt::print(self.expand_expr(expr)?, meta),
+            StmtKind::Expr { expr } => Stmt::expr(self.expand_expr(expr)?, meta),
+            StmtKind::Seed { seed } => Stmt::seed(self.expand_expr(seed)?, meta),
+            StmtKind::Exit { code } => Stmt::exit(self.expand_expr(code)?, meta),
+            StmtKind::Assert { cond, message } => {
+                Stmt::assert(self.expand_expr(cond)?, message, meta)
+            }
+            StmtKind::For { var, iter, body } => {
+                let iter = self.expand_expr(iter)?;
+                self.locals.push(vec![var.kind.clone()]);
+                let body = self.expand_stmts(body);
+                self.locals.pop();
+                Stmt::for_(var, iter, body?, meta)
+            }
+            StmtKind::Plot {
+                name,
+                value,
+                var,
+                start,
+                end,
+                path,
+            } => {
+                let (start, end) = (self.expand_expr(start)?, self.expand_expr(end)?);
+                self.locals.push(vec![var.kind.clone()]);
+                let value = self.expand_expr(value);
+                self.locals.pop();
+                Stmt::plot(name, value?, var, start, end, path, meta)
+            }
+            StmtKind::Match { expr, arms } => {
+                let expr = self.expand_expr(expr)?;
+                let arms = arms
+                    .into_iter()
+                    .map(|(pattern, body)| Ok((pattern, self.expand_stmts(body)?)))
+                    .collect::<Result<_>>()?;
+                Stmt::match_(expr, arms, meta)
+            }
+            StmtKind::Module { name, body } => Stmt::module(name, self.expand_stmts(body)?, meta),
+            StmtKind::Import { path } => Stmt::import(path, meta),
+            StmtKind::Use { module } => Stmt::use_(module, meta),
+            StmtKind::Macro { name, params, body } => {
+                self.define(name, params, body)?;
+                return Ok(None);
+            }
+            kind @ (StmtKind::Record { .. } | StmtKind::Enum { .. }) => Stmt { kind, meta },
+        };
+        Ok(Some(stmt))
+    }
+
+    fn define(
+        &mut self,
+        name: VarName<TokSpan>,
+        params: Vec<VarName<TokSpan>>,
+        body: Expr<TokSpan>,
+    ) -> Result<()> {
+        if self.macros.contains_key(&name.kind) {
+            return Err(self.macro_err(MacroError::DuplicateMacro(name.kind.clone()), &name));
+        }
+        for (i, param) in params.iter().enumerate() {
+            if params[..i].iter().any(|other| other.kind == param.kind) {
+                return Err(
+                    self.macro_err(MacroError::DuplicateParameter(param.kind.clone()), param)
+                );
+            }
+        }
+
+        // the macros called by the body are expanded once and for all, the parameters shadowing
+        // the variables they refer to
+        let params: Vec<_> = params.into_iter().map(|param| param.kind).collect();
+        self.locals.push(params.clone());
+        let body = self.expand_expr(body);
+        self.locals.pop();
+
+        self.macros.insert(
+            name.kind,
+            Rc::new(Macro {
+                params,
+                body: body?,
+            }),
+        );
+        Ok(())
+    }
+
+    fn expand_expr(&mut self, expr: Expr<TokSpan>) -> Result<Expr<TokSpan>> {
+        match expr.kind {
+            ExprKind::Call { name, args } if self.macros.contains_key(&name.kind) => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.expand_expr(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                self.expand_call(&name, args, &expr.meta)
+            }
+            ExprKind::Lambda {
+                params,
+                ret,
+                body,
+                captures,
+            } => {
+                self.locals
+                    .push(params.iter().map(|(param, _)| param.kind.clone()).collect());
+                let body = self.expand_expr(*body);
+                self.locals.pop();
+                Ok(Expr::lambda(params, ret, body?, captures, expr.meta))
+            }
+            ExprKind::Deriv { expr: body, var } => {
+                self.locals.push(vec![var.kind.clone()]);
+                let body = self.expand_expr(*body);
+                self.locals.pop();
+                Ok(Expr::deriv(body?, var, expr.meta))
+            }
+            kind => map_children(
+                Meta {
+                    kind,
+                    meta: expr.meta,
+                },
+                &mut |child| self.expand_expr(child),
+            ),
+        }
+    }
+
+    /// Replace a macro call by the body of the macro.
+    fn expand_call(
+        &mut self,
+        name: &VarName<TokSpan>,
+        args: Vec<Expr<TokSpan>>,
+        call_site: &TokSpan,
+    ) -> Result<Expr<TokSpan>> {
+        let macro_ = self.macros[&name.kind].clone();
+        if args.len() != macro_.params.len() {
+            return Err(self.macro_err(
+                MacroError::WrongArgumentCount(name.kind.clone(), macro_.params.len(), args.len()),
+                call_site,
+            ));
+        }
+
+        let mut expansion = Expansion {
+            name: &name.kind,
+            args: macro_.params.iter().cloned().zip(args).collect(),
+            renames: Vec::new(),
+        };
+        self.substitute(expanded_at(macro_.body.clone(), call_site), &mut expansion)
+    }
+
+    /// Replace the parameters of a macro body by the arguments, and rename the variables it binds.
+    fn substitute(
+        &mut self,
+        expr: Expr<TokSpan>,
+        expansion: &mut Expansion,
+    ) -> Result<Expr<TokSpan>> {
+        match expr.kind {
+            ExprKind::Variable(name) => {
+                if let Some(renamed) = expansion.renamed(&name.kind) {
+                    Ok(Expr::variable(VarName::new(renamed, name.meta), expr.meta))
+                } else if let Some(arg) = expansion.args.get(&name.kind) {
+                    Ok(arg.clone())
+                } else {
+                    self.check_free(&name, expansion)?;
+                    Ok(Expr::variable(name, expr.meta))
+                }
+            }
+            ExprKind::Call { name, args } => {
+                let args = args
+                    .into_iter()
+                    .map(|arg| self.substitute(arg, expansion))
+                    .collect::<Result<Vec<_>>>()?;
+
+                // a function passed as argument can only be called by name
+                let name = if let Some(renamed) = expansion.renamed(&name.kind) {
+                    VarName::new(renamed, name.meta)
+                } else if let Some(arg) = expansion.args.get(&name.kind) {
+                    match &arg.kind {
+                        ExprKind::Variable(function) => function.clone(),
+                        _ => {
+                            return Err(self.macro_err(
+                                MacroError::UncallableArgument(
+                                    expansion.name.to_owned(),
+                                    name.kind.clone(),
+                                ),
+                                arg,
+                            ))
+                        }
+                    }
+                } else {
+                    self.check_free(&name, expansion)?;
+                    name
+                };
+                Ok(Expr::call(name, args, expr.meta))
+            }
+            ExprKind::Lambda {
+                params,
+                ret,
+                body,
+                captures,
+            } => {
+                let renames: HashMap<_, _> = params
+                    .iter()
+                    .map(|(param, _)| (param.kind.clone(), self.rename(&param.kind)))
+                    .collect();
+                let params = params
+                    .into_iter()
+                    .map(|(param, type_name)| {
+                        (VarName::new(&renames[&param.kind], param.meta), type_name)
+                    })
+                    .collect();
+
+                expansion.renames.push(renames);
+                let body = self.substitute(*body, expansion);
+                expansion.renames.pop();
+                Ok(Expr::lambda(params, ret, body?, captures, expr.meta))
+            }
+            // the variable of a derivative is bound like a lambda parameter, unless passed as
+            // argument, e.g. `macro slope(e, x) => deriv(e, x);`
+            ExprKind::Deriv { expr: body, var } => {
+                if let Some(arg) = self.bound_argument(&var, expansion)? {
+                    let body = self.substitute(*body, expansion)?;
+                    return Ok(Expr::deriv(body, arg, expr.meta));
+                }
+                let renamed = self.rename(&var.kind);
+                expansion
+                    .renames
+                    .push(HashMap::from([(var.kind.clone(), renamed.clone())]));
+                let body = self.substitute(*body, expansion);
+                expansion.renames.pop();
+                Ok(Expr::deriv(
+                    body?,
+                    VarName::new(renamed, var.meta),
+                    expr.meta,
+                ))
+            }
+            ExprKind::Eval {
+                expr: symbolic,
+                var,
+                value,
+            } => {
+                let var = match expansion.renamed(&var.kind) {
+                    Some(renamed) => VarName::new(renamed, var.meta),
+                    None => self.bound_argument(&var, expansion)?.unwrap_or(var),
+                };
+                let symbolic = self.substitute(*symbolic, expansion)?;
+                let value = self.substitute(*value, expansion)?;
+                Ok(Expr::eval(symbolic, var, value, expr.meta))
+            }
+            kind => map_children(
+                Meta {
+                    kind,
+                    meta: expr.meta,
+                },
+                &mut |child| self.substitute(child, expansion),
+            ),
+        }
+    }
+
+    /// Variable passed as the argument for a parameter which the macro body binds, if it is one.
+    fn bound_argument(
+        &self,
+        var: &VarName<TokSpan>,
+        expansion: &Expansion,
+    ) -> Result<Option<VarName<TokSpan>>> {
+        if expansion.renamed(&var.kind).is_some() {
+            return Ok(None);
+        }
+        match expansion.args.get(&var.kind) {
+            Some(Meta {
+                kind: ExprKind::Variable(name),
+                ..
+            }) => Ok(Some(name.clone())),
+            Some(arg) => Err(self.macro_err(
+                MacroError::UnboundArgument(expansion.name.to_owned(), var.kind.clone()),
+                arg,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Check that a variable which the macro body refers to isn't shadowed where it is called.
+    fn check_free(&self, name: &VarName<TokSpan>, expansion: &Expansion) -> Result<()> {
+        if self
+            .locals
+            .iter()
+            .flatten()
+            .any(|local| *local == name.kind)
+        {
+            return Err(self.macro_err(
+                MacroError::ShadowedVariable(expansion.name.to_owned(), name.kind.clone()),
+                name,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Unique name for a variable bound by a macro body, which can't be written in the source.
+    fn rename(&mut self, name: &str) -> String {
+        self.renamed += 1;
+        let name = name.split('#').next().unwrap_or(name);
+        format!("{}#{}", name, self.renamed)
+    }
+
+    fn macro_err(&self, err: MacroError, node: &impl Spanned) -> ExpanderError {
+        ExpanderError::MacroError(err, node.to_error(&self.input.sources))
+    }
+}
+
+/// Mark all the nodes of a macro body as expanded from a call.
+fn expanded_at(expr: Expr<TokSpan>, call_site: &TokSpan) -> Expr<TokSpan> {
+    let mark = |meta: TokSpan| meta.expanded_at(call_site);
+    let mark_name = |name: VarName<TokSpan>| VarName::new(name.kind, mark(name.meta));
+
+    let expr = map_children(expr, &mut |child| {
+        Ok::<_, Infallible>(expanded_at(child, call_site))
+    })
+    .unwrap_or_else(|never| match never {});
+    let kind = match expr.kind {
+        ExprKind::Variable(name) => ExprKind::Variable(mark_name(name)),
+        ExprKind::BinOp { op, left, right } => ExprKind::BinOp {
+            op: Meta {
+                kind: op.kind,
+                meta: mark(op.meta),
+            },
+            left,
+            right,
+        },
+        ExprKind::UnaryOp { op, operand } => ExprKind::UnaryOp {
+            op: Meta {
+                kind: op.kind,
+                meta: mark(op.meta),
+            },
+            operand,
+        },
+        ExprKind::Call { name, args } => ExprKind::Call {
+            name: mark_name(name),
+            args,
+        },
+        ExprKind::Record { name, fields } => ExprKind::Record {
+            name: type_name_expanded_at(name, call_site),
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| (mark_name(field), value))
+                .collect(),
+        },
+        ExprKind::Field { expr, field } => ExprKind::Field {
+            expr,
+            field: mark_name(field),
+        },
+        ExprKind::Variant { name, variant } => ExprKind::Variant {
+            name: type_name_expanded_at(name, call_site),
+            variant: mark_name(variant),
+        },
+        ExprKind::Quantity { value, unit } => ExprKind::Quantity {
+            value,
+            unit: mark_name(unit),
+        },
+        ExprKind::Lambda {
+            params,
+            ret,
+            body,
+            captures,
+        } => ExprKind::Lambda {
+            params: params
+                .into_iter()
+                .map(|(param, type_name)| {
+                    let type_name = type_name.map(|t| type_name_expanded_at(t, call_site));
+                    (mark_name(param), type_name)
+                })
+                .collect(),
+            ret: ret.map(|ret| type_name_expanded_at(ret, call_site)),
+            body,
+            captures,
+        },
+        ExprKind::Deriv { expr, var } => ExprKind::Deriv {
+            expr,
+            var: mark_name(var),
+        },
+        ExprKind::Eval { expr, var, value } => ExprKind::Eval {
+            expr,
+            var: mark_name(var),
+            value,
+        },
+        kind => kind,
+    };
+    Meta {
+        kind,
+        meta: mark(expr.meta),
+    }
+}
+
+/// Mark all the parts of a type written in a macro body as expanded from a call.
+fn type_name_expanded_at(type_name: TypeName<TokSpan>, call_site: &TokSpan) -> TypeName<TokSpan> {
+    let mark_all = |type_names: Vec<TypeName<TokSpan>>| {
+        type_names
+            .into_iter()
+            .map(|type_name| type_name_expanded_at(type_name, call_site))
+            .collect()
+    };
+    let kind = match type_name.kind {
+        TypeNameKind::Named(name) => TypeNameKind::Named(name),
+        TypeNameKind::Fn { params, ret } => TypeNameKind::Fn {
+            params: mark_all(params),
+            ret: Box::new(type_name_expanded_at(*ret, call_site)),
+        },
+        TypeNameKind::Optional(inner) => {
+            TypeNameKind::Optional(Box::new(type_name_expanded_at(*inner, call_site)))
+        }
+        TypeNameKind::Tuple(elements) => TypeNameKind::Tuple(mark_all(elements)),
+    };
+    Meta {
+        kind,
+        meta: type_name.meta.expanded_at(call_site),
+    }
+}
+
+/// Rebuild an expression with its direct sub-expressions mapped.
+fn map_children<E>(
+    expr: Expr<TokSpan>,
+    f: &mut impl FnMut(Expr<TokSpan>) -> std::result::Result<Expr<TokSpan>, E>,
+) -> std::result::Result<Expr<TokSpan>, E> {
+    let map_all = |exprs: Vec<Expr<TokSpan>>, f: &mut dyn FnMut(Expr<TokSpan>) -> _| {
+        exprs
+            .into_iter()
+            .map(f)
+            .collect::<std::result::Result<Vec<_>, E>>()
+    };
+
+    let kind = match expr.kind {
+        ExprKind::BinOp { op, left, right } => ExprKind::BinOp {
+            op,
+            left: Box::new(f(*left)?),
+            right: Box::new(f(*right)?),
+        },
+        ExprKind::UnaryOp { op, operand } => ExprKind::UnaryOp {
+            op,
+            operand: Box::new(f(*operand)?),
+        },
+        ExprKind::Call { name, args } => ExprKind::Call {
+            name,
+            args: map_all(args, f)?,
+        },
+        ExprKind::Apply { callee, args } => ExprKind::Apply {
+            callee: Box::new(f(*callee)?),
+            args: map_all(args, f)?,
+        },
+        ExprKind::Tuple(exprs) => ExprKind::Tuple(map_all(exprs, f)?),
+        ExprKind::Matrix(rows) => ExprKind::Matrix(
+            rows.into_iter()
+                .map(|row| map_all(row, f))
+                .collect::<std::result::Result<_, E>>()?,
+        ),
+        ExprKind::Record { name, fields } => ExprKind::Record {
+            name,
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| Ok((field, f(value)?)))
+                .collect::<std::result::Result<_, E>>()?,
+        },
+        ExprKind::Field { expr, field } => ExprKind::Field {
+            expr: Box::new(f(*expr)?),
+            field,
+        },
+        ExprKind::TupleIndex { expr, index } => ExprKind::TupleIndex {
+            expr: Box::new(f(*expr)?),
+            index,
+        },
+        ExprKind::Range { start, end } => ExprKind::Range {
+            start: Box::new(f(*start)?),
+            end: Box::new(f(*end)?),
+        },
+        ExprKind::Unwrap { expr, default } => ExprKind::Unwrap {
+            expr: Box::new(f(*expr)?),
+            default: Box::new(f(*default)?),
+        },
+        ExprKind::Quantity { value, unit } => ExprKind::Quantity {
+            value: Box::new(f(*value)?),
+            unit,
+        },
+        ExprKind::Convert(expr) => ExprKind::Convert(Box::new(f(*expr)?)),
+        ExprKind::Lambda {
+            params,
+            ret,
+            body,
+            captures,
+        } => ExprKind::Lambda {
+            params,
+            ret,
+            body: Box::new(f(*body)?),
+            captures,
+        },
+        ExprKind::Conditional {
+            cond,
+            then_branch,
+            else_branch,
+        } => ExprKind::Conditional {
+            cond: Box::new(f(*cond)?),
+            then_branch: Box::new(f(*then_branch)?),
+            else_branch: Box::new(f(*else_branch)?),
+        },
+        ExprKind::Deriv { expr, var } => ExprKind::Deriv {
+            expr: Box::new(f(*expr)?),
+            var,
+        },
+        ExprKind::Eval { expr, var, value } => ExprKind::Eval {
+            expr: Box::new(f(*expr)?),
+            var,
+            value: Box::new(f(*value)?),
+        },
+        kind @ (ExprKind::Variable(_)
+        | ExprKind::Variant { .. }
+        | ExprKind::Integer(_)
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::Imaginary(_)
+        | ExprKind::None) => kind,
+    };
+    Ok(Meta {
+        kind,
+        meta: expr.meta,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::checker::check;
+    use crate::pipeline::expander::expand;
+    use crate::pipeline::parser::parse;
+    use crate::pipeline::tokenizer::tokenize;
+    use crate::states::{ExpandedState, InputState};
+
+    fn expand_code(code: &str) -> ExpandedState {
+        let tokenized = tokenize(InputState::from(code)).unwrap();
+        expand(parse(tokenized).unwrap()).unwrap()
+    }
+
+    fn expand_err(code: &str) -> String {
+        let tokenized = tokenize(InputState::from(code)).unwrap();
+        expand(parse(tokenized).unwrap()).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_expander() {
+        // arguments are substituted as a whole, and nested macros are expanded
+        let expanded = expand_code(
+            "macro square(x) => (x) * (x); macro quad(x) => square(square(x)); \
+             print square(1 + 2); print quad(n);",
+        );
+        let mut output = String::new();
+        expanded.raw_ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_expander_hygiene() {
+        // the variables bound by a macro are renamed, so that they can't capture the arguments
+        let expanded = expand_code(
+            "macro add_all(v, k) => map(v, (n) => n + k); macro twice(f, x) => f(f(x)); \
+             n = 1; print add_all((1, 2), n); print twice(inc, n);",
+        );
+        let mut output = String::new();
+        expanded.raw_ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_expander_errors() {
+        insta::assert_snapshot!(
+            "duplicate macro",
+            expand_err("macro a(x) => x; macro a(y) => y;")
+        );
+        insta::assert_snapshot!(
+            "duplicate parameter",
+            expand_err("macro add(x, x) => x + x;")
+        );
+        insta::assert_snapshot!(
+            "wrong argument count",
+            expand_err("macro square(x) => (x) * (x); print square(1, 2);")
+        );
+        insta::assert_snapshot!(
+            "shadowed variable",
+            expand_err("macro scale(x) => x * k; k = 2; print map((1, 2), (k) => scale(k));")
+        );
+        insta::assert_snapshot!(
+            "uncallable argument",
+            expand_err("macro twice(f, x) => f(f(x)); print twice(1 + 2, 3);")
+        );
+
+        // errors in expanded code show the definition, then the call
+        let expanded = expand_code("macro square(x) => (x) * (x);\nprint square(true);");
+        insta::assert_snapshot!(
+            "expanded type error",
+            check(expanded).err().unwrap().to_string()
+        );
+    }
+}
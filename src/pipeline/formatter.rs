@@ -1,36 +1,82 @@
 //! Formatter
 //!
-//! This pipeline stage operates on a generic `Ast<T>` context data, as it doesn't require any AST
-//! metadata. This means that it can be run on either `ParsedState` or `CheckedState`.
+//! This pipeline stage operates on a generic `Ast<T>` context data, as it only requires the spans
+//! of the AST nodes. This means that it can be run on either `ParsedState` or `CheckedState`.
+//!
+//! Only the main file is formatted: the statements of the files it imports and of the modules it
+//! uses, which are merged into the program, are left out, and their `import` and `use` statements
+//! are kept.
 
 use crate::context::ast::Ast;
-use crate::data::ast::{Expr, ExprKind, Stmt, StmtKind};
+use crate::context::source::PRELUDE;
+use crate::data::ast::{Expr, ExprKind, Pattern, PatternKind, Stmt, StmtKind};
+use crate::errors::Spanned;
 use std::fmt;
 use std::fmt::{Debug, Display, Write};
 
-pub(crate) fn format<T: Debug + Display, W: Write>(
+pub(crate) fn format<T: Debug + Display + Spanned, W: Write>(
+    input: &Ast<T>,
+    writer: &mut W,
+) -> Result<(), fmt::Error> {
+    let mut formatter = Formatter::new(input, writer, false);
+    formatter.format()?;
+    Ok(())
+}
+
+/// Format the AST, showing the conversions inserted by the type checker as `type(expr)`.
+pub(crate) fn format_conversions<T: Debug + Display + Spanned, W: Write>(
     input: &Ast<T>,
     writer: &mut W,
 ) -> Result<(), fmt::Error> {
-    let mut formatter = Formatter::new(input, writer);
+    let mut formatter = Formatter::new(input, writer, true);
     formatter.format()?;
     Ok(())
 }
 
-struct Formatter<'a, T: Debug + Display, W: Write> {
+/// Format a single expression, e.g. a symbolic value.
+pub(crate) fn format_expr<T: Debug + Display + Spanned, W: Write>(
+    expr: &Expr<T>,
+    writer: &mut W,
+) -> Result<(), fmt::Error> {
+    let input = Ast::new();
+    let mut formatter = Formatter::new(&input, writer, false);
+    formatter.format_expr(expr)
+}
+
+struct Formatter<'a, T: Debug + Display + Spanned, W: Write> {
     input: &'a Ast<T>,
     writer: &'a mut W,
-    // here there would be additional state, e.g. indentation level
-    // nothing because indentation is not needed for this toy language
+    show_conversions: bool,
+
+    // state
+    /// current indentation level
+    indent: usize,
 }
 
-impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
-    fn new(input: &'a Ast<T>, writer: &'a mut W) -> Self {
-        Self { input, writer }
+impl<'a, T: Debug + Display + Spanned, W: Write> Formatter<'a, T, W> {
+    fn new(input: &'a Ast<T>, writer: &'a mut W, show_conversions: bool) -> Self {
+        Self {
+            input,
+            writer,
+            show_conversions,
+            indent: 0,
+        }
+    }
+
+    fn precedence(&self, expr: &Expr<T>) -> u8 {
+        match &expr.kind {
+            ExprKind::Convert(_) if self.show_conversions => 255,
+            kind => kind.precedence(),
+        }
     }
 
     fn format(&mut self) -> fmt::Result {
-        for stmt in self.input.stmts() {
+        let main = self
+            .input
+            .stmts()
+            .iter()
+            .filter(|stmt| stmt.span().start.source == 0);
+        for stmt in main {
             self.format_stmt(stmt)?;
             writeln!(self.writer)?;
         }
@@ -45,6 +91,27 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
                 self.format_expr(value)?;
                 write!(self.writer, ";")?;
             }
+            StmtKind::Let { pattern, value } => {
+                write!(self.writer, "let ")?;
+                self.format_pattern(pattern)?;
+                write!(self.writer, " = ")?;
+                self.format_expr(value)?;
+                write!(self.writer, ";")?;
+            }
+            StmtKind::Declare {
+                name,
+                type_name,
+                value,
+            } => {
+                write!(self.writer, "{}: {} = ", name, type_name)?;
+                self.format_expr(value)?;
+                write!(self.writer, ";")?;
+            }
+            StmtKind::CompoundAssign { name, op, value } => {
+                write!(self.writer, "{} {}= ", name, op)?;
+                self.format_expr(value)?;
+                write!(self.writer, ";")?;
+            }
             StmtKind::Print { expr } => {
                 write!(self.writer, "print ")?;
                 self.format_expr(expr)?;
@@ -54,17 +121,116 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
                 self.format_expr(expr)?;
                 write!(self.writer, ";")?;
             }
+            StmtKind::Seed { seed } => {
+                write!(self.writer, "seed(")?;
+                self.format_expr(seed)?;
+                write!(self.writer, ");")?;
+            }
+            StmtKind::Exit { code } => {
+                write!(self.writer, "exit(")?;
+                self.format_expr(code)?;
+                write!(self.writer, ");")?;
+            }
+            StmtKind::Assert { cond, message } => {
+                write!(self.writer, "assert ")?;
+                self.format_expr(cond)?;
+                write!(self.writer, ", \"{}\";", message)?;
+            }
+            StmtKind::For { var, iter, body } => {
+                write!(self.writer, "for {} in ", var)?;
+                self.format_expr(iter)?;
+                write!(self.writer, " ")?;
+                self.format_block(body)?;
+            }
+            StmtKind::Plot {
+                name,
+                value,
+                var,
+                start,
+                end,
+                path,
+            } => {
+                write!(self.writer, "plot {} = ", name)?;
+                self.format_expr(value)?;
+                write!(self.writer, " for {} in ", var)?;
+                self.format_expr(start)?;
+                write!(self.writer, "..")?;
+                self.format_expr(end)?;
+                write!(self.writer, ", \"{}\";", path)?;
+            }
+            StmtKind::Record { name, fields } => {
+                write!(self.writer, "record {} {{ ", name)?;
+                for (i, (field, type_name)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    write!(self.writer, "{} {}", field, type_name)?;
+                }
+                write!(self.writer, " }}")?;
+            }
+            StmtKind::Enum { name, variants } => {
+                write!(self.writer, "enum {} {{ ", name)?;
+                for (i, variant) in variants.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    write!(self.writer, "{}", variant)?;
+                }
+                write!(self.writer, " }}")?;
+            }
+            StmtKind::Match { expr, arms } => {
+                write!(self.writer, "match ")?;
+                self.format_expr(expr)?;
+                writeln!(self.writer, " {{")?;
+                self.indent += 1;
+                for (pattern, body) in arms {
+                    write!(self.writer, "{:1$}", "", self.indent * 4)?;
+                    self.format_expr(pattern)?;
+                    write!(self.writer, " => ")?;
+                    self.format_block(body)?;
+                    writeln!(self.writer)?;
+                }
+                self.indent -= 1;
+                write!(self.writer, "{:1$}}}", "", self.indent * 4)?;
+            }
+            StmtKind::Import { path } => write!(self.writer, "import \"{}\";", path)?,
+            StmtKind::Use { module } => write!(self.writer, "use {};", module)?,
+            StmtKind::Module { .. } => unreachable!("modules are formatted in their own file"),
+            StmtKind::Macro { name, params, body } => {
+                write!(self.writer, "macro {}(", name)?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    write!(self.writer, "{}", param)?;
+                }
+                write!(self.writer, ") => ")?;
+                self.format_expr(body)?;
+                write!(self.writer, ";")?;
+            }
         }
 
         Ok(())
     }
 
+    fn format_block(&mut self, stmts: &[Stmt<T>]) -> fmt::Result {
+        writeln!(self.writer, "{{")?;
+        self.indent += 1;
+        for stmt in stmts {
+            write!(self.writer, "{:1$}", "", self.indent * 4)?;
+            self.format_stmt(stmt)?;
+            writeln!(self.writer)?;
+        }
+        self.indent -= 1;
+        write!(self.writer, "{:1$}}}", "", self.indent * 4)
+    }
+
     fn format_expr(&mut self, expr: &Expr<T>) -> fmt::Result {
         match &expr.kind {
-            ExprKind::Variable(name) => write!(self.writer, "{}", name)?,
+            ExprKind::Variable(name) => write!(self.writer, "{}", source_name(&name.kind))?,
             ExprKind::BinOp { op, left, right } => {
-                let left_precedence = left.kind.precedence();
-                let right_precedence = right.kind.precedence();
+                let left_precedence = self.precedence(left);
+                let right_precedence = self.precedence(right);
                 let op_precedence = op.kind.precedence();
                 if left_precedence < op_precedence {
                     self.format_expr_paren(left)?;
@@ -72,7 +238,9 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
                     self.format_expr(left)?;
                 }
                 write!(self.writer, " {} ", op)?;
-                if right_precedence < op_precedence {
+                // operators are left-associative, so a right operand of the same precedence was
+                // parenthesized, e.g. `a - (b - c)`
+                if right_precedence <= op_precedence {
                     self.format_expr_paren(right)?;
                 } else {
                     self.format_expr(right)?;
@@ -80,7 +248,7 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
             }
             ExprKind::UnaryOp { op, operand } => {
                 let op_precedence = op.kind.precedence();
-                let operand_precedence = operand.kind.precedence();
+                let operand_precedence = self.precedence(operand);
                 write!(self.writer, "{}", op)?;
 
                 if operand_precedence < op_precedence {
@@ -89,6 +257,19 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
                     self.format_expr(operand)?;
                 }
             }
+            ExprKind::Call { name, args } => {
+                write!(self.writer, "{}(", source_name(&name.kind))?;
+                self.format_args(args)?;
+            }
+            ExprKind::Apply { callee, args } => {
+                if self.precedence(callee) < 255 {
+                    self.format_expr_paren(callee)?;
+                } else {
+                    self.format_expr(callee)?;
+                }
+                write!(self.writer, "(")?;
+                self.format_args(args)?;
+            }
             ExprKind::Tuple(exprs) => {
                 write!(self.writer, "(")?;
                 for (i, expr) in exprs.iter().enumerate() {
@@ -102,13 +283,193 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
                 }
                 write!(self.writer, ")")?;
             }
+            ExprKind::Matrix(rows) => {
+                write!(self.writer, "[")?;
+                for (i, row) in rows.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, "; ")?;
+                    }
+                    for (j, expr) in row.iter().enumerate() {
+                        if j > 0 {
+                            write!(self.writer, ", ")?;
+                        }
+                        self.format_expr(expr)?;
+                    }
+                }
+                write!(self.writer, "]")?;
+            }
+            ExprKind::Record { name, fields } => {
+                write!(self.writer, "{} {{ ", name)?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    write!(self.writer, "{}: ", field)?;
+                    self.format_expr(value)?;
+                }
+                write!(self.writer, " }}")?;
+            }
+            ExprKind::Field {
+                expr: record,
+                field,
+            } => {
+                if self.precedence(record) < expr.kind.precedence() {
+                    self.format_expr_paren(record)?;
+                } else {
+                    self.format_expr(record)?;
+                }
+                write!(self.writer, ".{}", field)?;
+            }
+            ExprKind::Variant { name, variant } => write!(self.writer, "{}::{}", name, variant)?,
+            ExprKind::TupleIndex { expr: tuple, index } => {
+                if self.precedence(tuple) < expr.kind.precedence() {
+                    self.format_expr_paren(tuple)?;
+                } else {
+                    self.format_expr(tuple)?;
+                }
+                write!(self.writer, ".{}", index)?;
+            }
             ExprKind::Integer(i) => write!(self.writer, "{}", i)?,
             ExprKind::Float(f) => write!(self.writer, "{:?}", f)?,
+            ExprKind::Bool(b) => write!(self.writer, "{}", b)?,
+            ExprKind::Imaginary(f) => write!(self.writer, "{:?}i", f)?,
+            ExprKind::None => write!(self.writer, "none")?,
+            ExprKind::Unwrap {
+                expr: optional,
+                default,
+            } => {
+                // right-associative
+                let precedence = expr.kind.precedence();
+                if self.precedence(optional) <= precedence {
+                    self.format_expr_paren(optional)?;
+                } else {
+                    self.format_expr(optional)?;
+                }
+                write!(self.writer, " ?? ")?;
+                if self.precedence(default) < precedence {
+                    self.format_expr_paren(default)?;
+                } else {
+                    self.format_expr(default)?;
+                }
+            }
+            ExprKind::Quantity { value, unit } => {
+                self.format_expr(value)?;
+                write!(self.writer, " {}", unit)?;
+            }
+            ExprKind::Range { start, end } => {
+                let precedence = expr.kind.precedence();
+                for (i, bound) in [start, end].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, "..")?;
+                    }
+                    if self.precedence(bound) <= precedence {
+                        self.format_expr_paren(bound)?;
+                    } else {
+                        self.format_expr(bound)?;
+                    }
+                }
+            }
+            ExprKind::Convert(operand) => {
+                if self.show_conversions {
+                    write!(self.writer, "{}(", expr.meta)?;
+                    self.format_expr(operand)?;
+                    write!(self.writer, ")")?;
+                } else {
+                    self.format_expr(operand)?;
+                }
+            }
+            ExprKind::Conditional {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                write!(self.writer, "if ")?;
+                self.format_expr(cond)?;
+                write!(self.writer, " then ")?;
+                self.format_expr(then_branch)?;
+                write!(self.writer, " else ")?;
+                self.format_expr(else_branch)?;
+            }
+            ExprKind::Deriv { expr, var } => {
+                write!(self.writer, "deriv(")?;
+                self.format_expr(expr)?;
+                write!(self.writer, ", {})", var)?;
+            }
+            ExprKind::Eval { expr, var, value } => {
+                write!(self.writer, "eval(")?;
+                self.format_expr(expr)?;
+                write!(self.writer, ", {} = ", var)?;
+                self.format_expr(value)?;
+                write!(self.writer, ")")?;
+            }
+            ExprKind::Lambda {
+                params, ret, body, ..
+            } => {
+                match ret {
+                    Some(ret) => {
+                        write!(self.writer, "fn(")?;
+                        for (i, (param, type_name)) in params.iter().enumerate() {
+                            if i > 0 {
+                                write!(self.writer, ", ")?;
+                            }
+                            write!(self.writer, "{}", param)?;
+                            if let Some(type_name) = type_name {
+                                write!(self.writer, " {}", type_name)?;
+                            }
+                        }
+                        write!(self.writer, ") {} => ", ret)?;
+                    }
+                    // short form, with inferred types
+                    None if params.len() == 1 => write!(self.writer, "{} => ", params[0].0)?,
+                    None => {
+                        write!(self.writer, "(")?;
+                        for (i, (param, _)) in params.iter().enumerate() {
+                            if i > 0 {
+                                write!(self.writer, ", ")?;
+                            }
+                            write!(self.writer, "{}", param)?;
+                        }
+                        write!(self.writer, ") => ")?;
+                    }
+                }
+                self.format_expr(body)?;
+            }
         }
 
         Ok(())
     }
 
+    fn format_pattern(&mut self, pattern: &Pattern<T>) -> fmt::Result {
+        match &pattern.kind {
+            PatternKind::Name(name) => write!(self.writer, "{}", name),
+            PatternKind::Wildcard => write!(self.writer, "_"),
+            PatternKind::Tuple(patterns) => {
+                write!(self.writer, "(")?;
+                for (i, pattern) in patterns.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.writer, ", ")?;
+                    }
+                    self.format_pattern(pattern)?;
+                }
+                if patterns.len() == 1 {
+                    write!(self.writer, ",")?;
+                }
+                write!(self.writer, ")")
+            }
+        }
+    }
+
+    /// Format the arguments of a call, up to the closing parenthesis.
+    fn format_args(&mut self, args: &[Expr<T>]) -> fmt::Result {
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                write!(self.writer, ", ")?;
+            }
+            self.format_expr(arg)?;
+        }
+        write!(self.writer, ")")
+    }
+
     fn format_expr_paren(&mut self, expr: &Expr<T>) -> fmt::Result {
         write!(self.writer, "(")?;
         self.format_expr(expr)?;
@@ -116,14 +477,23 @@ impl<'a, T: Debug + Display, W: Write> Formatter<'a, T, W> {
     }
 }
 
+/// Name of a variable as written in the source, where those of the prelude, which the type checker
+/// qualifies, are visible unqualified.
+fn source_name(name: &str) -> &str {
+    name.strip_prefix(PRELUDE)
+        .and_then(|name| name.strip_prefix('.'))
+        .unwrap_or(name)
+}
+
 #[cfg(test)]
 mod test {
-    use crate::pipeline::checker::check;
-    use crate::pipeline::formatter::format;
+    use crate::pipeline::checker::{check, check_promote};
+    use crate::pipeline::expander::expand;
+    use crate::pipeline::formatter::{format, format_conversions};
     use crate::pipeline::optimizer::optimize;
     use crate::pipeline::parser;
     use crate::pipeline::tokenizer::tokenize;
-    use crate::states::{InputState, ParsedState};
+    use crate::states::{InputState, Load, ParsedState};
 
     fn parse(input: &str) -> ParsedState {
         let input = InputState::from(input);
@@ -142,7 +512,7 @@ mod test {
     #[test]
     fn test_formatter_from_checked_ast() {
         let parsed = parse("a = (1.3 + 3.2) * 45.1; b = a * 3.2; print 1 + 2 * 3;");
-        let checked = check(parsed).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
         let mut output = String::new();
         format(&checked.ast, &mut output).unwrap();
         insta::assert_snapshot!(output);
@@ -151,7 +521,7 @@ mod test {
     #[test]
     fn test_formatter_from_optimized_ast() {
         let parsed = parse("a = (1.3 + 3.2) * 45.1; b = a * 3.2; print 1 + 2 * 3;");
-        let checked = check(parsed).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
         let optimized = optimize(checked);
         let mut output = String::new();
         format(&optimized.ast, &mut output).unwrap();
@@ -165,4 +535,178 @@ mod test {
         format(&parsed.raw_ast, &mut output).unwrap();
         insta::assert_snapshot!(output);
     }
+
+    #[test]
+    fn test_formatter_conversions() {
+        let parsed = parse("n = 2; x = 2.5; print x * n; print -(n + 1) * x;");
+        let checked = check_promote(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format_conversions(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_for() {
+        let parsed = parse("for i in 0..n+1 { for x in (1, 2) { print x*i; } s = (0..2); }");
+        let mut output = String::new();
+        format(&parsed.raw_ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_compound_assign() {
+        let parsed = parse("x = 1; x += 2*3; x -= 1; x *= (2+1); x /= 2;");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_bitwise() {
+        let parsed = parse("x = 1; print (x|2)&~x; print x<<1+2; print (x<<1)+2; print x&(3^x);");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_conditional() {
+        let parsed =
+            parse("a = 1; b = 2; m = if a<b then a else b; print 1 + (if a==b then 1 else 2) * 2;");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_record() {
+        let parsed = parse(
+            "record Point{x float,y float,} p=Point{y:2.0,x:1.0}; print (if true then p else p).x + -p.y;",
+        );
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_product() {
+        let parsed = parse("t=(1,(2.5,true)); print t.1.0 + -(if true then t else t).1.0;");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_match() {
+        let parsed = parse(
+            "enum Mode{Fast,Slow,} m=Mode::Fast; for i in 0..2 { match m{Mode::Slow=>{print i;} Mode::Fast=>{}} }",
+        );
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_lambda() {
+        let parsed = parse("f=fn(x int,y float)float=>if x>0 then y else -y; print f(1,2.0)*2.0;");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_import() {
+        // only the main file is formatted, with its imports and the modules it uses
+        let format_file = |path: &str| {
+            let input = InputState::from_file(path.into(), Load::Imports).unwrap();
+            let tokenized = tokenize(input).unwrap();
+            let parsed = parser::parse(tokenized).unwrap();
+            let mut output = String::new();
+            format(&parsed.raw_ast, &mut output).unwrap();
+
+            let checked = check(expand(parsed).unwrap()).unwrap();
+            let mut checked_output = String::new();
+            format(&checked.ast, &mut checked_output).unwrap();
+            assert_eq!(output, checked_output);
+            output
+        };
+
+        insta::assert_snapshot!(format_file("testdata/imports/main.calc"));
+        insta::assert_snapshot!("use", format_file("testdata/modules/main.calc"));
+    }
+
+    #[test]
+    fn test_formatter_function_type() {
+        let parsed = parse(
+            "t=fn(f fn(int,float)int)int=>f(1,2.0);mk=fn(n int)fn()int=>fn()int=>n;print mk(2)();print (fn(x int)int=>x+1)(2)+t((a,b)=>a);",
+        );
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_type_name() {
+        let parsed = parse(
+            "t:(int,float)?=none;u:((int,int))=(1,2);f:(fn(int)int)?=none;g:fn(int)int?=fn(x int)int?=>none;",
+        );
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_higher_order() {
+        let parsed = parse("t=(1,2);print map(t,x=>x*2);print fold(t,0,(a,b)=>a+b);");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_optional() {
+        let parsed = parse("x:int?=none;x=1;print(x??2)*3;print x==none;");
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        format(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+
+        let mut output = String::new();
+        format_conversions(&checked.ast, &mut output).unwrap();
+        insta::assert_snapshot!("formatter_optional_conversions", output);
+    }
+
+    #[test]
+    fn test_formatter_let() {
+        let parsed = parse("t=(1,(2.5,true)); let (a,(_,c))=t; let (x,)=(a,);");
+        let mut output = String::new();
+        format(&parsed.raw_ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_symbolic() {
+        let parsed = parse("d=deriv(x*x+3*x,x); print eval(d,x=2.0); print a-(b-c);");
+        let mut output = String::new();
+        format(&parsed.raw_ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_formatter_plot() {
+        let parsed = parse(r#"plot y=a*x for x in -1..2.5,"line.svg";"#);
+        let mut output = String::new();
+        format(&parsed.raw_ast, &mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
 }
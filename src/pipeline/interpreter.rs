@@ -1,14 +1,26 @@
-use crate::context::checked_ast::{CheckedExpr, CheckedStmt};
-use crate::data::ast::{BinOpKind, ExprKind, StmtKind, UnaryOpKind};
-use crate::errors::{InterpreterError, Spanned, SyntaxError, TypeError};
+use crate::context::checked_ast::{
+    CheckedBinOp, CheckedExpr, CheckedPattern, CheckedStmt, CheckedVarName, EnumType, RecordType,
+    Type,
+};
+use crate::data::ast::{BinOpKind, Expr, ExprKind, PatternKind, StmtKind, UnaryOpKind};
+use crate::data::builtin::Builtin;
+use crate::data::plot::Plot;
+use crate::data::rng::Rng;
+use crate::data::symbolic::Symbolic;
+use crate::data::unit::{Dimension, Unit};
+use crate::errors::{InterpreterError, RuntimeError, Spanned, SyntaxError, TypeError};
+use crate::pipeline::formatter::format_expr;
 use crate::states::CheckedState;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Write;
+use std::rc::Rc;
 
 type Result<T> = std::result::Result<T, InterpreterError>;
 
-pub(crate) fn interpret<W: Write>(input: &CheckedState, writer: &mut W) -> Result<()> {
+/// Run the program and return its exit status.
+pub(crate) fn interpret<W: Write>(input: &CheckedState, writer: &mut W) -> Result<i32> {
     let mut interpreter = Interpreter::new(input, writer);
     interpreter.run()
 }
@@ -17,23 +29,129 @@ pub(crate) fn interpret<W: Write>(input: &CheckedState, writer: &mut W) -> Resul
 pub enum Value {
     Int(i32),
     Float(f64),
+    Bool(bool),
+    Complex(f64, f64),
+    Quantity(f64, Dimension),
     Tuple(Vec<Value>),
+    Matrix {
+        rows: usize,
+        cols: usize,
+        data: Vec<f64>, // row-major
+    },
+    Range(i32, i32), // half-open
+    Seq(Vec<Value>),
+    Optional(Option<Box<Value>>),
+    Record {
+        type_: Rc<RecordType>,
+        fields: Vec<Value>, // in declaration order
+    },
+    Enum {
+        type_: Rc<EnumType>,
+        index: usize, // of the variant, in declaration order
+    },
+    Closure {
+        type_: Type,
+        params: Vec<String>,
+        body: Rc<CheckedExpr>,
+        env: HashMap<String, Value>, // captured variables
+    },
+    Symbolic(Rc<Symbolic>),
 }
 
 impl Value {
+    /// Build a float with a dimension, which is a plain float if dimensionless.
+    fn quantity(value: f64, dim: Dimension) -> Self {
+        if dim.is_dimensionless() {
+            Self::Float(value)
+        } else {
+            Self::Quantity(value, dim)
+        }
+    }
+
+    fn matrix(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        Self::Matrix { rows, cols, data }
+    }
+
+    /// Elements of an iterable value.
+    fn into_elements(self) -> Vec<Value> {
+        match self {
+            Self::Range(start, end) => (start..end).map(Self::Int).collect(),
+            Self::Tuple(values) | Self::Seq(values) => values,
+            _ => unreachable!("type checker should have checked this"),
+        }
+    }
+
+    /// Promote a scalar value to a complex number.
+    fn as_complex(&self) -> Option<(f64, f64)> {
+        match self {
+            Self::Int(i) => Some((*i as f64, 0.0)),
+            Self::Float(fl) => Some((*fl, 0.0)),
+            Self::Complex(re, im) => Some((*re, *im)),
+            Self::Bool(_)
+            | Self::Quantity(..)
+            | Self::Tuple(_)
+            | Self::Matrix { .. }
+            | Self::Range(..)
+            | Self::Seq(_)
+            | Self::Optional(_)
+            | Self::Record { .. }
+            | Self::Enum { .. }
+            | Self::Closure { .. }
+            | Self::Symbolic(_) => None,
+        }
+    }
+
     fn bin_op(&self, op: &BinOpKind, other: &Value) -> Option<Value> {
         match (self, other) {
+            _ if op.is_comparison() => match (self, other) {
+                (Self::Int(a), Self::Int(b)) => Some(Self::Bool(compare(*op, a, b))),
+                (Self::Float(a), Self::Float(b)) | (Self::Quantity(a, _), Self::Quantity(b, _)) => {
+                    Some(Self::Bool(compare(*op, a, b)))
+                }
+                (Self::Bool(a), Self::Bool(b)) => Some(Self::Bool(compare(*op, a, b))),
+                (Self::Enum { index: a, .. }, Self::Enum { index: b, .. }) => {
+                    Some(Self::Bool(compare(*op, a, b)))
+                }
+                // tuples are equal if all of their elements are
+                (Self::Tuple(t1), Self::Tuple(t2)) => {
+                    let mut equal = true;
+                    for (v1, v2) in t1.iter().zip(t2) {
+                        equal &= matches!(v1.bin_op(&BinOpKind::Eq, v2)?, Self::Bool(true));
+                    }
+                    Some(Self::Bool(compare(*op, equal, true)))
+                }
+                // optionals are equal if both are none, or both hold equal values
+                (Self::Optional(o1), Self::Optional(o2)) => {
+                    let equal = match (o1, o2) {
+                        (Some(v1), Some(v2)) => {
+                            matches!(v1.bin_op(&BinOpKind::Eq, v2)?, Self::Bool(true))
+                        }
+                        (None, None) => true,
+                        _ => false,
+                    };
+                    Some(Self::Bool(compare(*op, equal, true)))
+                }
+                (Self::Complex(..), _) | (_, Self::Complex(..)) => Some(Self::Bool(compare(
+                    *op,
+                    self.as_complex()?,
+                    other.as_complex()?,
+                ))),
+                _ => None,
+            },
             (Self::Int(i1), Self::Int(i2)) => match op {
                 BinOpKind::Add => Some(Self::Int(i1 + i2)),
                 BinOpKind::Sub => Some(Self::Int(i1 - i2)),
                 BinOpKind::Mul => Some(Self::Int(i1 * i2)),
                 BinOpKind::Div => Some(Self::Int(i1 / i2)),
+                BinOpKind::MatMul => None,
+                _ => Some(Self::Int(eval_bitwise(*op, *i1, *i2))),
             },
             (Self::Float(f1), Self::Float(f2)) => match op {
                 BinOpKind::Add => Some(Self::Float(f1 + f2)),
                 BinOpKind::Sub => Some(Self::Float(f1 - f2)),
                 BinOpKind::Mul => Some(Self::Float(f1 * f2)),
                 BinOpKind::Div => Some(Self::Float(f1 / f2)),
+                _ => None,
             },
             (Self::Tuple(t1), Self::Tuple(t2)) => {
                 // tuple addition and subtraction are element-wise
@@ -47,28 +165,101 @@ impl Value {
                     Some(Self::Tuple(res))
                 }
             }
-            (Self::Tuple(t), Self::Int(i)) | (Self::Int(i), Self::Tuple(t)) => {
+            (Self::Tuple(t), scalar) | (scalar, Self::Tuple(t)) => {
                 // tuple multiplication and division is scalar multiplication
                 if !matches!(op, BinOpKind::Mul | BinOpKind::Div) {
                     None
                 } else {
                     let mut res = Vec::new();
                     for v in t {
-                        res.push(v.bin_op(op, &Self::Int(*i))?);
+                        res.push(v.bin_op(op, scalar)?);
                     }
                     Some(Self::Tuple(res))
                 }
             }
-            (Self::Tuple(t), Self::Float(fl)) | (Self::Float(fl), Self::Tuple(t)) => {
-                // tuple multiplication and division is scalar multiplication
-                if !matches!(op, BinOpKind::Mul | BinOpKind::Div) {
-                    None
-                } else {
-                    let mut res = Vec::new();
-                    for v in t {
-                        res.push(v.bin_op(op, &Self::Float(*fl))?);
+            (
+                Self::Matrix {
+                    rows: r1,
+                    cols: c1,
+                    data: m1,
+                },
+                Self::Matrix {
+                    rows: r2,
+                    cols: c2,
+                    data: m2,
+                },
+            ) => match op {
+                BinOpKind::Add | BinOpKind::Sub if (r1, c1) == (r2, c2) => {
+                    let data = m1
+                        .iter()
+                        .zip(m2)
+                        .map(|(a, b)| if *op == BinOpKind::Add { a + b } else { a - b })
+                        .collect();
+                    Some(Self::matrix(*r1, *c1, data))
+                }
+                BinOpKind::MatMul if c1 == r2 => {
+                    let mut data = vec![0.0; r1 * c2];
+                    for i in 0..*r1 {
+                        for j in 0..*c2 {
+                            for k in 0..*c1 {
+                                data[i * c2 + j] += m1[i * c1 + k] * m2[k * c2 + j];
+                            }
+                        }
                     }
-                    Some(Self::Tuple(res))
+                    Some(Self::matrix(*r1, *c2, data))
+                }
+                _ => None,
+            },
+            (Self::Matrix { rows, cols, data }, Self::Float(fl)) => match op {
+                BinOpKind::Mul => Some(Self::matrix(
+                    *rows,
+                    *cols,
+                    data.iter().map(|v| v * fl).collect(),
+                )),
+                BinOpKind::Div => Some(Self::matrix(
+                    *rows,
+                    *cols,
+                    data.iter().map(|v| v / fl).collect(),
+                )),
+                _ => None,
+            },
+            (Self::Float(fl), Self::Matrix { rows, cols, data }) => match op {
+                BinOpKind::Mul => Some(Self::matrix(
+                    *rows,
+                    *cols,
+                    data.iter().map(|v| fl * v).collect(),
+                )),
+                _ => None,
+            },
+            (Self::Quantity(q1, d1), Self::Quantity(q2, d2)) => match op {
+                BinOpKind::Add => Some(Self::Quantity(q1 + q2, *d1)),
+                BinOpKind::Sub => Some(Self::Quantity(q1 - q2, *d1)),
+                BinOpKind::Mul => Some(Self::quantity(q1 * q2, *d1 + *d2)),
+                BinOpKind::Div => Some(Self::quantity(q1 / q2, *d1 - *d2)),
+                _ => None,
+            },
+            (Self::Quantity(q, d), Self::Float(fl)) => match op {
+                BinOpKind::Mul => Some(Self::Quantity(q * fl, *d)),
+                BinOpKind::Div => Some(Self::Quantity(q / fl, *d)),
+                _ => None,
+            },
+            (Self::Float(fl), Self::Quantity(q, d)) => match op {
+                BinOpKind::Mul => Some(Self::Quantity(fl * q, *d)),
+                BinOpKind::Div => Some(Self::Quantity(fl / q, -*d)),
+                _ => None,
+            },
+            (Self::Complex(..), _) | (_, Self::Complex(..)) => {
+                let (a, b) = self.as_complex()?;
+                let (c, d) = other.as_complex()?;
+                match op {
+                    BinOpKind::Add => Some(Self::Complex(a + c, b + d)),
+                    BinOpKind::Sub => Some(Self::Complex(a - c, b - d)),
+                    BinOpKind::Mul => Some(Self::Complex(a * c - b * d, a * d + b * c)),
+                    BinOpKind::Div => {
+                        let den = c * c + d * d;
+                        Some(Self::Complex((a * c + b * d) / den, (b * c - a * d) / den))
+                    }
+                    _ => None,
                 }
             }
             _ => None,
@@ -80,24 +271,89 @@ impl Value {
             Self::Int(i) => match op {
                 UnaryOpKind::Pos => Self::Int(*i),
                 UnaryOpKind::Neg => Self::Int(-*i),
+                UnaryOpKind::BitNot => Self::Int(!*i),
             },
             Self::Float(f) => match op {
                 UnaryOpKind::Pos => Self::Float(*f),
                 UnaryOpKind::Neg => Self::Float(-*f),
+                UnaryOpKind::BitNot => unreachable!("type checker should have checked this"),
+            },
+            Self::Complex(re, im) => match op {
+                UnaryOpKind::Pos => Self::Complex(*re, *im),
+                UnaryOpKind::Neg => Self::Complex(-*re, -*im),
+                UnaryOpKind::BitNot => unreachable!("type checker should have checked this"),
+            },
+            Self::Quantity(q, d) => match op {
+                UnaryOpKind::Pos => Self::Quantity(*q, *d),
+                UnaryOpKind::Neg => Self::Quantity(-*q, *d),
+                UnaryOpKind::BitNot => unreachable!("type checker should have checked this"),
             },
             Self::Tuple(values) => match op {
                 UnaryOpKind::Pos => Self::Tuple(values.clone()),
                 UnaryOpKind::Neg => Self::Tuple(values.iter().map(|v| v.unary_op(op)).collect()),
+                UnaryOpKind::BitNot => unreachable!("type checker should have checked this"),
             },
+            Self::Matrix { rows, cols, data } => match op {
+                UnaryOpKind::Pos => Self::matrix(*rows, *cols, data.clone()),
+                UnaryOpKind::Neg => Self::matrix(*rows, *cols, data.iter().map(|v| -v).collect()),
+                UnaryOpKind::BitNot => unreachable!("type checker should have checked this"),
+            },
+            Self::Bool(_)
+            | Self::Range(..)
+            | Self::Seq(_)
+            | Self::Optional(_)
+            | Self::Record { .. }
+            | Self::Enum { .. }
+            | Self::Closure { .. }
+            | Self::Symbolic(_) => {
+                unreachable!("type checker should have checked this")
+            }
         }
     }
 }
 
+/// Evaluate a bitwise or shift operator on integers.
+///
+/// Shifting by a negative amount or by more than 31 bits is well-defined: `<<` yields 0 and `>>`
+/// yields the sign fill of the left operand. The LLVM backend implements the same semantics.
+pub(crate) fn eval_bitwise(op: BinOpKind, a: i32, b: i32) -> i32 {
+    let oversized = b as u32 > 31;
+    match op {
+        BinOpKind::BitAnd => a & b,
+        BinOpKind::BitOr => a | b,
+        BinOpKind::BitXor => a ^ b,
+        BinOpKind::Shl if oversized => 0,
+        BinOpKind::Shl => a << b,
+        BinOpKind::Shr if oversized => a >> 31,
+        BinOpKind::Shr => a >> b,
+        _ => unreachable!("not a bitwise operator: {}", op),
+    }
+}
+
+/// Evaluate a comparison operator. Only equality is ever requested for complex numbers and bools.
+pub(crate) fn compare<T: PartialOrd>(op: BinOpKind, a: T, b: T) -> bool {
+    match op {
+        BinOpKind::Eq => a == b,
+        BinOpKind::Ne => a != b,
+        BinOpKind::Lt => a < b,
+        BinOpKind::Le => a <= b,
+        BinOpKind::Gt => a > b,
+        BinOpKind::Ge => a >= b,
+        _ => unreachable!("not a comparison operator: {}", op),
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Value::Int(i) => i.fmt(f),
             Value::Float(fl) => write!(f, "{:?}", fl),
+            Value::Bool(b) => b.fmt(f),
+            Value::Complex(re, im) => {
+                let sign = if im.is_sign_negative() { '-' } else { '+' };
+                write!(f, "{:?}{}{:?}i", re, sign, im.abs())
+            }
+            Value::Quantity(q, dim) => write!(f, "{:?} {}", q, dim),
             Value::Tuple(values) => {
                 write!(f, "(")?;
                 for (i, value) in values.iter().enumerate() {
@@ -108,8 +364,111 @@ impl fmt::Display for Value {
                 }
                 write!(f, ")")
             }
+            Value::Matrix { cols, data, .. } => {
+                write!(f, "[")?;
+                for (i, value) in data.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, "{}", if i % cols == 0 { "; " } else { ", " })?;
+                    }
+                    write!(f, "{:?}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Range(start, end) => write!(f, "{}..{}", start, end),
+            Value::Seq(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Record { type_, fields } => {
+                write!(f, "{} {{ ", type_.name)?;
+                for (i, ((name, _), value)) in type_.fields.iter().zip(fields).enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, " }}")
+            }
+            Value::Enum { type_, index } => write!(f, "{}::{}", type_.name, type_.variants[*index]),
+            Value::Optional(Some(value)) => write!(f, "{}", value),
+            Value::Optional(None) => write!(f, "none"),
+            Value::Closure { type_, .. } => write!(f, "<{}>", type_),
+            Value::Symbolic(symbolic) => write!(f, "{}", symbolic),
+        }
+    }
+}
+
+/// Compute the determinant of a square matrix using Gaussian elimination with partial pivoting.
+fn determinant(n: usize, data: &[f64]) -> f64 {
+    let mut m = data.to_vec();
+    let mut det = 1.0;
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| m[a * n + col].abs().total_cmp(&m[b * n + col].abs()))
+            .expect("matrix is not empty");
+        if m[pivot * n + col] == 0.0 {
+            return 0.0;
+        }
+        if pivot != col {
+            for j in 0..n {
+                m.swap(pivot * n + j, col * n + j);
+            }
+            det = -det;
+        }
+
+        det *= m[col * n + col];
+        for row in col + 1..n {
+            let factor = m[row * n + col] / m[col * n + col];
+            for j in col..n {
+                m[row * n + j] -= factor * m[col * n + j];
+            }
+        }
+    }
+    det
+}
+
+/// Compute the inverse of a square matrix using Gauss-Jordan elimination with partial pivoting.
+///
+/// Like a float division by zero, inverting a singular matrix yields non-finite values.
+fn inverse(n: usize, data: &[f64]) -> Vec<f64> {
+    let mut m = data.to_vec();
+    let mut inv = vec![0.0; n * n];
+    for i in 0..n {
+        inv[i * n + i] = 1.0;
+    }
+
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&a, &b| m[a * n + col].abs().total_cmp(&m[b * n + col].abs()))
+            .expect("matrix is not empty");
+        for j in 0..n {
+            m.swap(pivot * n + j, col * n + j);
+            inv.swap(pivot * n + j, col * n + j);
+        }
+
+        let p = m[col * n + col];
+        for j in 0..n {
+            m[col * n + j] /= p;
+            inv[col * n + j] /= p;
+        }
+
+        for row in 0..n {
+            if row != col {
+                let factor = m[row * n + col];
+                for j in 0..n {
+                    m[row * n + j] -= factor * m[col * n + j];
+                    inv[row * n + j] -= factor * inv[col * n + j];
+                }
+            }
         }
     }
+    inv
 }
 
 struct Interpreter<'a, W: Write> {
@@ -117,7 +476,11 @@ struct Interpreter<'a, W: Write> {
     writer: &'a mut W,
 
     // state
-    vars: HashMap<String, Value>,
+    /// variable scopes, innermost last
+    vars: Vec<HashMap<String, Value>>,
+    rng: Rng,
+    /// status requested by an `exit` statement, which stops the program
+    exit_code: Option<i32>,
 }
 
 impl<'a, W: Write> Interpreter<'a, W> {
@@ -125,12 +488,23 @@ impl<'a, W: Write> Interpreter<'a, W> {
         Self {
             input,
             writer,
-            vars: HashMap::new(),
+            vars: vec![HashMap::new()],
+            rng: Rng::default(),
+            exit_code: None,
         }
     }
 
-    fn run(&mut self) -> Result<()> {
-        for stmt in self.input.ast.stmts() {
+    fn run(&mut self) -> Result<i32> {
+        self.run_block(self.input.ast.stmts())?;
+        Ok(self.exit_code.unwrap_or(0))
+    }
+
+    /// Run statements in order, until the program exits.
+    fn run_block(&mut self, stmts: &[CheckedStmt]) -> Result<()> {
+        for stmt in stmts {
+            if self.exit_code.is_some() {
+                break;
+            }
             self.run_stmt(stmt)?;
         }
         Ok(())
@@ -138,36 +512,213 @@ impl<'a, W: Write> Interpreter<'a, W> {
 
     fn run_stmt(&mut self, stmt: &CheckedStmt) -> Result<()> {
         match &stmt.kind {
-            StmtKind::Assign { name, value } => {
+            StmtKind::Assign { name, value } | StmtKind::Declare { name, value, .. } => {
+                let value = self.run_expr(value)?;
+                self.set_var(&name.kind, value);
+            }
+            StmtKind::Let { pattern, value } => {
                 let value = self.run_expr(value)?;
-                self.vars.insert(name.kind.clone(), value);
+                self.bind_pattern(pattern, value);
+            }
+            StmtKind::CompoundAssign { name, op, value } => {
+                let current = self.get_var(name)?;
+                let right_val = self.run_expr(value)?;
+                self.check_division(op, &current, &right_val)?;
+                let new_value = current.bin_op(&op.kind, &right_val).ok_or_else(|| {
+                    // this should never happen as the type checker should have caught this
+                    InterpreterError::TypeError(
+                        TypeError::MismatchedTypesForBinaryOp(
+                            name.meta.type_.clone(),
+                            value.meta.type_.clone(),
+                        ),
+                        op.to_error(&self.input.sources),
+                    )
+                })?;
+                self.set_var(&name.kind, new_value);
             }
             StmtKind::Print { expr } => {
                 let value = self.run_expr(expr)?;
                 writeln!(self.writer, "{}", value)?;
             }
             StmtKind::Expr { expr } => {
-                // somewhat pointless as no side effects as possible in this language
+                // only useful for side effects, e.g. drawing a random number
                 let _ = self.run_expr(expr)?;
             }
+            StmtKind::Seed { seed } => {
+                if let Value::Int(seed) = self.run_expr(seed)? {
+                    self.rng = Rng::new(seed);
+                }
+            }
+            StmtKind::Exit { code } => {
+                if let Value::Int(code) = self.run_expr(code)? {
+                    self.exit_code = Some(code);
+                }
+            }
+            StmtKind::Assert { cond, message } => {
+                if let Value::Bool(false) = self.run_expr(cond)? {
+                    return Err(InterpreterError::AssertionFailed(
+                        message.clone(),
+                        stmt.to_error(&self.input.sources),
+                    ));
+                }
+            }
+            StmtKind::For { var, iter, body } => {
+                for value in self.run_expr(iter)?.into_elements() {
+                    // each iteration runs in a fresh scope holding the loop variable
+                    self.vars.push(HashMap::from([(var.kind.clone(), value)]));
+                    let res = self.run_block(body);
+                    self.vars.pop();
+                    res?;
+
+                    if self.exit_code.is_some() {
+                        break;
+                    }
+                }
+            }
+            StmtKind::Plot {
+                name,
+                value,
+                var,
+                start,
+                end,
+                path,
+            } => {
+                let (Value::Float(start), Value::Float(end)) =
+                    (self.run_expr(start)?, self.run_expr(end)?)
+                else {
+                    unreachable!("type checker should have checked this")
+                };
+                if start.partial_cmp(&end) != Some(Ordering::Less) {
+                    return Err(InterpreterError::RuntimeError(
+                        RuntimeError::EmptyPlotInterval(start, end),
+                        stmt.to_error(&self.input.sources),
+                    ));
+                }
+
+                let mut points = vec![];
+                for x in Plot::samples(start, end) {
+                    self.vars
+                        .push(HashMap::from([(var.kind.clone(), Value::Float(x))]));
+                    let y = self.run_expr(value);
+                    self.vars.pop();
+                    if let Value::Float(y) = y? {
+                        points.push((x, y));
+                    }
+                }
+
+                let mut title = format!("{} = ", name.kind);
+                format_expr(value, &mut title)?;
+                let plot = Plot::new(title, &var.kind, &name.kind, points);
+                std::fs::write(path, plot.to_string()).map_err(|err| {
+                    InterpreterError::RuntimeError(
+                        RuntimeError::UnwritablePlot(path.clone(), err.to_string()),
+                        stmt.to_error(&self.input.sources),
+                    )
+                })?;
+            }
+            StmtKind::Match { expr, arms } => {
+                let index = match self.run_expr(expr)? {
+                    Value::Enum { index, .. } => index,
+                    _ => unreachable!("type checker should have checked this"),
+                };
+
+                // the checker guarantees that exactly one arm matches
+                for (pattern, body) in arms {
+                    if let Value::Enum {
+                        index: arm_index, ..
+                    } = self.run_expr(pattern)?
+                    {
+                        if arm_index == index {
+                            self.vars.push(HashMap::new());
+                            let res = self.run_block(body);
+                            self.vars.pop();
+                            res?;
+                            break;
+                        }
+                    }
+                }
+            }
+            // declarations are only relevant to the type checker
+            StmtKind::Record { .. } | StmtKind::Enum { .. } => {}
+            // the global variables of a module are qualified with its name by the type checker
+            StmtKind::Module { body, .. } => self.run_block(body)?,
+            StmtKind::Import { .. } | StmtKind::Use { .. } => {}
+            StmtKind::Macro { .. } => unreachable!("macros are expanded before type checking"),
         }
         Ok(())
     }
 
+    /// Integer division by zero has no result, and neither has the division of the smallest
+    /// integer by -1, which overflows. Both are reported as runtime errors, including for each
+    /// element of a tuple.
+    fn check_division(&self, op: &CheckedBinOp, left: &Value, right: &Value) -> Result<()> {
+        if op.kind != BinOpKind::Div {
+            return Ok(());
+        }
+        match (left, right) {
+            (Value::Int(i1), Value::Int(i2)) if i1.checked_div(*i2).is_none() => {
+                let err = if *i2 == 0 {
+                    RuntimeError::DivisionByZero
+                } else {
+                    RuntimeError::IntegerOverflow
+                };
+                Err(InterpreterError::RuntimeError(
+                    err,
+                    op.to_error(&self.input.sources),
+                ))
+            }
+            (Value::Tuple(values), _) => values
+                .iter()
+                .try_for_each(|value| self.check_division(op, value, right)),
+            _ => Ok(()),
+        }
+    }
+
+    fn get_var(&self, name: &CheckedVarName) -> Result<Value> {
+        let value = self
+            .vars
+            .iter()
+            .rev()
+            .find_map(|s| s.get::<String>(name.as_ref()));
+        let value = value.ok_or_else(|| {
+            InterpreterError::SyntaxError(
+                SyntaxError::UnknownVariable(name.to_string()),
+                name.to_error(&self.input.sources),
+            )
+        })?;
+        Ok(value.clone())
+    }
+
+    /// Assign the parts of a value to the variables of a pattern.
+    fn bind_pattern(&mut self, pattern: &CheckedPattern, value: Value) {
+        match &pattern.kind {
+            PatternKind::Name(name) => self.set_var(&name.kind, value),
+            PatternKind::Wildcard => {}
+            PatternKind::Tuple(patterns) => {
+                for (pattern, value) in patterns.iter().zip(value.into_elements()) {
+                    self.bind_pattern(pattern, value);
+                }
+            }
+        }
+    }
+
+    /// Assign a variable in the innermost scope where it exists, or create it in the innermost
+    /// scope.
+    fn set_var(&mut self, name: &str, value: Value) {
+        let scope = match self.vars.iter_mut().rev().find(|s| s.contains_key(name)) {
+            Some(scope) => scope,
+            None => self.vars.last_mut().unwrap(),
+        };
+        scope.insert(name.to_owned(), value);
+    }
+
     fn run_expr(&mut self, expr: &CheckedExpr) -> Result<Value> {
         match &expr.kind {
-            ExprKind::Variable(name) => {
-                let value = self.vars.get::<String>(name.as_ref()).ok_or_else(|| {
-                    InterpreterError::SyntaxError(
-                        SyntaxError::UnknownVariable(name.to_string()),
-                        name.to_error(&self.input.source),
-                    )
-                })?;
-                Ok(value.clone())
-            }
+            ExprKind::Variable(name) => self.get_var(name),
             ExprKind::BinOp { op, left, right } => {
                 let left_val = self.run_expr(left)?;
                 let right_val = self.run_expr(right)?;
+                self.check_division(op, &left_val, &right_val)?;
                 let value = left_val.bin_op(&op.kind, &right_val).ok_or_else(|| {
                     // this should never happen as the type checker should have caught this
                     InterpreterError::TypeError(
@@ -175,7 +726,7 @@ impl<'a, W: Write> Interpreter<'a, W> {
                             left.meta.type_.clone(),
                             right.meta.type_.clone(),
                         ),
-                        op.to_error(&self.input.source),
+                        op.to_error(&self.input.sources),
                     )
                 })?;
                 Ok(value)
@@ -184,6 +735,44 @@ impl<'a, W: Write> Interpreter<'a, W> {
                 let value = self.run_expr(operand)?;
                 Ok(value.unary_op(&op.kind))
             }
+            ExprKind::Call { name, args } => {
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.run_expr(arg)?);
+                }
+                match name.meta.type_ {
+                    Type::Function { .. } => {
+                        let closure = self.get_var(name)?;
+                        self.call_closure(&closure, values)
+                    }
+                    _ => self.run_builtin(name, values),
+                }
+            }
+            ExprKind::Apply { callee, args } => {
+                let closure = self.run_expr(callee)?;
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.run_expr(arg)?);
+                }
+                self.call_closure(&closure, values)
+            }
+            ExprKind::Lambda {
+                params,
+                body,
+                captures,
+                ..
+            } => {
+                let mut env = HashMap::new();
+                for capture in captures {
+                    env.insert(capture.kind.clone(), self.get_var(capture)?);
+                }
+                Ok(Value::Closure {
+                    type_: expr.meta.type_.clone(),
+                    params: params.iter().map(|(param, _)| param.kind.clone()).collect(),
+                    body: Rc::new((**body).clone()),
+                    env,
+                })
+            }
             ExprKind::Tuple(exprs) => {
                 let mut values = Vec::new();
                 for expr in exprs {
@@ -191,19 +780,291 @@ impl<'a, W: Write> Interpreter<'a, W> {
                 }
                 Ok(Value::Tuple(values))
             }
+            ExprKind::Matrix(rows) => {
+                let mut data = Vec::new();
+                for row in rows {
+                    for expr in row {
+                        match self.run_expr(expr)? {
+                            Value::Float(fl) => data.push(fl),
+                            _ => unreachable!("type checker should have checked this"),
+                        }
+                    }
+                }
+                Ok(Value::matrix(rows.len(), rows[0].len(), data))
+            }
+            ExprKind::Record { fields, .. } => {
+                let Type::Record(type_) = &expr.meta.type_ else {
+                    unreachable!("type checker should have checked this")
+                };
+
+                // fields are evaluated in source order, but stored in declaration order
+                let mut values = vec![None; type_.fields.len()];
+                for (field, value) in fields {
+                    let (index, _) = type_
+                        .field(&field.kind)
+                        .expect("type checker should have checked this");
+                    values[index] = Some(self.run_expr(value)?);
+                }
+                Ok(Value::Record {
+                    type_: type_.clone(),
+                    fields: values.into_iter().flatten().collect(),
+                })
+            }
+            ExprKind::Variant { variant, .. } => match &expr.meta.type_ {
+                Type::Enum(type_) => Ok(Value::Enum {
+                    type_: type_.clone(),
+                    index: type_
+                        .variant(&variant.kind)
+                        .expect("type checker should have checked this"),
+                }),
+                _ => unreachable!("type checker should have checked this"),
+            },
+            ExprKind::TupleIndex { expr: tuple, index } => match self.run_expr(tuple)? {
+                Value::Tuple(mut values) => Ok(values.swap_remove(*index)),
+                _ => unreachable!("type checker should have checked this"),
+            },
+            ExprKind::Field {
+                expr: record,
+                field,
+            } => match self.run_expr(record)? {
+                Value::Record { type_, mut fields } => {
+                    let (index, _) = type_
+                        .field(&field.kind)
+                        .expect("type checker should have checked this");
+                    Ok(fields.swap_remove(index))
+                }
+                _ => unreachable!("type checker should have checked this"),
+            },
+            // integer literals may have been resolved to floats by the type checker
+            ExprKind::Integer(i) if expr.meta.type_ == Type::Float => Ok(Value::Float(*i as f64)),
             ExprKind::Integer(i) => Ok(Value::Int(*i)),
             ExprKind::Float(fl) => Ok(Value::Float(*fl)),
+            ExprKind::Bool(b) => Ok(Value::Bool(*b)),
+            ExprKind::Imaginary(fl) => Ok(Value::Complex(0.0, *fl)),
+            ExprKind::Quantity { value, unit } => {
+                let unit =
+                    Unit::from_name(unit.as_ref()).expect("type checker should have checked this");
+                match self.run_expr(value)? {
+                    // values are stored in base units
+                    Value::Float(fl) => Ok(Value::quantity(fl * unit.scale, unit.dim)),
+                    _ => unreachable!("type checker should have checked this"),
+                }
+            }
+            ExprKind::Range { start, end } => match (self.run_expr(start)?, self.run_expr(end)?) {
+                (Value::Int(start), Value::Int(end)) => Ok(Value::Range(start, end)),
+                _ => unreachable!("type checker should have checked this"),
+            },
+            ExprKind::Convert(operand) => match (&expr.meta.type_, self.run_expr(operand)?) {
+                (Type::Optional(_), value) => Ok(Value::Optional(Some(Box::new(value)))),
+                (_, Value::Int(i)) => Ok(Value::Float(i as f64)),
+                (_, Value::Tuple(values)) => Ok(Value::Tuple(
+                    values
+                        .into_iter()
+                        .map(|value| match value {
+                            Value::Int(i) => Value::Float(i as f64),
+                            _ => unreachable!("type checker only converts tuples of ints"),
+                        })
+                        .collect(),
+                )),
+                _ => {
+                    unreachable!("type checker only inserts int to float and optional conversions")
+                }
+            },
+            ExprKind::None => Ok(Value::Optional(None)),
+            // the default is only evaluated if there is no value
+            ExprKind::Unwrap {
+                expr: optional,
+                default,
+            } => match self.run_expr(optional)? {
+                Value::Optional(Some(value)) => Ok(*value),
+                Value::Optional(None) => self.run_expr(default),
+                _ => unreachable!("type checker should have checked this"),
+            },
+            // only the selected branch is evaluated
+            ExprKind::Conditional {
+                cond,
+                then_branch,
+                else_branch,
+            } => match self.run_expr(cond)? {
+                Value::Bool(true) => self.run_expr(then_branch),
+                Value::Bool(false) => self.run_expr(else_branch),
+                _ => unreachable!("type checker should have checked this"),
+            },
+            ExprKind::Deriv { expr: body, var } => {
+                let symbolic = match body.meta.type_ {
+                    Type::Symbolic => match self.run_expr(body)? {
+                        Value::Symbolic(symbolic) => symbolic,
+                        _ => unreachable!("type checker should have checked this"),
+                    },
+                    _ => Rc::new(Symbolic::new(
+                        &var.kind,
+                        self.bind_constants(body, &var.kind)?,
+                    )),
+                };
+                Ok(Value::Symbolic(Rc::new(symbolic.derivative(&var.kind))))
+            }
+            ExprKind::Eval {
+                expr: symbolic,
+                var,
+                value,
+            } => {
+                let Value::Symbolic(symbolic) = self.run_expr(symbolic)? else {
+                    unreachable!("type checker should have checked this")
+                };
+                let value = self.run_expr(value)?;
+                if symbolic.var != var.kind {
+                    return Err(InterpreterError::RuntimeError(
+                        RuntimeError::WrongVariable(symbolic.var.clone(), var.kind.clone()),
+                        var.to_error(&self.input.sources),
+                    ));
+                }
+
+                self.vars.push(HashMap::from([(var.kind.clone(), value)]));
+                let res = self.run_expr(&symbolic.expr);
+                self.vars.pop();
+                res
+            }
+        }
+    }
+
+    /// Replace the variables of the body of a derivative, other than the one it is taken with
+    /// respect to, by their current values.
+    fn bind_constants(&self, expr: &CheckedExpr, var: &str) -> Result<CheckedExpr> {
+        let meta = expr.meta.clone();
+        Ok(match &expr.kind {
+            ExprKind::Variable(name) if name.kind != var => match self.get_var(name)? {
+                Value::Int(i) => Expr::integer(i, meta),
+                Value::Float(fl) => Expr::float(fl, meta),
+                _ => unreachable!("type checker should have checked this"),
+            },
+            ExprKind::BinOp { op, left, right } => Expr::bin_op(
+                op.clone(),
+                self.bind_constants(left, var)?,
+                self.bind_constants(right, var)?,
+                meta,
+            ),
+            ExprKind::UnaryOp { op, operand } => {
+                Expr::unary_op(op.clone(), self.bind_constants(operand, var)?, meta)
+            }
+            ExprKind::Convert(operand) => Expr::convert(self.bind_constants(operand, var)?, meta),
+            _ => expr.clone(),
+        })
+    }
+
+    /// Call a closure, whose body only sees its parameters and captured variables.
+    fn call_closure(&mut self, closure: &Value, args: Vec<Value>) -> Result<Value> {
+        let Value::Closure {
+            params, body, env, ..
+        } = closure
+        else {
+            unreachable!("type checker should have checked this")
+        };
+
+        let mut scope = env.clone();
+        scope.extend(params.iter().cloned().zip(args));
+        let outer_vars = std::mem::replace(&mut self.vars, vec![scope]);
+        let res = self.run_expr(body);
+        self.vars = outer_vars;
+        res
+    }
+
+    fn run_builtin(&mut self, name: &CheckedVarName, args: Vec<Value>) -> Result<Value> {
+        let builtin =
+            Builtin::from_name(&name.kind).expect("type checker should have checked this");
+        match (builtin, args.as_slice()) {
+            (Builtin::Rand, []) => Ok(Value::Float(self.rng.next_float())),
+            (Builtin::RandInt, [Value::Int(lo), Value::Int(hi)]) => {
+                Ok(Value::Int(self.rng.next_int(*lo, *hi)))
+            }
+            // the absolute value of the smallest integer overflows
+            (Builtin::Abs, [Value::Int(i)]) => i.checked_abs().map(Value::Int).ok_or_else(|| {
+                InterpreterError::RuntimeError(
+                    RuntimeError::IntegerOverflow,
+                    name.to_error(&self.input.sources),
+                )
+            }),
+            _ if builtin.is_higher_order() => self.run_higher_order(builtin, args),
+            _ => Ok(self.run_builtin_unary(builtin, &args[0])),
+        }
+    }
+
+    /// Run `map`, `filter` or `fold`, calling the function argument on each element in order.
+    fn run_higher_order(&mut self, builtin: Builtin, args: Vec<Value>) -> Result<Value> {
+        let mut args = args.into_iter();
+        let seq = args.next().expect("type checker should have checked this");
+        let is_tuple = matches!(seq, Value::Tuple(_));
+        let values = seq.into_elements();
+
+        match builtin {
+            Builtin::Map => {
+                let func = args.next().expect("type checker should have checked this");
+                let mut res = Vec::with_capacity(values.len());
+                for value in values {
+                    res.push(self.call_closure(&func, vec![value])?);
+                }
+                Ok(if is_tuple {
+                    Value::Tuple(res)
+                } else {
+                    Value::Seq(res)
+                })
+            }
+            Builtin::Filter => {
+                let func = args.next().expect("type checker should have checked this");
+                let mut res = Vec::new();
+                for value in values {
+                    if let Value::Bool(true) = self.call_closure(&func, vec![value.clone()])? {
+                        res.push(value);
+                    }
+                }
+                Ok(Value::Seq(res))
+            }
+            Builtin::Fold => {
+                let mut acc = args.next().expect("type checker should have checked this");
+                let func = args.next().expect("type checker should have checked this");
+                for value in values {
+                    acc = self.call_closure(&func, vec![acc, value])?;
+                }
+                Ok(acc)
+            }
+            _ => unreachable!("only higher-order built-ins are run here"),
+        }
+    }
+
+    fn run_builtin_unary(&self, builtin: Builtin, arg: &Value) -> Value {
+        match (builtin, arg) {
+            (Builtin::Abs, Value::Float(fl)) => Value::Float(fl.abs()),
+            (Builtin::Abs, Value::Complex(re, im)) => Value::Float(re.hypot(*im)),
+            (Builtin::Abs, Value::Quantity(q, dim)) => Value::Quantity(q.abs(), *dim),
+            (Builtin::Re, Value::Complex(re, _)) => Value::Float(*re),
+            (Builtin::Im, Value::Complex(_, im)) => Value::Float(*im),
+            (Builtin::Transpose, Value::Matrix { rows, cols, data }) => {
+                let mut res = Vec::with_capacity(data.len());
+                for j in 0..*cols {
+                    for i in 0..*rows {
+                        res.push(data[i * cols + j]);
+                    }
+                }
+                Value::matrix(*cols, *rows, res)
+            }
+            (Builtin::Det, Value::Matrix { rows, data, .. }) => {
+                Value::Float(determinant(*rows, data))
+            }
+            (Builtin::Inverse, Value::Matrix { rows, cols, data }) => {
+                Value::matrix(*rows, *cols, inverse(*rows, data))
+            }
+            _ => unreachable!("type checker should have checked this"),
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::pipeline::checker::check;
+    use crate::pipeline::checker::{check, check_promote};
+    use crate::pipeline::expander::expand;
     use crate::pipeline::interpreter::interpret;
     use crate::pipeline::parser::parse;
     use crate::pipeline::tokenizer::tokenize;
-    use crate::states::InputState;
+    use crate::states::{InputState, Load};
 
     #[test]
     fn test_interpreter() {
@@ -218,11 +1079,594 @@ mod test {
         );
         let tokenized = tokenize(input).unwrap();
         let parsed = parse(tokenized).unwrap();
-        let checked = check(parsed).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_complex() {
+        let input = InputState::from(
+            r###"
+                z = 3 + 4i;
+                print z;
+                print abs(z);
+                print z * (1.0 - 2i);
+                print (1, 2) * z;
+                print re(z / 2i) + im(-z);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
 
         let mut output = String::new();
         interpret(&checked, &mut output).unwrap();
 
         insta::assert_snapshot!(output);
     }
+
+    #[test]
+    fn test_interpreter_units() {
+        let input = InputState::from(
+            r###"
+                d = 3.0 m;
+                t = 2.0 s;
+                print d / t;
+                print 1.0 km / 2.0 h;
+                print d * d / t / t * 2.0 kg;
+                print (1.0, 2.0) * d;
+                print d / 1.0 mm;
+                print 1.0 / t;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_matrix() {
+        let input = InputState::from(
+            r###"
+                a = [1.0, 2.0; 3.0, 4.0];
+                v = [1.0; 2.0];
+                print a @ v;
+                print transpose(v) @ a;
+                print a + a * 2.0 - -a;
+                print det(a);
+                print inverse(a) @ a;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_rand() {
+        let input = InputState::from(
+            r###"
+                seed(42);
+                a = rand();
+                b = rand_int(1, 6);
+                seed(42);
+                print rand() - a;
+                print rand_int(1, 6) - b;
+                rand();
+                print rand_int(3, 3);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_untyped_literals() {
+        let input = InputState::from(
+            r###"
+                x = 2.5;
+                print x * 2;
+                print 1 + x;
+                print [1, 0; 0, 2] * -x;
+                print 3 km / 2;
+                print (1.0, 2.0) * 3;
+                print 2 * 3 * x;
+                print x * -(1 + 2);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_promote() {
+        let input = InputState::from(
+            r###"
+                n = 3;
+                x = 0.5;
+                print n * x;
+                print n / 2;
+                print n / 2.0;
+                t = (1, 2);
+                print t * x;
+                print (1, 2) * 2.5;
+                print t + (0.5, 1.5);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check_promote(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_for() {
+        let input = InputState::from(
+            r###"
+                s = 0;
+                for i in 1..11 {
+                    s = s + i;
+                }
+                print s;
+                for x in (1.5, 2.5) {
+                    y = x * 2;
+                    print y;
+                }
+                i = 2.5;
+                for i in 0..2 {
+                    for j in i..2 {
+                        print (i, j);
+                    }
+                }
+                print i;
+                print 0..3;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_compound_assign() {
+        let input = InputState::from(
+            r###"
+                x = 1.5;
+                x += 2;
+                x *= x;
+                print x;
+                n = 7;
+                n -= 2;
+                n /= 2;
+                for i in 0..3 {
+                    n += i;
+                }
+                print n;
+                m = [1, 2; 3, 4];
+                m *= 2;
+                m -= m / 2;
+                print m;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_bitwise() {
+        let input = InputState::from(
+            r###"
+                flags = 12;
+                print (flags & 10, flags | 3, flags ^ 5, ~flags);
+                print (1 << 4 | 1, 256 >> 2 + 1, -16 >> 2);
+                print (1 << 32, 1 << -1, -5 >> 40, 5 >> 32);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_conditional() {
+        let input = InputState::from(
+            r###"
+                n = 0;
+                print if n != 0 then 10 / n else -1;
+                a = 3.5;
+                b = 2;
+                print if a > b then a else b;
+                print if 1 m < 1 km then true else false;
+                print (1i == 1i, 2 <= 1, 1 + 2i != 1);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check_promote(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_assert() {
+        let input = InputState::from(
+            r###"
+                x = 3;
+                assert x > 0, "x must be positive";
+                print x;
+                for i in 0..5 {
+                    assert i < 2, "i is too large";
+                    print i;
+                }
+                print x;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        let err = interpret(&checked, &mut output).err().unwrap();
+
+        insta::assert_snapshot!(output + &err.to_string());
+    }
+
+    #[test]
+    fn test_interpreter_exit() {
+        let run = |input: &str| {
+            let tokenized = tokenize(InputState::from(input)).unwrap();
+            let parsed = parse(tokenized).unwrap();
+            let checked = check(expand(parsed).unwrap()).unwrap();
+
+            let mut output = String::new();
+            let status = match interpret(&checked, &mut output) {
+                Ok(status) => status,
+                Err(err) => {
+                    output += &err.to_string();
+                    output.push('\n');
+                    err.exit_code()
+                }
+            };
+            format!("{}status: {}", output, status)
+        };
+
+        insta::assert_snapshot!("success", run("print 1;"));
+        insta::assert_snapshot!(
+            "exit in loop",
+            run("for i in 0..5 { for j in 0..5 { print j; exit(i + 2); } } print 9;")
+        );
+        insta::assert_snapshot!("runtime error", run("x = 0; print 1; print 3 / x;"));
+        insta::assert_snapshot!(
+            "integer overflow",
+            run("x = -2147483647 - 1; print 1; print x / -1;")
+        );
+        insta::assert_snapshot!(
+            "abs overflow",
+            run("x = -2147483647 - 1; print abs(-1); print abs(x);")
+        );
+        insta::assert_snapshot!("assertion failed", run(r#"assert 1 > 2, "nope";"#));
+    }
+
+    #[test]
+    fn test_interpreter_record() {
+        let input = InputState::from(
+            r###"
+                record Point { x float, y float }
+                record Segment { start Point, end Point, label int }
+                p = Point { y: 2, x: 1.5 };
+                s = Segment { start: p, end: Point { x: 4.0, y: p.y * 3.0 }, label: 7 };
+                print p;
+                print s;
+                print s.end.y - s.start.y;
+                print (if s.label > 5 then s.end else p).x;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_product() {
+        let input = InputState::from(
+            r###"
+                t = (1, 2.5, true);
+                u = ((1, 2.0), t);
+                print t;
+                print u;
+                print t.1 * 2.0;
+                print u.0.1 + u.1.1;
+                print t == (1, 2.5, true);
+                print t != (1, 2.5, true);
+                print u == ((1, 2.0), (1, 2.5, false));
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_match() {
+        let input = InputState::from(
+            r###"
+                enum Mode { Fast, Slow, Off }
+                record Job { mode Mode, n int }
+                j = Job { mode: Mode::Slow, n: 3 };
+                print j;
+                for i in 0..3 {
+                    m = if i == 0 then Mode::Off else if i == 1 then Mode::Fast else j.mode;
+                    match m {
+                        Mode::Slow => { x = i * 10; print x; }
+                        Mode::Fast => { print 1.5; }
+                        Mode::Off => { print m; }
+                    }
+                }
+                print j.mode == Mode::Slow;
+                print Mode::Fast != Mode::Fast;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_lambda() {
+        let input = InputState::from(
+            r###"
+                k = 3.0;
+                sq = fn(x int) int => x * x;
+                scale = fn(x float) float => x * k;
+                print sq(4);
+                print scale(2);
+                k = 10.0;
+                print scale(2.5);
+                add = fn(a int, b int) int => a + b + sq(b);
+                print add(1, 2);
+                print sq;
+                for i in 0..3 {
+                    f = fn(x int) int => x + i;
+                    print f(100);
+                }
+                twice = fn(f fn(int) int, x int) int => f(f(x));
+                print twice(x => x * 3, 2);
+                mk = fn(n int) fn(int) int => x => x * n;
+                print mk(2)(3);
+                fs = (mk(4), sq);
+                print fs.0(5);
+                record Op { apply fn(int) int }
+                op = Op { apply: mk(6) };
+                print op.apply(7);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_higher_order() {
+        let input = InputState::from(
+            r###"
+                t = (1, 2, 3, 4);
+                print map(t, x => x * 2);
+                print fold(t, 0, (a, b) => a + b);
+                evens = filter(0..10, i => (i & 1) == 0);
+                print evens;
+                k = 3;
+                print fold(map(evens, x => x + k), 0, (acc, x) => acc + x);
+                sq = fn(x int) int => x * x;
+                for x in map(0..3, sq) {
+                    print x;
+                }
+                print filter(0..3, x => x > 5);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_optional() {
+        let input = InputState::from(
+            r###"
+                x: int? = none;
+                print x;
+                print x ?? 3;
+                x = 5;
+                print x ?? 3;
+                print x == 5;
+                record P { a int, b float? }
+                print P { a: 1, b: none };
+                f = fn(v int?) int => v ?? 0;
+                print f(none) + f(7);
+                y: int? = none;
+                print y ?? x ?? 0;
+                print x ?? 1 / 0;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_module() {
+        let input =
+            InputState::from_file("testdata/modules/main.calc".into(), Load::Imports).unwrap();
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_let() {
+        let input = InputState::from(
+            r###"
+                t = (1, (2.5, true));
+                let (a, (b, c)) = t;
+                print a;
+                print b;
+                print c;
+                let (_, (x, _)) = t;
+                print x * 2.0;
+                for i in 0..2 {
+                    let (p, q) = (i, i * 10);
+                    print p + q;
+                }
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_interpreter_symbolic() {
+        let input = InputState::from(
+            r###"
+                d = deriv(x * x + 3 * x, x);
+                print d;
+                print eval(d, x = 2.0);
+                print deriv(d, x);
+                print deriv(deriv(d, x), x);
+                a = 4.0;
+                q = deriv(a * x / (x + 1), x);
+                print q;
+                print eval(q, x = 1.0);
+                print eval(d, y = 1.0);
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        let err = interpret(&checked, &mut output).err().unwrap();
+
+        insta::assert_snapshot!(output + &err.to_string());
+    }
+
+    #[test]
+    fn test_interpreter_plot() {
+        let path = std::env::temp_dir().join("calc_test_interpreter_plot.svg");
+        let input = InputState::from(format!(
+            r###"
+                a = 2.0;
+                plot y = a * x * x - 1 for x in -1..2, "{}";
+                print a;
+            "###,
+            path.display()
+        ));
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+        assert_eq!(output, "2.0\n");
+
+        let svg = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        insta::assert_snapshot!(svg);
+
+        // the interval is only known when running
+        let input = InputState::from(r#"b = 1.0; plot y = x for x in b..0, "empty.svg";"#);
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let err = interpret(&checked, &mut output).err().unwrap();
+        insta::assert_snapshot!("empty plot interval", err.to_string());
+    }
 }
@@ -1,15 +1,23 @@
-use crate::context::checked_ast::{CheckedBinOp, CheckedExpr, CheckedStmt, CheckedUnaryOp, Type};
-use crate::data::ast::{BinOpKind, ExprKind, StmtKind, UnaryOpKind};
+use crate::context::checked_ast::{
+    CheckedBinOp, CheckedExpr, CheckedPattern, CheckedStmt, CheckedUnaryOp, CheckedVarName, Type,
+    TypeInfo,
+};
+use crate::data::ast::{BinOpKind, ExprKind, PatternKind, StmtKind, TypeName, UnaryOpKind};
+use crate::data::builtin::Builtin;
+use crate::data::unit::Unit;
+use crate::errors::{LlvmError, Spanned};
 use crate::states::CheckedState;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
-pub(crate) fn llvm_codegen<W: fmt::Write>(input: &CheckedState, writer: &mut W) -> fmt::Result {
+type Result<T> = std::result::Result<T, LlvmError>;
+
+pub(crate) fn llvm_codegen<W: fmt::Write>(input: &CheckedState, writer: &mut W) -> Result<()> {
     let mut codegen = LlvmCodegen::new(input, writer);
     codegen.run()
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 enum LlvmType {
     Builtin(Type),
     // more types here, e.g. tuple
@@ -18,10 +26,24 @@ enum LlvmType {
 impl LlvmType {
     pub fn init_val(&self) -> &'static str {
         match self {
-            Self::Builtin(Type::Float) => "0.0",
-            Self::Builtin(Type::Integer) => "0",
+            Self::Builtin(Type::Float | Type::Quantity(_)) => "0.0",
+            Self::Builtin(Type::Integer | Type::Enum(_)) => "0",
+            Self::Builtin(Type::Bool) => "false",
+            Self::Builtin(
+                Type::Complex
+                | Type::Matrix { .. }
+                | Type::Range
+                | Type::Seq(_)
+                | Type::Tuple { .. }
+                | Type::Product(_)
+                | Type::Record(_)
+                | Type::Function { .. }
+                | Type::Optional(_),
+            ) => "zeroinitializer",
             Self::Builtin(Type::Stmt) => unreachable!(),
-            Self::Builtin(Type::Tuple { .. }) => todo!(),
+            Self::Builtin(Type::Symbolic) => {
+                unreachable!("symbolic values are rejected by codegen_expr")
+            }
         }
     }
 }
@@ -30,17 +52,117 @@ impl fmt::Display for LlvmType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             LlvmType::Builtin(type_) => match type_ {
-                Type::Integer => write!(f, "i32"),
-                Type::Float => write!(f, "double"),
-                Type::Tuple { .. } => todo!(),
+                // enum values are variant indices
+                Type::Integer | Type::Enum(_) => write!(f, "i32"),
+                Type::Bool => write!(f, "i1"),
+                Type::Float | Type::Quantity(_) => write!(f, "double"),
+                Type::Complex => write!(f, "{{double, double}}"),
+                Type::Range => write!(f, "{{i32, i32}}"),
+                // sequences pair their length with a pointer to their heap-allocated elements
+                Type::Seq(type_) => write!(f, "{{i32, {}*}}", LlvmType::Builtin((**type_).clone())),
+                Type::Matrix { rows, cols } => write!(f, "[{} x double]", rows * cols),
+                Type::Tuple { type_, len } => {
+                    write!(f, "[{} x {}]", len, LlvmType::Builtin((**type_).clone()))
+                }
+                // named struct types are declared from the type table
+                Type::Record(record) => write!(f, "%{}", record.name),
+                Type::Product(types) => {
+                    write!(f, "{{")?;
+                    for (i, type_) in types.iter().enumerate() {
+                        if i != 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", LlvmType::Builtin(type_.clone()))?;
+                    }
+                    write!(f, "}}")
+                }
+                // closures pair a function pointer with a pointer to their environment
+                Type::Function { .. } => write!(f, "{{{}, i8*}}", fn_ptr_type(type_)),
+                // optionals pair a flag telling whether there is a value with the value itself
+                Type::Optional(type_) => {
+                    write!(f, "{{i1, {}}}", LlvmType::Builtin((**type_).clone()))
+                }
+                Type::Symbolic => unreachable!("symbolic values are rejected by codegen_expr"),
                 Type::Stmt => unreachable!(),
             },
         }
     }
 }
 
+/// Type of the function pointer of a closure, whose environment is passed as first argument.
+fn fn_ptr_type(type_: &Type) -> String {
+    let Type::Function { params, ret } = type_ else {
+        unreachable!("not a function type: {}", type_)
+    };
+    let mut params: Vec<_> = params
+        .iter()
+        .map(|type_| LlvmType::Builtin(type_.clone()).to_string())
+        .collect();
+    params.insert(0, "i8*".to_owned());
+    format!(
+        "{} ({})*",
+        LlvmType::Builtin((**ret).clone()),
+        params.join(", ")
+    )
+}
+
+/// Whether the values of a type hold symbolic expressions, which can't be lowered.
+fn has_symbolic(type_: &Type) -> bool {
+    match type_ {
+        Type::Symbolic => true,
+        Type::Seq(type_) | Type::Optional(type_) | Type::Tuple { type_, .. } => has_symbolic(type_),
+        Type::Product(types) => types.iter().any(has_symbolic),
+        Type::Function { params, ret } => params.iter().any(has_symbolic) || has_symbolic(ret),
+        _ => false,
+    }
+}
+
+/// Types of the elements of a tuple. Heterogeneous tuples are lowered to anonymous structs, and
+/// homogeneous ones to arrays, whose elements are both accessed with `extractvalue`.
+fn tuple_types(type_: &Type) -> Vec<Type> {
+    match type_ {
+        Type::Tuple { type_, len } => vec![(**type_).clone(); *len],
+        Type::Product(types) => types.clone(),
+        _ => unreachable!("not a tuple type: {}", type_),
+    }
+}
+
+/// Symbol of a global variable. Variables are prefixed so that they can't collide with the runtime
+/// and generated functions, and those of a module are already qualified by the type checker, e.g.
+/// `geo.radius` gives `@calc.geo.radius`.
+fn global_symbol(name: &str) -> String {
+    format!("@calc.{}", name)
+}
+
+/// Write the definition of a function, given its signature (e.g. `i32 @calc_main()`).
+fn write_function<W: fmt::Write>(writer: &mut W, signature: &str, body: &[String]) -> fmt::Result {
+    writeln!(writer, "define {} {{", signature)?;
+    for line in body {
+        if line.ends_with(':') {
+            writeln!(writer, "{line}")?;
+        } else {
+            writeln!(writer, "    {line}")?;
+        }
+    }
+    writeln!(writer, "}}")
+}
+
+/// Elements iterated over by a loop or a higher-order built-in.
+enum LlvmIter {
+    /// elements of a tuple literal, over which loops are unrolled
+    Values(Vec<LlvmValue>),
+    Range {
+        start: String,
+        end: String,
+    },
+    Seq {
+        value: LlvmValue,
+        elem_type: Type,
+    },
+}
+
 struct LlvmValue {
-    // TODO: the representation of the value must be changed to an enum for tuple
+    // Note: matrices live in memory, so their register holds a pointer to the data.
     pub register: String,
     pub type_: LlvmType,
 }
@@ -57,8 +179,16 @@ struct LlvmCodegen<'a, W: fmt::Write> {
 
     // state
     code: Vec<String>,
+    allocas: Vec<String>,
     globals: BTreeMap<String, LlvmType>,
+    /// variables scoped to loop bodies (innermost last), mapped to their stack slot
+    locals: Vec<HashMap<String, (String, LlvmType)>>,
+    strings: Vec<String>,
+    /// signature and body of the functions generated for lambdas
+    functions: Vec<(String, Vec<String>)>,
     id: usize,
+    /// label of the basic block currently being emitted, as needed by `phi` nodes
+    cur_label: String,
 }
 
 impl<'a, W: fmt::Write> LlvmCodegen<'a, W> {
@@ -67,8 +197,13 @@ impl<'a, W: fmt::Write> LlvmCodegen<'a, W> {
             input,
             writer,
             code: Vec::new(),
+            allocas: Vec::new(),
             globals: BTreeMap::new(),
+            locals: Vec::new(),
+            strings: Vec::new(),
+            functions: Vec::new(),
             id: 0,
+            cur_label: String::new(),
         }
     }
 
@@ -86,7 +221,70 @@ impl<'a, W: fmt::Write> LlvmCodegen<'a, W> {
         format!("%r{}", self.next_id())
     }
 
-    fn run(&mut self) -> fmt::Result {
+    fn next_label(&mut self) -> String {
+        format!("L{}", self.next_id())
+    }
+
+    fn label(&mut self, label: &str) {
+        self.code.push(format!("{}:", label));
+        self.cur_label = label.to_owned();
+    }
+
+    /// Allocate a stack slot in the entry block and return a pointer to it.
+    fn alloca(&mut self, type_: &LlvmType) -> String {
+        let reg = self.next_reg();
+        self.allocas.push(format!("{} = alloca {}", reg, type_));
+        reg
+    }
+
+    /// Emit a loop over `0..n`, calling `body` to generate the loop body with the register
+    /// holding the loop index.
+    fn codegen_loop(&mut self, n: usize, body: impl FnOnce(&mut Self, &str)) {
+        self.codegen_range_loop("0", &n.to_string(), body);
+    }
+
+    /// Emit a loop over `start..end`, where the bounds are `i32` registers or constants.
+    fn codegen_range_loop(&mut self, start: &str, end: &str, body: impl FnOnce(&mut Self, &str)) {
+        let counter = self.alloca(&LlvmType::Builtin(Type::Integer));
+        let cond_label = self.next_label();
+        let body_label = self.next_label();
+        let end_label = self.next_label();
+
+        self.out(format!("store i32 {}, i32* {}", start, counter));
+        self.out(format!("br label %{}", cond_label));
+
+        self.label(&cond_label);
+        let index = self.next_reg();
+        let cond = self.next_reg();
+        self.out(format!("{} = load i32, i32* {}", index, counter));
+        self.out(format!("{} = icmp slt i32 {}, {}", cond, index, end));
+        self.out(format!(
+            "br i1 {}, label %{}, label %{}",
+            cond, body_label, end_label
+        ));
+
+        self.label(&body_label);
+        body(self, &index);
+        let next = self.next_reg();
+        self.out(format!("{} = add i32 {}, 1", next, index));
+        self.out(format!("store i32 {}, i32* {}", next, counter));
+        self.out(format!("br label %{}", cond_label));
+
+        self.label(&end_label);
+    }
+
+    /// Register a string constant and return a `i8*` pointer to it.
+    fn string_ptr(&mut self, s: &str) -> String {
+        let id = self.strings.len();
+        let len = s.len() + 1;
+        self.strings.push(s.to_owned());
+        format!(
+            "getelementptr inbounds ([{0} x i8], [{0} x i8]* @.str{1}, i32 0, i32 0)",
+            len, id
+        )
+    }
+
+    fn run(&mut self) -> Result<()> {
         // generate code for each statement
         for stmt in self.input.ast.stmts() {
             self.codegen_stmt(stmt)?;
@@ -95,41 +293,150 @@ impl<'a, W: fmt::Write> LlvmCodegen<'a, W> {
         // write output
         writeln!(self.writer, "declare void @_print_int(i32 %x)")?;
         writeln!(self.writer, "declare void @_print_float(double %x)")?;
+        writeln!(self.writer, "declare void @_print_bool(i1 zeroext %x)")?;
+        writeln!(
+            self.writer,
+            "declare void @_print_complex(double %re, double %im)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_print_quantity(double %x, i8* %unit)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_print_matrix(double* %data, i32 %rows, i32 %cols)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare double @_mat_det(double* %data, i32 %n)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_mat_inverse(double* %data, double* %res, i32 %n)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_print_range(i32 %start, i32 %end)"
+        )?;
+        writeln!(self.writer, "declare void @_write_int(i32 %x)")?;
+        writeln!(self.writer, "declare void @_write_float(double %x)")?;
+        writeln!(self.writer, "declare void @_write_bool(i1 zeroext %x)")?;
+        writeln!(
+            self.writer,
+            "declare void @_write_complex(double %re, double %im)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_write_range(i32 %start, i32 %end)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_write_quantity(double %x, i8* %unit)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_write_matrix(double* %data, i32 %rows, i32 %cols)"
+        )?;
+        writeln!(self.writer, "declare void @_write_str(i8* %s)")?;
+        writeln!(self.writer, "declare void @_seed(i32 %seed)")?;
+        writeln!(
+            self.writer,
+            "declare void @_assert_fail(i8* %msg, i8* %file, i32 %line, i32 %col)"
+        )?;
+        writeln!(
+            self.writer,
+            "declare void @_runtime_error(i8* %msg, i8* %file, i32 %line, i32 %col)"
+        )?;
+        writeln!(self.writer, "declare double @_rand()")?;
+        writeln!(self.writer, "declare i32 @_rand_int(i32 %lo, i32 %hi)")?;
+        writeln!(self.writer, "declare double @llvm.fabs.f64(double %x)")?;
+        writeln!(self.writer, "declare double @llvm.sqrt.f64(double %x)")?;
+        writeln!(self.writer, "declare i8* @malloc(i64 %size)")?;
         writeln!(self.writer)?;
 
+        // declare record types
+        for record in self.input.ast.types() {
+            let fields: Vec<_> = record
+                .fields
+                .iter()
+                .map(|(_, type_)| LlvmType::Builtin(type_.clone()).to_string())
+                .collect();
+            writeln!(
+                self.writer,
+                "%{} = type {{{}}}",
+                record.name,
+                fields.join(", ")
+            )?;
+        }
+
         // declare global variables
         for (name, ltype) in &self.globals {
             writeln!(
                 self.writer,
-                "@{} = global {} {}",
-                name,
+                "{} = global {} {}",
+                global_symbol(name),
                 ltype,
                 ltype.init_val()
             )?;
         }
 
+        // declare string constants
+        for (id, string) in self.strings.iter().enumerate() {
+            let escaped: String = string
+                .bytes()
+                .map(|b| {
+                    if (b.is_ascii_graphic() || b == b' ') && b != b'"' && b != b'\\' {
+                        (b as char).to_string()
+                    } else {
+                        format!("\\{:02X}", b)
+                    }
+                })
+                .collect();
+            writeln!(
+                self.writer,
+                "@.str{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+                id,
+                string.len() + 1,
+                escaped
+            )?;
+        }
+
         writeln!(self.writer)?;
-        writeln!(self.writer, "define void @calc_main() {{")?;
+        let main_body: Vec<_> = self
+            .allocas
+            .iter()
+            .chain(&self.code)
+            .cloned()
+            .chain(["ret i32 0".to_owned()])
+            .collect();
+        write_function(self.writer, "i32 @calc_main()", &main_body)?;
 
-        for line in &self.code {
-            writeln!(self.writer, "    {line}")?;
+        for (signature, body) in &self.functions {
+            writeln!(self.writer)?;
+            write_function(self.writer, signature, body)?;
         }
 
-        writeln!(self.writer, "    ret void")?;
-        writeln!(self.writer, "}}")?;
-
         Ok(())
     }
 
-    fn codegen_stmt(&mut self, stmt: &CheckedStmt) -> fmt::Result {
+    fn codegen_stmt(&mut self, stmt: &CheckedStmt) -> Result<()> {
         match &stmt.kind {
-            StmtKind::Assign { name, value } => {
+            StmtKind::Assign { name, value } | StmtKind::Declare { name, value, .. } => {
                 let llvm_value = self.codegen_expr(value)?;
-                self.out(format!(
-                    "store {} {}, {}* @{}",
-                    llvm_value.type_, llvm_value.register, llvm_value.type_, name
-                ));
-                self.globals.insert(name.to_string(), llvm_value.type_);
+                self.codegen_assign(name, &llvm_value);
+            }
+            StmtKind::Let { pattern, value } => {
+                let llvm_value = self.codegen_expr(value)?;
+                self.codegen_pattern(pattern, &llvm_value);
+            }
+            StmtKind::CompoundAssign { name, op, value } => {
+                let current = self.codegen_variable(name.as_ref())?;
+                let right = self.codegen_expr(value)?;
+                let llvm_value = self.codegen_bin_op_values(op, current, right)?;
+                let (ptr, _) = self
+                    .lookup_var(name.as_ref())
+                    .expect("type checker should have checked this");
+                self.codegen_store(&llvm_value, &ptr);
             }
             StmtKind::Print { expr } => {
                 let llvm_value = self.codegen_expr(expr)?;
@@ -138,7 +445,61 @@ impl<'a, W: fmt::Write> LlvmCodegen<'a, W> {
                     Type::Stmt => unreachable!("expression cannot have Stmt type"),
                     Type::Integer => "_print_int",
                     Type::Float => "_print_float",
-                    Type::Tuple { .. } => todo!(),
+                    Type::Bool => "_print_bool",
+                    Type::Complex => {
+                        let (re, im) = self.codegen_complex_parts(&llvm_value);
+                        self.out(format!(
+                            "call void @_print_complex(double {}, double {})",
+                            re, im
+                        ));
+                        return Ok(());
+                    }
+                    Type::Quantity(dim) => {
+                        let unit = self.string_ptr(&dim.to_string());
+                        self.out(format!(
+                            "call void @_print_quantity(double {}, i8* {})",
+                            llvm_value.register, unit
+                        ));
+                        return Ok(());
+                    }
+                    Type::Matrix { rows, cols } => {
+                        let data = self.codegen_matrix_data(&llvm_value);
+                        self.out(format!(
+                            "call void @_print_matrix(double* {}, i32 {}, i32 {})",
+                            data, rows, cols
+                        ));
+                        return Ok(());
+                    }
+                    Type::Range => {
+                        let start = self.next_reg();
+                        let end = self.next_reg();
+                        self.out(format!(
+                            "{} = extractvalue {} {}, 0",
+                            start, llvm_value.type_, llvm_value.register
+                        ));
+                        self.out(format!(
+                            "{} = extractvalue {} {}, 1",
+                            end, llvm_value.type_, llvm_value.register
+                        ));
+                        self.out(format!(
+                            "call void @_print_range(i32 {}, i32 {})",
+                            start, end
+                        ));
+                        return Ok(());
+                    }
+                    Type::Record(_)
+                    | Type::Tuple { .. }
+                    | Type::Product(_)
+                    | Type::Enum(_)
+                    | Type::Function { .. }
+                    | Type::Seq(_)
+                    | Type::Optional(_) => {
+                        self.codegen_write(&llvm_value, &expr.meta.type_);
+                        let newline = self.string_ptr("\n");
+                        self.out(format!("call void @_write_str(i8* {})", newline));
+                        return Ok(());
+                    }
+                    Type::Symbolic => unreachable!("symbolic values are rejected by codegen_expr"),
                 };
 
                 self.out(format!(
@@ -147,149 +508,2368 @@ impl<'a, W: fmt::Write> LlvmCodegen<'a, W> {
                 ));
             }
             StmtKind::Expr { expr } => {
-                // pointless since no possibly side effects
+                // only useful for side effects, e.g. drawing a random number
                 self.codegen_expr(expr)?;
             }
+            StmtKind::Seed { seed } => {
+                let llvm_value = self.codegen_expr(seed)?;
+                self.out(format!("call void @_seed(i32 {})", llvm_value.register));
+            }
+            StmtKind::Assert { cond, message } => {
+                let llvm_value = self.codegen_expr(cond)?;
+                let fail_label = self.next_label();
+                let ok_label = self.next_label();
+                self.out(format!(
+                    "br i1 {}, label %{}, label %{}",
+                    llvm_value.register, ok_label, fail_label
+                ));
+
+                self.label(&fail_label);
+                self.codegen_failure("_assert_fail", message, stmt);
+
+                self.label(&ok_label);
+            }
+            StmtKind::Exit { code } => {
+                let llvm_value = self.codegen_expr(code)?;
+                self.out(format!("ret i32 {}", llvm_value.register));
+
+                // any code following the exit is unreachable, but must still be in a block
+                let label = self.next_label();
+                self.label(&label);
+            }
+            StmtKind::For { var, iter, body } => self.codegen_for(var.as_ref(), iter, body)?,
+            // plots are only supported by the interpreter
+            StmtKind::Plot { .. } => return Err(self.unsupported("plots", stmt)),
+            StmtKind::Match { expr, arms } => {
+                let Type::Enum(enum_) = &expr.meta.type_ else {
+                    unreachable!("type checker should have checked this")
+                };
+                let value = self.codegen_expr(expr)?;
+
+                // arms may be in any order, whereas switch cases are in variant order
+                self.codegen_switch(&value.register, enum_.variants.len(), |this, index| {
+                    let (_, body) = arms
+                        .iter()
+                        .find(|(pattern, _)| match &pattern.kind {
+                            ExprKind::Variant { variant, .. } => {
+                                enum_.variant(&variant.kind) == Some(index)
+                            }
+                            _ => false,
+                        })
+                        .expect("type checker should have checked this");
+
+                    // as for loops, variables defined in an arm are scoped to it
+                    this.locals.push(HashMap::new());
+                    let res = body.iter().try_for_each(|stmt| this.codegen_stmt(stmt));
+                    this.locals.pop();
+                    res
+                })?;
+            }
+            // record types are declared from the type table, enum values are plain integers, and
+            // imported files and used modules are merged into the program
+            StmtKind::Record { .. }
+            | StmtKind::Enum { .. }
+            | StmtKind::Import { .. }
+            | StmtKind::Use { .. } => {}
+            StmtKind::Module { body, .. } => {
+                body.iter().try_for_each(|stmt| self.codegen_stmt(stmt))?
+            }
+            StmtKind::Macro { .. } => unreachable!("macros are expanded before type checking"),
         }
         Ok(())
     }
 
-    fn codegen_expr(&mut self, expr: &CheckedExpr) -> Result<LlvmValue, fmt::Error> {
-        match &expr.kind {
-            ExprKind::Variable(name) => self.codegen_variable(name.as_ref()),
-            ExprKind::UnaryOp { op, operand } => self.codegen_unary_op(op, operand),
-            ExprKind::BinOp { op, left, right } => self.codegen_bin_op(op, left, right),
-            ExprKind::Tuple(..) => todo!(),
-            ExprKind::Integer(i) => Ok(LlvmValue::new(
-                i.to_string(),
-                LlvmType::Builtin(Type::Integer),
-            )),
-            ExprKind::Float(f) => Ok(LlvmValue::new(
-                format!("{:?}", f),
-                LlvmType::Builtin(Type::Float),
-            )),
+    fn codegen_assign(&mut self, name: &CheckedVarName, llvm_value: &LlvmValue) {
+        let ptr = match self.lookup_local(name.as_ref()) {
+            Some((ptr, type_)) if type_ == llvm_value.type_ => ptr,
+            None if self.locals.is_empty() || self.globals.contains_key(&name.kind) => {
+                self.globals
+                    .insert(name.kind.clone(), llvm_value.type_.clone());
+                global_symbol(&name.kind)
+            }
+            // variables first assigned in a loop body are local to it (and may be redefined with
+            // another type)
+            _ => {
+                let ptr = self.alloca(&llvm_value.type_);
+                self.locals
+                    .last_mut()
+                    .unwrap()
+                    .insert(name.kind.clone(), (ptr.clone(), llvm_value.type_.clone()));
+                ptr
+            }
+        };
+        self.codegen_store(llvm_value, &ptr);
+    }
+
+    /// Assign the elements of a value to the variables of a pattern.
+    fn codegen_pattern(&mut self, pattern: &CheckedPattern, llvm_value: &LlvmValue) {
+        match &pattern.kind {
+            PatternKind::Name(name) => self.codegen_assign(name, llvm_value),
+            PatternKind::Wildcard => {}
+            PatternKind::Tuple(patterns) => {
+                for (index, pattern) in patterns.iter().enumerate() {
+                    if let PatternKind::Wildcard = pattern.kind {
+                        continue;
+                    }
+                    let element = self.codegen_extract(llvm_value, index, &pattern.meta.type_);
+                    self.codegen_pattern(pattern, &element);
+                }
+            }
         }
     }
 
-    fn codegen_variable(&mut self, name: &str) -> Result<LlvmValue, fmt::Error> {
-        let reg = self.next_reg();
-        let type_ = self
-            .globals
-            .get(name)
-            .expect("type checker should have checked this")
-            .clone();
+    /// Write a value without a trailing newline, as needed to print the fields of a record.
+    fn codegen_write(&mut self, value: &LlvmValue, type_: &Type) {
+        match type_ {
+            Type::Record(record) => {
+                let open = self.string_ptr(&format!("{} {{ ", record.name));
+                self.out(format!("call void @_write_str(i8* {})", open));
+                for (i, (name, field_type)) in record.fields.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    let label = self.string_ptr(&format!("{}{}: ", sep, name));
+                    self.out(format!("call void @_write_str(i8* {})", label));
 
-        self.out(format!("{0} = load {1}, {1}* @{2}", reg, type_, name));
-        Ok(LlvmValue::new(reg, type_))
+                    let field_value = self.codegen_extract(value, i, field_type);
+                    self.codegen_write(&field_value, field_type);
+                }
+                let close = self.string_ptr(" }");
+                self.out(format!("call void @_write_str(i8* {})", close));
+            }
+            Type::Tuple { .. } | Type::Product(_) => {
+                for (i, elem_type) in tuple_types(type_).iter().enumerate() {
+                    let sep = self.string_ptr(if i == 0 { "(" } else { ", " });
+                    self.out(format!("call void @_write_str(i8* {})", sep));
+                    let elem = self.codegen_extract(value, i, elem_type);
+                    self.codegen_write(&elem, elem_type);
+                }
+                let close = self.string_ptr(")");
+                self.out(format!("call void @_write_str(i8* {})", close));
+            }
+            Type::Quantity(dim) => {
+                let unit = self.string_ptr(&dim.to_string());
+                self.out(format!(
+                    "call void @_write_quantity(double {}, i8* {})",
+                    value.register, unit
+                ));
+            }
+            Type::Matrix { rows, cols } => {
+                let data = self.codegen_matrix_data(value);
+                self.out(format!(
+                    "call void @_write_matrix(double* {}, i32 {}, i32 {})",
+                    data, rows, cols
+                ));
+            }
+            Type::Complex => {
+                let (re, im) = self.codegen_complex_parts(value);
+                self.out(format!(
+                    "call void @_write_complex(double {}, double {})",
+                    re, im
+                ));
+            }
+            Type::Range => {
+                let start = self.next_reg();
+                let end = self.next_reg();
+                self.out(format!(
+                    "{} = extractvalue {} {}, 0",
+                    start, value.type_, value.register
+                ));
+                self.out(format!(
+                    "{} = extractvalue {} {}, 1",
+                    end, value.type_, value.register
+                ));
+                self.out(format!(
+                    "call void @_write_range(i32 {}, i32 {})",
+                    start, end
+                ));
+            }
+            Type::Enum(enum_) => {
+                self.codegen_switch(&value.register, enum_.variants.len(), |this, index| {
+                    let name =
+                        this.string_ptr(&format!("{}::{}", enum_.name, enum_.variants[index]));
+                    this.out(format!("call void @_write_str(i8* {})", name));
+                    Ok(())
+                })
+                .expect("writing a variant name cannot fail");
+            }
+            Type::Function { .. } => {
+                let name = self.string_ptr(&format!("<{}>", type_));
+                self.out(format!("call void @_write_str(i8* {})", name));
+            }
+            Type::Seq(elem_type) => {
+                let open = self.string_ptr("[");
+                self.out(format!("call void @_write_str(i8* {})", open));
+                let iter = LlvmIter::Seq {
+                    value: LlvmValue::new(value.register.clone(), value.type_.clone()),
+                    elem_type: (**elem_type).clone(),
+                };
+                let first = self.alloca(&LlvmType::Builtin(Type::Bool));
+                self.out(format!("store i1 true, i1* {}", first));
+                self.codegen_for_each(iter, |this, elem| {
+                    // elements are separated by commas
+                    let is_first = this.next_reg();
+                    let sep_label = this.next_label();
+                    let elem_label = this.next_label();
+                    this.out(format!("{} = load i1, i1* {}", is_first, first));
+                    this.out(format!(
+                        "br i1 {}, label %{}, label %{}",
+                        is_first, elem_label, sep_label
+                    ));
+                    this.label(&sep_label);
+                    let sep = this.string_ptr(", ");
+                    this.out(format!("call void @_write_str(i8* {})", sep));
+                    this.out(format!("br label %{}", elem_label));
+                    this.label(&elem_label);
+                    this.out(format!("store i1 false, i1* {}", first));
+                    this.codegen_write(&elem, elem_type);
+                    Ok(())
+                })
+                .expect("writing an element cannot fail");
+                let close = self.string_ptr("]");
+                self.out(format!("call void @_write_str(i8* {})", close));
+            }
+            Type::Optional(inner) => {
+                let inner = (**inner).clone();
+                self.codegen_if_some(
+                    value,
+                    |this, value| this.codegen_write(value, &inner),
+                    |this| {
+                        let none = this.string_ptr("none");
+                        this.out(format!("call void @_write_str(i8* {})", none));
+                    },
+                );
+            }
+            Type::Integer | Type::Float | Type::Bool => {
+                let func = match type_ {
+                    Type::Integer => "_write_int",
+                    Type::Float => "_write_float",
+                    _ => "_write_bool",
+                };
+                self.out(format!(
+                    "call void @{}({} {})",
+                    func, value.type_, value.register
+                ));
+            }
+            Type::Symbolic => unreachable!("symbolic values are rejected by codegen_expr"),
+            Type::Stmt => unreachable!(),
+        }
     }
 
-    fn codegen_unary_op(
+    /// Emit a `switch` on an `i32` value in `0..cases`, calling `case` to generate the code of each
+    /// case. All cases then continue with the code following the switch.
+    fn codegen_switch(
         &mut self,
-        op: &CheckedUnaryOp,
-        operand: &CheckedExpr,
-    ) -> Result<LlvmValue, fmt::Error> {
-        let operand = self.codegen_expr(operand)?;
-        let operand_type = operand.type_.clone();
+        value: &str,
+        cases: usize,
+        mut case: impl FnMut(&mut Self, usize) -> Result<()>,
+    ) -> Result<()> {
+        let labels: Vec<_> = (0..cases).map(|_| self.next_label()).collect();
+        let end_label = self.next_label();
 
-        //TODO: should match on operand.type_ when it's properly supported
-        match &operand_type {
-            LlvmType::Builtin(type_) => self.codegen_unary_op_builtin(type_, op.kind, operand),
+        let targets: Vec<_> = labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| format!("i32 {}, label %{}", index, label))
+            .collect();
+        self.out(format!(
+            "switch i32 {}, label %{} [ {} ]",
+            value,
+            end_label,
+            targets.join(" ")
+        ));
+
+        for (index, label) in labels.iter().enumerate() {
+            self.label(label);
+            case(self, index)?;
+            self.out(format!("br label %{}", end_label));
         }
+
+        self.label(&end_label);
+        Ok(())
     }
 
-    fn codegen_unary_op_builtin(
+    /// Insert a value in an aggregate (a record or a tuple), returning the register
+    /// holding the new aggregate.
+    fn codegen_insert(
         &mut self,
-        type_: &Type,
-        op: UnaryOpKind,
+        aggregate: &str,
+        type_: &LlvmType,
         value: LlvmValue,
-    ) -> Result<LlvmValue, fmt::Error> {
-        match op {
-            UnaryOpKind::Neg => {
-                let reg = self.next_reg();
-
-                let (opcode, cst) = match type_ {
-                    Type::Integer => ("sub", "0"),
-                    Type::Float => ("fsub", "0.0"),
-                    Type::Tuple { .. } => todo!(),
-                    Type::Stmt => unreachable!(),
-                };
+        index: usize,
+    ) -> String {
+        // matrices are stored by value in aggregates
+        let value = match value.type_ {
+            LlvmType::Builtin(Type::Matrix { .. }) => self.codegen_load(&value),
+            _ => value,
+        };
+        let reg = self.next_reg();
+        self.out(format!(
+            "{} = insertvalue {} {}, {} {}, {}",
+            reg, type_, aggregate, value.type_, value.register, index
+        ));
+        reg
+    }
 
-                self.out(format!(
-                    "{} = {} {} {}, {}",
-                    reg, opcode, value.type_, cst, value.register
-                ));
+    /// Extract the value at the given index of an aggregate (a record or a tuple).
+    fn codegen_extract(&mut self, aggregate: &LlvmValue, index: usize, type_: &Type) -> LlvmValue {
+        let type_ = LlvmType::Builtin(type_.clone());
+        let reg = self.next_reg();
+        self.out(format!(
+            "{} = extractvalue {} {}, {}",
+            reg, aggregate.type_, aggregate.register, index
+        ));
 
-                Ok(LlvmValue::new(reg, value.type_))
-            }
-            UnaryOpKind::Pos => Ok(value),
+        // matrices live in memory
+        if let LlvmType::Builtin(Type::Matrix { .. }) = type_ {
+            let ptr = self.alloca(&type_);
+            self.out(format!("store {0} {1}, {0}* {2}", type_, reg, ptr));
+            return LlvmValue::new(ptr, type_);
         }
+        LlvmValue::new(reg, type_)
     }
 
-    fn codegen_bin_op(
+    fn unsupported(&self, feature: &str, node: &impl Spanned) -> LlvmError {
+        LlvmError::Unsupported(feature.to_owned(), node.to_error(&self.input.sources))
+    }
+
+    /// Call a runtime function reporting a failure at the location of `node`, which exits the
+    /// program.
+    fn codegen_failure(&mut self, func: &str, message: &str, node: &impl Spanned) {
+        let loc = node.span().start;
+        let message = self.string_ptr(message);
+        let file = self.string_ptr(self.input.sources.get(loc.source).name());
+        self.out(format!(
+            "call void @{}(i8* {}, i8* {}, i32 {}, i32 {})",
+            func, message, file, loc.line, loc.col
+        ));
+        self.out("unreachable");
+    }
+
+    /// Integer division by zero has no defined result, and neither has the division of the
+    /// smallest integer by -1, which overflows: both are reported as runtime errors. Divisions by
+    /// another constant need no check.
+    fn codegen_division_check(
         &mut self,
         op: &CheckedBinOp,
-        left: &CheckedExpr,
-        right: &CheckedExpr,
-    ) -> Result<LlvmValue, fmt::Error> {
-        let left = self.codegen_expr(left)?;
-        let right = self.codegen_expr(right)?;
-        let left_type = left.type_.clone();
+        dividend: &LlvmValue,
+        divisor: &LlvmValue,
+    ) {
+        let constant = divisor.register.parse::<i32>().ok();
+        if constant.is_some_and(|d| d != 0 && d != -1) {
+            return;
+        }
 
-        //TODO: should match on operand.type_ when it's properly supported
-        match &left_type {
-            LlvmType::Builtin(type_) => self.codegen_bin_op_builtin(type_, op.kind, left, right),
+        if constant != Some(-1) {
+            let is_zero = self.next_reg();
+            let fail_label = self.next_label();
+            let ok_label = self.next_label();
+            self.out(format!("{} = icmp eq i32 {}, 0", is_zero, divisor.register));
+            self.out(format!(
+                "br i1 {}, label %{}, label %{}",
+                is_zero, fail_label, ok_label
+            ));
+
+            self.label(&fail_label);
+            self.codegen_failure("_runtime_error", "division by zero", op);
+
+            self.label(&ok_label);
+        }
+
+        if constant != Some(0) {
+            let is_min = self.next_reg();
+            let is_minus_one = self.next_reg();
+            let overflows = self.next_reg();
+            let fail_label = self.next_label();
+            let ok_label = self.next_label();
+            self.out(format!(
+                "{} = icmp eq i32 {}, {}",
+                is_min,
+                dividend.register,
+                i32::MIN
+            ));
+            self.out(format!(
+                "{} = icmp eq i32 {}, -1",
+                is_minus_one, divisor.register
+            ));
+            self.out(format!(
+                "{} = and i1 {}, {}",
+                overflows, is_min, is_minus_one
+            ));
+            self.out(format!(
+                "br i1 {}, label %{}, label %{}",
+                overflows, fail_label, ok_label
+            ));
+
+            self.label(&fail_label);
+            self.codegen_failure("_runtime_error", "integer overflow", op);
+
+            self.label(&ok_label);
         }
     }
 
-    fn codegen_bin_op_builtin(
+    fn codegen_for(&mut self, var: &str, iter: &CheckedExpr, body: &[CheckedStmt]) -> Result<()> {
+        // the length of a tuple is known, so the loop over its elements is unrolled
+        if let Type::Seq(_) | Type::Tuple { .. } = iter.meta.type_ {
+            let iter = self.codegen_iter(iter)?;
+            return self.codegen_for_each(iter, |this, value| {
+                let ptr = this.alloca(&value.type_);
+                this.codegen_store(&value, &ptr);
+                this.codegen_loop_body(var, ptr, value.type_, body)
+            });
+        }
+
+        let range = self.codegen_expr(iter)?;
+        let start = self.next_reg();
+        let end = self.next_reg();
+        self.out(format!(
+            "{} = extractvalue {} {}, 0",
+            start, range.type_, range.register
+        ));
+        self.out(format!(
+            "{} = extractvalue {} {}, 1",
+            end, range.type_, range.register
+        ));
+
+        let type_ = LlvmType::Builtin(Type::Integer);
+        let ptr = self.alloca(&type_);
+        let mut res = Ok(());
+        self.codegen_range_loop(&start, &end, |this, index| {
+            this.out(format!("store i32 {}, i32* {}", index, ptr));
+            res = this.codegen_loop_body(var, ptr, type_, body);
+        });
+        res
+    }
+
+    /// Evaluate the first argument of a higher-order built-in or the iterable of a loop.
+    fn codegen_iter(&mut self, iter: &CheckedExpr) -> Result<LlvmIter> {
+        // the elements of a tuple literal are used as they are, rather than gathered in an array
+        if let ExprKind::Tuple(exprs) = &iter.kind {
+            let values = exprs
+                .iter()
+                .map(|expr| self.codegen_expr(expr))
+                .collect::<Result<_>>()?;
+            return Ok(LlvmIter::Values(values));
+        }
+
+        let value = self.codegen_expr(iter)?;
+        match &iter.meta.type_ {
+            Type::Range => {
+                let start = self.next_reg();
+                let end = self.next_reg();
+                self.out(format!(
+                    "{} = extractvalue {} {}, 0",
+                    start, value.type_, value.register
+                ));
+                self.out(format!(
+                    "{} = extractvalue {} {}, 1",
+                    end, value.type_, value.register
+                ));
+                Ok(LlvmIter::Range { start, end })
+            }
+            Type::Seq(elem_type) => Ok(LlvmIter::Seq {
+                value,
+                elem_type: (**elem_type).clone(),
+            }),
+            type_ => {
+                let values = tuple_types(type_)
+                    .iter()
+                    .enumerate()
+                    .map(|(index, elem_type)| self.codegen_extract(&value, index, elem_type))
+                    .collect();
+                Ok(LlvmIter::Values(values))
+            }
+        }
+    }
+
+    /// Return the number of elements of an iterable, as an `i32` register or constant.
+    fn codegen_iter_len(&mut self, iter: &LlvmIter) -> String {
+        match iter {
+            LlvmIter::Values(values) => values.len().to_string(),
+            LlvmIter::Range { start, end } => {
+                // empty ranges may have their end before their start
+                let diff = self.next_reg();
+                let is_neg = self.next_reg();
+                let len = self.next_reg();
+                self.out(format!("{} = sub i32 {}, {}", diff, end, start));
+                self.out(format!("{} = icmp slt i32 {}, 0", is_neg, diff));
+                self.out(format!(
+                    "{} = select i1 {}, i32 0, i32 {}",
+                    len, is_neg, diff
+                ));
+                len
+            }
+            LlvmIter::Seq { value, .. } => {
+                let len = self.next_reg();
+                self.out(format!(
+                    "{} = extractvalue {} {}, 0",
+                    len, value.type_, value.register
+                ));
+                len
+            }
+        }
+    }
+
+    /// Call `body` on each element of an iterable, within a loop unless the elements are known.
+    fn codegen_for_each(
         &mut self,
-        type_: &Type,
-        op: BinOpKind,
-        left: LlvmValue,
-        right: LlvmValue,
-    ) -> Result<LlvmValue, fmt::Error> {
-        let opcode = match type_ {
-            Type::Integer => match op {
-                BinOpKind::Add => "add",
-                BinOpKind::Sub => "sub",
-                BinOpKind::Mul => "mul",
-                BinOpKind::Div => "sdiv",
-            },
-            Type::Float => match op {
-                BinOpKind::Add => "fadd",
-                BinOpKind::Sub => "fsub",
-                BinOpKind::Mul => "fmul",
-                BinOpKind::Div => "fdiv",
-            },
-            Type::Tuple { .. } => todo!(),
-            Type::Stmt => unreachable!(),
+        iter: LlvmIter,
+        mut body: impl FnMut(&mut Self, LlvmValue) -> Result<()>,
+    ) -> Result<()> {
+        let (start, end) = match &iter {
+            LlvmIter::Values(_) => ("0".to_owned(), String::new()),
+            LlvmIter::Range { start, end } => (start.clone(), end.clone()),
+            LlvmIter::Seq { .. } => ("0".to_owned(), self.codegen_iter_len(&iter)),
         };
+        if let LlvmIter::Values(values) = iter {
+            return values.into_iter().try_for_each(|value| body(self, value));
+        }
 
+        let mut res = Ok(());
+        self.codegen_range_loop(&start, &end, |this, index| {
+            let value = match &iter {
+                LlvmIter::Seq { value, elem_type } => {
+                    let data = this.next_reg();
+                    this.out(format!(
+                        "{} = extractvalue {} {}, 1",
+                        data, value.type_, value.register
+                    ));
+                    this.codegen_seq_elem(&data, index, elem_type)
+                }
+                _ => LlvmValue::new(index.to_owned(), LlvmType::Builtin(Type::Integer)),
+            };
+            res = body(this, value);
+        });
+        res
+    }
+
+    /// Return the element at the given index of the data of a sequence.
+    fn codegen_seq_elem(&mut self, data: &str, index: &str, elem_type: &Type) -> LlvmValue {
+        let type_ = LlvmType::Builtin(elem_type.clone());
+        let ptr = self.next_reg();
+        self.out(format!(
+            "{0} = getelementptr inbounds {1}, {1}* {2}, i32 {3}",
+            ptr, type_, data, index
+        ));
+
+        // matrices are used in place
+        if let LlvmType::Builtin(Type::Matrix { .. }) = type_ {
+            return LlvmValue::new(ptr, type_);
+        }
         let reg = self.next_reg();
+        self.out(format!("{0} = load {1}, {1}* {2}", reg, type_, ptr));
+        LlvmValue::new(reg, type_)
+    }
 
+    /// Allocate the data of a sequence of up to `len` elements on the heap, and return a pointer to
+    /// it.
+    fn codegen_seq_alloc(&mut self, elem_type: &Type, len: &str) -> String {
+        let type_ = LlvmType::Builtin(elem_type.clone());
+        let size_ptr = self.next_reg();
+        let size = self.next_reg();
+        let raw = self.next_reg();
+        let data = self.next_reg();
         self.out(format!(
-            "{} = {} {} {}, {}",
-            reg, opcode, left.type_, left.register, right.register
+            "{0} = getelementptr {1}, {1}* null, i32 {2}",
+            size_ptr, type_, len
+        ));
+        self.out(format!(
+            "{} = ptrtoint {}* {} to i64",
+            size, type_, size_ptr
         ));
+        self.out(format!("{} = call i8* @malloc(i64 {})", raw, size));
+        self.out(format!("{} = bitcast i8* {} to {}*", data, raw, type_));
+        data
+    }
 
-        Ok(LlvmValue::new(reg, left.type_))
+    /// Append a value to the data of a sequence, whose current length is stored at `len_ptr`.
+    fn codegen_seq_push(&mut self, data: &str, len_ptr: &str, value: &LlvmValue) {
+        let len = self.next_reg();
+        let ptr = self.next_reg();
+        let next = self.next_reg();
+        self.out(format!("{} = load i32, i32* {}", len, len_ptr));
+        self.out(format!(
+            "{0} = getelementptr inbounds {1}, {1}* {2}, i32 {3}",
+            ptr, value.type_, data, len
+        ));
+        self.codegen_store(value, &ptr);
+        self.out(format!("{} = add i32 {}, 1", next, len));
+        self.out(format!("store i32 {}, i32* {}", next, len_ptr));
     }
-}
 
-#[cfg(test)]
-mod test {
-    use crate::pipeline::checker::check;
-    use crate::pipeline::llvm::llvm_codegen;
-    use crate::pipeline::parser::parse;
-    use crate::pipeline::tokenizer::tokenize;
-    use crate::states::InputState;
+    /// Generate the body of a loop, in a new scope holding the loop variable.
+    fn codegen_loop_body(
+        &mut self,
+        var: &str,
+        ptr: String,
+        type_: LlvmType,
+        body: &[CheckedStmt],
+    ) -> Result<()> {
+        self.locals
+            .push(HashMap::from([(var.to_owned(), (ptr, type_))]));
+        let res = body.iter().try_for_each(|stmt| self.codegen_stmt(stmt));
+        self.locals.pop();
+        res
+    }
 
-    #[test]
-    fn test_llvm_codegen() {
-        let input = InputState::from("a = (1.3 + 3.2) * 45.1; b = a * 3.2; print 1 + 2 * 3;");
+    fn lookup_local(&self, name: &str) -> Option<(String, LlvmType)> {
+        self.locals
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+    }
+
+    /// Find the pointer to a variable, looking up the loop scopes first.
+    fn lookup_var(&self, name: &str) -> Option<(String, LlvmType)> {
+        self.lookup_local(name).or_else(|| {
+            self.globals
+                .get(name)
+                .map(|type_| (global_symbol(name), type_.clone()))
+        })
+    }
+
+    fn codegen_store(&mut self, value: &LlvmValue, ptr: &str) {
+        let mut value = LlvmValue::new(value.register.clone(), value.type_.clone());
+        if let LlvmType::Builtin(Type::Matrix { .. }) = value.type_ {
+            value = self.codegen_load(&value);
+        }
+        self.out(format!(
+            "store {} {}, {}* {}",
+            value.type_, value.register, value.type_, ptr
+        ));
+    }
+
+    fn codegen_expr(&mut self, expr: &CheckedExpr) -> Result<LlvmValue> {
+        // symbolic expressions are only supported by the interpreter, so the values holding them
+        // are rejected before their types are lowered
+        if has_symbolic(&expr.meta.type_) {
+            return Err(self.unsupported("symbolic expressions", expr));
+        }
+        match &expr.kind {
+            ExprKind::Variable(name) => self.codegen_variable(name.as_ref()),
+            ExprKind::UnaryOp { op, operand } => self.codegen_unary_op(op, operand),
+            ExprKind::BinOp { op, left, right } => self.codegen_bin_op(op, left, right),
+            ExprKind::Call { name, args } if matches!(name.meta.type_, Type::Function { .. }) => {
+                self.codegen_closure_call(name.as_ref(), args, &expr.meta.type_)
+            }
+            ExprKind::Call { name, args } => self.codegen_call(name, args, &expr.meta.type_),
+            ExprKind::Apply { callee, args } => {
+                let closure = self.codegen_expr(callee)?;
+                let mut values = Vec::new();
+                for arg in args {
+                    values.push(self.codegen_expr(arg)?);
+                }
+                Ok(self.codegen_apply(&closure, values, &expr.meta.type_))
+            }
+            ExprKind::Lambda {
+                params,
+                body,
+                captures,
+                ..
+            } => self.codegen_lambda(params, body, captures, &expr.meta.type_),
+            ExprKind::Tuple(exprs) => {
+                let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+                let mut reg = "undef".to_owned();
+                for (index, elem) in exprs.iter().enumerate() {
+                    let value = self.codegen_expr(elem)?;
+                    reg = self.codegen_insert(&reg, &type_, value, index);
+                }
+                Ok(LlvmValue::new(reg, type_))
+            }
+            // symbolic expressions are only supported by the interpreter
+            ExprKind::Deriv { .. } | ExprKind::Eval { .. } => {
+                Err(self.unsupported("symbolic expressions", expr))
+            }
+            ExprKind::Matrix(rows) => {
+                let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+                let res = LlvmValue::new(self.alloca(&type_), type_);
+                for (i, elem) in rows.iter().flatten().enumerate() {
+                    let value = self.codegen_expr(elem)?;
+                    let elem_ptr = self.codegen_matrix_elem(&res, &i.to_string());
+                    self.out(format!(
+                        "store double {}, double* {}",
+                        value.register, elem_ptr
+                    ));
+                }
+                Ok(res)
+            }
+            ExprKind::Record { fields, .. } => {
+                let Type::Record(record) = &expr.meta.type_ else {
+                    unreachable!("type checker should have checked this")
+                };
+                let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+
+                // fields are evaluated in source order, and inserted at their declared index
+                let mut reg = "undef".to_owned();
+                for (field, value) in fields {
+                    let (index, _) = record
+                        .field(&field.kind)
+                        .expect("type checker should have checked this");
+                    let value = self.codegen_expr(value)?;
+                    reg = self.codegen_insert(&reg, &type_, value, index);
+                }
+                Ok(LlvmValue::new(reg, type_))
+            }
+            ExprKind::Field {
+                expr: record,
+                field,
+            } => {
+                let index = match &record.meta.type_ {
+                    Type::Record(record) => {
+                        record
+                            .field(&field.kind)
+                            .expect("type checker should have checked this")
+                            .0
+                    }
+                    _ => unreachable!("type checker should have checked this"),
+                };
+                let value = self.codegen_expr(record)?;
+                Ok(self.codegen_extract(&value, index, &expr.meta.type_))
+            }
+            ExprKind::Variant { variant, .. } => {
+                let Type::Enum(enum_) = &expr.meta.type_ else {
+                    unreachable!("type checker should have checked this")
+                };
+                let index = enum_
+                    .variant(&variant.kind)
+                    .expect("type checker should have checked this");
+                Ok(LlvmValue::new(
+                    index.to_string(),
+                    LlvmType::Builtin(expr.meta.type_.clone()),
+                ))
+            }
+            ExprKind::TupleIndex { expr: tuple, index } => {
+                let value = self.codegen_expr(tuple)?;
+                Ok(self.codegen_extract(&value, *index, &expr.meta.type_))
+            }
+            // integer literals may have been resolved to floats by the type checker
+            ExprKind::Integer(i) if expr.meta.type_ == Type::Float => Ok(LlvmValue::new(
+                format!("{:?}", *i as f64),
+                LlvmType::Builtin(Type::Float),
+            )),
+            ExprKind::Integer(i) => Ok(LlvmValue::new(
+                i.to_string(),
+                LlvmType::Builtin(Type::Integer),
+            )),
+            ExprKind::Float(f) => Ok(LlvmValue::new(
+                format!("{:?}", f),
+                LlvmType::Builtin(Type::Float),
+            )),
+            ExprKind::Bool(b) => Ok(LlvmValue::new(b.to_string(), LlvmType::Builtin(Type::Bool))),
+            ExprKind::Imaginary(f) => Ok(LlvmValue::new(
+                format!("{{double 0.0, double {:?}}}", f),
+                LlvmType::Builtin(Type::Complex),
+            )),
+            ExprKind::Quantity { value, unit } => {
+                let value = self.codegen_expr(value)?;
+                let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+                let scale = Unit::from_name(unit.as_ref())
+                    .expect("type checker should have checked this")
+                    .scale;
+
+                // values are stored in base units
+                if scale == 1.0 {
+                    Ok(LlvmValue::new(value.register, type_))
+                } else {
+                    let reg = self.next_reg();
+                    self.out(format!(
+                        "{} = fmul double {}, {:?}",
+                        reg, value.register, scale
+                    ));
+                    Ok(LlvmValue::new(reg, type_))
+                }
+            }
+            ExprKind::Range { start, end } => {
+                let start = self.codegen_expr(start)?;
+                let end = self.codegen_expr(end)?;
+                let type_ = LlvmType::Builtin(Type::Range);
+                let tmp = self.next_reg();
+                let reg = self.next_reg();
+                self.out(format!(
+                    "{} = insertvalue {} undef, i32 {}, 0",
+                    tmp, type_, start.register
+                ));
+                self.out(format!(
+                    "{} = insertvalue {} {}, i32 {}, 1",
+                    reg, type_, tmp, end.register
+                ));
+                Ok(LlvmValue::new(reg, type_))
+            }
+            ExprKind::Convert(operand) if matches!(expr.meta.type_, Type::Optional(_)) => {
+                let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+                let value = self.codegen_expr(operand)?;
+                let flag = LlvmValue::new("true".to_owned(), LlvmType::Builtin(Type::Bool));
+                let reg = self.codegen_insert("undef", &type_, flag, 0);
+                let reg = self.codegen_insert(&reg, &type_, value, 1);
+                Ok(LlvmValue::new(reg, type_))
+            }
+            // tuples of integers are converted element-wise
+            ExprKind::Convert(operand) if matches!(expr.meta.type_, Type::Tuple { .. }) => {
+                let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+                let value = self.codegen_expr(operand)?;
+                let mut reg = "undef".to_owned();
+                for index in 0..tuple_types(&expr.meta.type_).len() {
+                    let elem = self.codegen_extract(&value, index, &Type::Integer);
+                    let float = self.next_reg();
+                    self.out(format!(
+                        "{} = sitofp i32 {} to double",
+                        float, elem.register
+                    ));
+                    let float = LlvmValue::new(float, LlvmType::Builtin(Type::Float));
+                    reg = self.codegen_insert(&reg, &type_, float, index);
+                }
+                Ok(LlvmValue::new(reg, type_))
+            }
+            ExprKind::Convert(operand) => {
+                let value = self.codegen_expr(operand)?;
+                let reg = self.next_reg();
+                self.out(format!("{} = sitofp i32 {} to double", reg, value.register));
+                Ok(LlvmValue::new(reg, LlvmType::Builtin(Type::Float)))
+            }
+            ExprKind::Conditional {
+                cond,
+                then_branch,
+                else_branch,
+            } => self.codegen_conditional(expr, cond, then_branch, else_branch),
+            ExprKind::None => Ok(LlvmValue::new(
+                "zeroinitializer".to_owned(),
+                LlvmType::Builtin(expr.meta.type_.clone()),
+            )),
+            ExprKind::Unwrap {
+                expr: optional,
+                default,
+            } => self.codegen_unwrap(expr, optional, default),
+        }
+    }
+
+    /// Emit an unwrapped optional, only evaluating the default if there is no value. As for
+    /// conditionals, the results are merged with a `phi` node.
+    fn codegen_unwrap(
+        &mut self,
+        expr: &CheckedExpr,
+        optional: &CheckedExpr,
+        default: &CheckedExpr,
+    ) -> Result<LlvmValue> {
+        let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+        // matrices live in memory, so the results are pointers
+        let reg_type = match expr.meta.type_ {
+            Type::Matrix { .. } => format!("{}*", type_),
+            _ => type_.to_string(),
+        };
+
+        let optional = self.codegen_expr(optional)?;
+        let has_value = self.next_reg();
+        let some_label = self.next_label();
+        let none_label = self.next_label();
+        let end_label = self.next_label();
+        self.out(format!(
+            "{} = extractvalue {} {}, 0",
+            has_value, optional.type_, optional.register
+        ));
+        self.out(format!(
+            "br i1 {}, label %{}, label %{}",
+            has_value, some_label, none_label
+        ));
+
+        self.label(&some_label);
+        let value = self.codegen_extract(&optional, 1, &expr.meta.type_);
+        self.out(format!("br label %{}", end_label));
+
+        // the default may contain control flow, so it ends in the block current at that point
+        self.label(&none_label);
+        let default_value = self.codegen_expr(default)?;
+        let default_end = self.cur_label.clone();
+        self.out(format!("br label %{}", end_label));
+
+        self.label(&end_label);
+        let reg = self.next_reg();
+        self.out(format!(
+            "{} = phi {} [{}, %{}], [{}, %{}]",
+            reg, reg_type, value.register, some_label, default_value.register, default_end
+        ));
+        Ok(LlvmValue::new(reg, type_))
+    }
+
+    /// Emit code depending on whether an optional holds a value, which is passed to `some`. Both
+    /// cases then continue with the code following them.
+    fn codegen_if_some(
+        &mut self,
+        optional: &LlvmValue,
+        some: impl FnOnce(&mut Self, &LlvmValue),
+        none: impl FnOnce(&mut Self),
+    ) {
+        let LlvmType::Builtin(Type::Optional(inner)) = &optional.type_ else {
+            unreachable!("not an optional: {}", optional.type_)
+        };
+        let inner = (**inner).clone();
+        let has_value = self.next_reg();
+        let some_label = self.next_label();
+        let none_label = self.next_label();
+        let end_label = self.next_label();
+        self.out(format!(
+            "{} = extractvalue {} {}, 0",
+            has_value, optional.type_, optional.register
+        ));
+        self.out(format!(
+            "br i1 {}, label %{}, label %{}",
+            has_value, some_label, none_label
+        ));
+
+        self.label(&some_label);
+        let value = self.codegen_extract(optional, 1, &inner);
+        some(self, &value);
+        self.out(format!("br label %{}", end_label));
+
+        self.label(&none_label);
+        none(self);
+        self.out(format!("br label %{}", end_label));
+
+        self.label(&end_label);
+    }
+
+    /// Emit a conditional expression.
+    ///
+    /// Branches which are literals or variables are safe to evaluate unconditionally, in which
+    /// case a `select` is used. Otherwise, only the selected branch is evaluated, and the results
+    /// are merged with a `phi` node.
+    fn codegen_conditional(
+        &mut self,
+        expr: &CheckedExpr,
+        cond: &CheckedExpr,
+        then_branch: &CheckedExpr,
+        else_branch: &CheckedExpr,
+    ) -> Result<LlvmValue> {
+        let is_trivial = |expr: &CheckedExpr| {
+            matches!(
+                expr.kind,
+                ExprKind::Variable(_)
+                    | ExprKind::Integer(_)
+                    | ExprKind::Float(_)
+                    | ExprKind::Bool(_)
+                    | ExprKind::Imaginary(_)
+            )
+        };
+
+        let type_ = LlvmType::Builtin(expr.meta.type_.clone());
+        // matrices live in memory, so the branches are pointers
+        let reg_type = match expr.meta.type_ {
+            Type::Matrix { .. } => format!("{}*", type_),
+            _ => type_.to_string(),
+        };
+
+        let cond = self.codegen_expr(cond)?;
+        let reg = if is_trivial(then_branch) && is_trivial(else_branch) {
+            let then_value = self.codegen_expr(then_branch)?;
+            let else_value = self.codegen_expr(else_branch)?;
+            let reg = self.next_reg();
+            self.out(format!(
+                "{0} = select i1 {1}, {2} {3}, {2} {4}",
+                reg, cond.register, reg_type, then_value.register, else_value.register
+            ));
+            reg
+        } else {
+            let then_label = self.next_label();
+            let else_label = self.next_label();
+            let end_label = self.next_label();
+            self.out(format!(
+                "br i1 {}, label %{}, label %{}",
+                cond.register, then_label, else_label
+            ));
+
+            // branches may contain control flow, so they end in the block current at that point
+            self.label(&then_label);
+            let then_value = self.codegen_expr(then_branch)?;
+            let then_end = self.cur_label.clone();
+            self.out(format!("br label %{}", end_label));
+
+            self.label(&else_label);
+            let else_value = self.codegen_expr(else_branch)?;
+            let else_end = self.cur_label.clone();
+            self.out(format!("br label %{}", end_label));
+
+            self.label(&end_label);
+            let reg = self.next_reg();
+            self.out(format!(
+                "{} = phi {} [{}, %{}], [{}, %{}]",
+                reg, reg_type, then_value.register, then_end, else_value.register, else_end
+            ));
+            reg
+        };
+
+        Ok(LlvmValue::new(reg, type_))
+    }
+
+    /// Generate the function implementing a lambda, and return the closure value pairing it with
+    /// a heap-allocated copy of the captured variables.
+    fn codegen_lambda(
+        &mut self,
+        params: &[(CheckedVarName, Option<TypeName<TypeInfo>>)],
+        body: &CheckedExpr,
+        captures: &[CheckedVarName],
+        type_: &Type,
+    ) -> Result<LlvmValue> {
+        let env_type = format!(
+            "{{{}}}",
+            captures
+                .iter()
+                .map(|capture| LlvmType::Builtin(capture.meta.type_.clone()).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        // the environment is allocated on the heap, as the closure may outlive the current scope
+        let env = if captures.is_empty() {
+            "null".to_owned()
+        } else {
+            let size_ptr = self.next_reg();
+            let size = self.next_reg();
+            let env = self.next_reg();
+            let env_ptr = self.next_reg();
+            self.out(format!(
+                "{0} = getelementptr {1}, {1}* null, i32 1",
+                size_ptr, env_type
+            ));
+            self.out(format!(
+                "{} = ptrtoint {}* {} to i64",
+                size, env_type, size_ptr
+            ));
+            self.out(format!("{} = call i8* @malloc(i64 {})", env, size));
+            self.out(format!(
+                "{} = bitcast i8* {} to {}*",
+                env_ptr, env, env_type
+            ));
+            for (i, capture) in captures.iter().enumerate() {
+                let value = self.codegen_variable(capture.as_ref())?;
+                let field_ptr = self.next_reg();
+                self.out(format!(
+                    "{0} = getelementptr inbounds {1}, {1}* {2}, i32 0, i32 {3}",
+                    field_ptr, env_type, env_ptr, i
+                ));
+                self.codegen_store(&value, &field_ptr);
+            }
+            env
+        };
+
+        // generate the function with its own code and scopes, the body only sees the captured
+        // variables and the parameters
+        let function = format!("@lambda{}", self.next_id());
+        let outer_code = std::mem::take(&mut self.code);
+        let outer_allocas = std::mem::take(&mut self.allocas);
+        let outer_locals = std::mem::replace(&mut self.locals, vec![HashMap::new()]);
+        let outer_label = std::mem::take(&mut self.cur_label);
+
+        if !captures.is_empty() {
+            let env_ptr = self.next_reg();
+            self.out(format!("{} = bitcast i8* %env to {}*", env_ptr, env_type));
+            for (i, capture) in captures.iter().enumerate() {
+                let field_ptr = self.next_reg();
+                self.out(format!(
+                    "{0} = getelementptr inbounds {1}, {1}* {2}, i32 0, i32 {3}",
+                    field_ptr, env_type, env_ptr, i
+                ));
+                let capture_type = LlvmType::Builtin(capture.meta.type_.clone());
+                self.locals[0].insert(capture.kind.clone(), (field_ptr, capture_type));
+            }
+        }
+        let mut signature_params = vec!["i8* %env".to_owned()];
+        for (i, (param, _)) in params.iter().enumerate() {
+            let param_type = LlvmType::Builtin(param.meta.type_.clone());
+            let ptr = self.alloca(&param_type);
+            self.out(format!("store {0} %p{1}, {0}* {2}", param_type, i, ptr));
+            signature_params.push(format!("{} %p{}", param_type, i));
+            self.locals[0].insert(param.kind.clone(), (ptr, param_type));
+        }
+        let res = self.codegen_expr(body).map(|value| match value.type_ {
+            LlvmType::Builtin(Type::Matrix { .. }) => self.codegen_load(&value),
+            _ => value,
+        });
+        if let Ok(value) = &res {
+            self.out(format!("ret {} {}", value.type_, value.register));
+        }
+
+        let code = std::mem::replace(&mut self.code, outer_code);
+        let allocas = std::mem::replace(&mut self.allocas, outer_allocas);
+        self.locals = outer_locals;
+        self.cur_label = outer_label;
+        let ret_type = LlvmType::Builtin(body.meta.type_.clone());
+        self.functions.push((
+            format!("{} {}({})", ret_type, function, signature_params.join(", ")),
+            allocas.into_iter().chain(code).collect(),
+        ));
+        res?;
+
+        let closure_type = LlvmType::Builtin(type_.clone());
+        let fn_ptr = self.next_reg();
+        let closure = self.next_reg();
+        self.out(format!(
+            "{} = insertvalue {} undef, {} {}, 0",
+            fn_ptr,
+            closure_type,
+            fn_ptr_type(type_),
+            function
+        ));
+        self.out(format!(
+            "{} = insertvalue {} {}, i8* {}, 1",
+            closure, closure_type, fn_ptr, env
+        ));
+        Ok(LlvmValue::new(closure, closure_type))
+    }
+
+    /// Lower `map`, `filter` or `fold` to a loop calling the function argument on each element.
+    /// The results of `map` and `filter` are sequences, whose data is allocated on the heap.
+    fn codegen_higher_order(
+        &mut self,
+        builtin: Builtin,
+        args: &[CheckedExpr],
+        type_: &Type,
+    ) -> Result<LlvmValue> {
+        let iter = self.codegen_iter(&args[0])?;
+        let res_type = LlvmType::Builtin(type_.clone());
+
+        if builtin == Builtin::Fold {
+            let init = self.codegen_expr(&args[1])?;
+            let func = self.codegen_expr(&args[2])?;
+            let acc_ptr = self.alloca(&init.type_);
+            self.codegen_store(&init, &acc_ptr);
+            let acc = |this: &mut Self| match res_type {
+                LlvmType::Builtin(Type::Matrix { .. }) => {
+                    LlvmValue::new(acc_ptr.clone(), res_type.clone())
+                }
+                _ => this.codegen_load(&LlvmValue::new(acc_ptr.clone(), res_type.clone())),
+            };
+            self.codegen_for_each(iter, |this, value| {
+                let acc_value = acc(this);
+                let res = this.codegen_apply(&func, vec![acc_value, value], type_);
+                this.codegen_store(&res, &acc_ptr);
+                Ok(())
+            })?;
+            return Ok(acc(self));
+        }
+
+        let func = self.codegen_expr(&args[1])?;
+        let Type::Seq(elem_type) = type_ else {
+            // mapping a tuple gives a tuple, whose elements are inserted one by one
+            let elem_types = tuple_types(type_);
+            let mut reg = "undef".to_owned();
+            let mut index = 0;
+            self.codegen_for_each(iter, |this, value| {
+                let res = this.codegen_apply(&func, vec![value], &elem_types[index]);
+                reg = this.codegen_insert(&reg, &res_type, res, index);
+                index += 1;
+                Ok(())
+            })?;
+            return Ok(LlvmValue::new(reg, res_type));
+        };
+        let len = self.codegen_iter_len(&iter);
+        let data = self.codegen_seq_alloc(elem_type, &len);
+        let len_ptr = self.alloca(&LlvmType::Builtin(Type::Integer));
+        self.out(format!("store i32 0, i32* {}", len_ptr));
+        self.codegen_for_each(iter, |this, value| {
+            if builtin == Builtin::Map {
+                let res = this.codegen_apply(&func, vec![value], elem_type);
+                this.codegen_seq_push(&data, &len_ptr, &res);
+                return Ok(());
+            }
+
+            // only the elements satisfying the predicate are kept
+            let arg = LlvmValue::new(value.register.clone(), value.type_.clone());
+            let keep = this.codegen_apply(&func, vec![arg], &Type::Bool);
+            let keep_label = this.next_label();
+            let next_label = this.next_label();
+            this.out(format!(
+                "br i1 {}, label %{}, label %{}",
+                keep.register, keep_label, next_label
+            ));
+            this.label(&keep_label);
+            this.codegen_seq_push(&data, &len_ptr, &value);
+            this.out(format!("br label %{}", next_label));
+            this.label(&next_label);
+            Ok(())
+        })?;
+
+        let final_len = self.next_reg();
+        let tmp = self.next_reg();
+        let reg = self.next_reg();
+        self.out(format!("{} = load i32, i32* {}", final_len, len_ptr));
+        self.out(format!(
+            "{} = insertvalue {} undef, i32 {}, 0",
+            tmp, res_type, final_len
+        ));
+        self.out(format!(
+            "{} = insertvalue {} {}, {}* {}, 1",
+            reg,
+            res_type,
+            tmp,
+            LlvmType::Builtin((**elem_type).clone()),
+            data
+        ));
+        Ok(LlvmValue::new(reg, res_type))
+    }
+
+    /// Call the closure held by a variable, passing its environment as first argument.
+    fn codegen_closure_call(
+        &mut self,
+        name: &str,
+        args: &[CheckedExpr],
+        ret: &Type,
+    ) -> Result<LlvmValue> {
+        let closure = self.codegen_variable(name)?;
+        let mut values = Vec::new();
+        for arg in args {
+            values.push(self.codegen_expr(arg)?);
+        }
+        Ok(self.codegen_apply(&closure, values, ret))
+    }
+
+    /// Call a closure value with already evaluated arguments.
+    fn codegen_apply(
+        &mut self,
+        closure: &LlvmValue,
+        args: Vec<LlvmValue>,
+        ret: &Type,
+    ) -> LlvmValue {
+        let fn_ptr = self.next_reg();
+        let env = self.next_reg();
+        self.out(format!(
+            "{} = extractvalue {} {}, 0",
+            fn_ptr, closure.type_, closure.register
+        ));
+        self.out(format!(
+            "{} = extractvalue {} {}, 1",
+            env, closure.type_, closure.register
+        ));
+
+        let mut call_args = vec![format!("i8* {}", env)];
+        for mut value in args {
+            if let LlvmType::Builtin(Type::Matrix { .. }) = value.type_ {
+                value = self.codegen_load(&value);
+            }
+            call_args.push(format!("{} {}", value.type_, value.register));
+        }
+
+        let ret_type = LlvmType::Builtin(ret.clone());
+        let reg = self.next_reg();
+        self.out(format!(
+            "{} = call {} {}({})",
+            reg,
+            ret_type,
+            fn_ptr,
+            call_args.join(", ")
+        ));
+
+        // matrices live in memory
+        if let LlvmType::Builtin(Type::Matrix { .. }) = ret_type {
+            let ptr = self.alloca(&ret_type);
+            self.out(format!("store {0} {1}, {0}* {2}", ret_type, reg, ptr));
+            return LlvmValue::new(ptr, ret_type);
+        }
+        LlvmValue::new(reg, ret_type)
+    }
+
+    fn codegen_call(
+        &mut self,
+        name: &CheckedVarName,
+        args: &[CheckedExpr],
+        type_: &Type,
+    ) -> Result<LlvmValue> {
+        let builtin =
+            Builtin::from_name(&name.kind).expect("type checker should have checked this");
+        match builtin {
+            _ if builtin.is_higher_order() => {
+                return self.codegen_higher_order(builtin, args, type_)
+            }
+            Builtin::Rand => {
+                let reg = self.next_reg();
+                self.out(format!("{} = call double @_rand()", reg));
+                return Ok(LlvmValue::new(reg, LlvmType::Builtin(Type::Float)));
+            }
+            Builtin::RandInt => {
+                let lo = self.codegen_expr(&args[0])?;
+                let hi = self.codegen_expr(&args[1])?;
+                let reg = self.next_reg();
+                self.out(format!(
+                    "{} = call i32 @_rand_int(i32 {}, i32 {})",
+                    reg, lo.register, hi.register
+                ));
+                return Ok(LlvmValue::new(reg, LlvmType::Builtin(Type::Integer)));
+            }
+            _ => {}
+        }
+
+        let arg = self.codegen_expr(&args[0])?;
+
+        match (builtin, &arg.type_) {
+            (Builtin::Abs, LlvmType::Builtin(Type::Integer)) => {
+                // the absolute value of the smallest integer overflows
+                let is_min = self.next_reg();
+                let fail_label = self.next_label();
+                let ok_label = self.next_label();
+                self.out(format!(
+                    "{} = icmp eq i32 {}, {}",
+                    is_min,
+                    arg.register,
+                    i32::MIN
+                ));
+                self.out(format!(
+                    "br i1 {}, label %{}, label %{}",
+                    is_min, fail_label, ok_label
+                ));
+                self.label(&fail_label);
+                self.codegen_failure("_runtime_error", "integer overflow", name);
+                self.label(&ok_label);
+
+                let neg = self.next_reg();
+                let is_neg = self.next_reg();
+                let reg = self.next_reg();
+                self.out(format!("{} = sub i32 0, {}", neg, arg.register));
+                self.out(format!("{} = icmp slt i32 {}, 0", is_neg, arg.register));
+                self.out(format!(
+                    "{} = select i1 {}, i32 {}, i32 {}",
+                    reg, is_neg, neg, arg.register
+                ));
+                Ok(LlvmValue::new(reg, arg.type_))
+            }
+            (Builtin::Abs, LlvmType::Builtin(Type::Float | Type::Quantity(_))) => {
+                let reg = self.next_reg();
+                self.out(format!(
+                    "{} = call double @llvm.fabs.f64(double {})",
+                    reg, arg.register
+                ));
+                Ok(LlvmValue::new(reg, arg.type_))
+            }
+            (Builtin::Abs, LlvmType::Builtin(Type::Complex)) => {
+                let (re, im) = self.codegen_complex_parts(&arg);
+                let re2 = self.next_reg();
+                let im2 = self.next_reg();
+                let sum = self.next_reg();
+                let reg = self.next_reg();
+                self.out(format!("{} = fmul double {}, {}", re2, re, re));
+                self.out(format!("{} = fmul double {}, {}", im2, im, im));
+                self.out(format!("{} = fadd double {}, {}", sum, re2, im2));
+                self.out(format!(
+                    "{} = call double @llvm.sqrt.f64(double {})",
+                    reg, sum
+                ));
+                Ok(LlvmValue::new(reg, LlvmType::Builtin(Type::Float)))
+            }
+            (Builtin::Re, LlvmType::Builtin(Type::Complex)) => {
+                let (re, _) = self.codegen_complex_parts(&arg);
+                Ok(LlvmValue::new(re, LlvmType::Builtin(Type::Float)))
+            }
+            (Builtin::Im, LlvmType::Builtin(Type::Complex)) => {
+                let (_, im) = self.codegen_complex_parts(&arg);
+                Ok(LlvmValue::new(im, LlvmType::Builtin(Type::Float)))
+            }
+            (Builtin::Transpose, LlvmType::Builtin(Type::Matrix { rows, cols })) => {
+                let (rows, cols) = (*rows, *cols);
+                let type_ = LlvmType::Builtin(Type::Matrix {
+                    rows: cols,
+                    cols: rows,
+                });
+                let res = LlvmValue::new(self.alloca(&type_), type_);
+                self.codegen_loop(rows, |this, i| {
+                    this.codegen_loop(cols, |this, j| {
+                        let src_idx = this.codegen_index(i, cols, j);
+                        let dst_idx = this.codegen_index(j, rows, i);
+                        let value = this.codegen_element(&arg, &src_idx);
+                        let dst = this.codegen_matrix_elem(&res, &dst_idx);
+                        this.out(format!("store double {}, double* {}", value, dst));
+                    })
+                });
+                Ok(res)
+            }
+            (Builtin::Det, LlvmType::Builtin(Type::Matrix { rows, .. })) => {
+                let n = *rows;
+                let data = self.codegen_matrix_data(&arg);
+                let reg = self.next_reg();
+                self.out(format!(
+                    "{} = call double @_mat_det(double* {}, i32 {})",
+                    reg, data, n
+                ));
+                Ok(LlvmValue::new(reg, LlvmType::Builtin(Type::Float)))
+            }
+            (Builtin::Inverse, LlvmType::Builtin(Type::Matrix { rows, .. })) => {
+                let n = *rows;
+                let res = LlvmValue::new(self.alloca(&arg.type_), arg.type_.clone());
+                let data = self.codegen_matrix_data(&arg);
+                let res_data = self.codegen_matrix_data(&res);
+                self.out(format!(
+                    "call void @_mat_inverse(double* {}, double* {}, i32 {})",
+                    data, res_data, n
+                ));
+                Ok(res)
+            }
+            _ => unreachable!("type checker should have checked this"),
+        }
+    }
+
+    /// Load the value pointed to by a value held in memory.
+    fn codegen_load(&mut self, value: &LlvmValue) -> LlvmValue {
+        let reg = self.next_reg();
+        self.out(format!(
+            "{0} = load {1}, {1}* {2}",
+            reg, value.type_, value.register
+        ));
+        LlvmValue::new(reg, value.type_.clone())
+    }
+
+    /// Compute the flat index `row * cols + col` of a matrix element.
+    fn codegen_index(&mut self, row: &str, cols: usize, col: &str) -> String {
+        let offset = self.next_reg();
+        let index = self.next_reg();
+        self.out(format!("{} = mul i32 {}, {}", offset, row, cols));
+        self.out(format!("{} = add i32 {}, {}", index, offset, col));
+        index
+    }
+
+    /// Return a `double*` pointer to the element of a matrix at the given flat index.
+    fn codegen_matrix_elem(&mut self, matrix: &LlvmValue, index: &str) -> String {
+        let reg = self.next_reg();
+        self.out(format!(
+            "{0} = getelementptr inbounds {1}, {1}* {2}, i32 0, i32 {3}",
+            reg, matrix.type_, matrix.register, index
+        ));
+        reg
+    }
+
+    /// Return a `double*` pointer to the data of a matrix, for use with runtime functions.
+    fn codegen_matrix_data(&mut self, matrix: &LlvmValue) -> String {
+        self.codegen_matrix_elem(matrix, "0")
+    }
+
+    /// Extract the real and imaginary parts of a complex value.
+    fn codegen_complex_parts(&mut self, value: &LlvmValue) -> (String, String) {
+        let re = self.next_reg();
+        let im = self.next_reg();
+        self.out(format!(
+            "{} = extractvalue {} {}, 0",
+            re, value.type_, value.register
+        ));
+        self.out(format!(
+            "{} = extractvalue {} {}, 1",
+            im, value.type_, value.register
+        ));
+        (re, im)
+    }
+
+    /// Build a complex value from its real and imaginary parts.
+    fn codegen_complex_from_parts(&mut self, re: &str, im: &str) -> LlvmValue {
+        let type_ = LlvmType::Builtin(Type::Complex);
+        let tmp = self.next_reg();
+        let reg = self.next_reg();
+        self.out(format!(
+            "{} = insertvalue {} undef, double {}, 0",
+            tmp, type_, re
+        ));
+        self.out(format!(
+            "{} = insertvalue {} {}, double {}, 1",
+            reg, type_, tmp, im
+        ));
+        LlvmValue::new(reg, type_)
+    }
+
+    /// Promote a scalar value to a complex number.
+    fn codegen_to_complex(&mut self, value: LlvmValue) -> LlvmValue {
+        match value.type_ {
+            LlvmType::Builtin(Type::Integer) => {
+                let re = self.next_reg();
+                self.out(format!("{} = sitofp i32 {} to double", re, value.register));
+                self.codegen_complex_from_parts(&re, "0.0")
+            }
+            LlvmType::Builtin(Type::Float) => {
+                self.codegen_complex_from_parts(&value.register, "0.0")
+            }
+            LlvmType::Builtin(Type::Complex) => value,
+            LlvmType::Builtin(_) => unreachable!("only scalars can be promoted to complex"),
+        }
+    }
+
+    fn codegen_variable(&mut self, name: &str) -> Result<LlvmValue> {
+        let (ptr, type_) = self
+            .lookup_var(name)
+            .expect("type checker should have checked this");
+
+        // matrices are used in place
+        if let LlvmType::Builtin(Type::Matrix { .. }) = type_ {
+            return Ok(LlvmValue::new(ptr, type_));
+        }
+
+        let reg = self.next_reg();
+        self.out(format!("{0} = load {1}, {1}* {2}", reg, type_, ptr));
+        Ok(LlvmValue::new(reg, type_))
+    }
+
+    fn codegen_unary_op(
+        &mut self,
+        op: &CheckedUnaryOp,
+        operand: &CheckedExpr,
+    ) -> Result<LlvmValue> {
+        let operand = self.codegen_expr(operand)?;
+        let operand_type = operand.type_.clone();
+
+        if let (LlvmType::Builtin(Type::Matrix { .. }), UnaryOpKind::Neg) = (&operand_type, op.kind)
+        {
+            let res = LlvmValue::new(self.alloca(&operand_type), operand_type);
+            return Ok(self.codegen_element_wise(&res, |this, i| {
+                let value = this.codegen_element(&operand, i);
+                let reg = this.next_reg();
+                this.out(format!("{} = fsub double 0.0, {}", reg, value));
+                reg
+            }));
+        }
+
+        //TODO: should match on operand.type_ when it's properly supported
+        match &operand_type {
+            LlvmType::Builtin(type_) => self.codegen_unary_op_builtin(type_, op.kind, operand),
+        }
+    }
+
+    fn codegen_unary_op_builtin(
+        &mut self,
+        type_: &Type,
+        op: UnaryOpKind,
+        value: LlvmValue,
+    ) -> Result<LlvmValue> {
+        match op {
+            UnaryOpKind::Neg if *type_ == Type::Complex => {
+                let (re, im) = self.codegen_complex_parts(&value);
+                let neg_re = self.next_reg();
+                let neg_im = self.next_reg();
+                self.out(format!("{} = fsub double 0.0, {}", neg_re, re));
+                self.out(format!("{} = fsub double 0.0, {}", neg_im, im));
+                Ok(self.codegen_complex_from_parts(&neg_re, &neg_im))
+            }
+            UnaryOpKind::Neg => {
+                let reg = self.next_reg();
+
+                let (opcode, cst) = match type_ {
+                    Type::Integer => ("sub", "0"),
+                    Type::Float | Type::Quantity(_) => ("fsub", "0.0"),
+                    Type::Complex | Type::Matrix { .. } | Type::Tuple { .. } => unreachable!(),
+                    Type::Stmt
+                    | Type::Bool
+                    | Type::Range
+                    | Type::Seq(_)
+                    | Type::Product(_)
+                    | Type::Record(_)
+                    | Type::Enum(_)
+                    | Type::Function { .. }
+                    | Type::Optional(_)
+                    | Type::Symbolic => {
+                        unreachable!()
+                    }
+                };
+
+                self.out(format!(
+                    "{} = {} {} {}, {}",
+                    reg, opcode, value.type_, cst, value.register
+                ));
+
+                Ok(LlvmValue::new(reg, value.type_))
+            }
+            UnaryOpKind::Pos => Ok(value),
+            UnaryOpKind::BitNot => {
+                let reg = self.next_reg();
+                self.out(format!("{} = xor i32 {}, -1", reg, value.register));
+                Ok(LlvmValue::new(reg, value.type_))
+            }
+        }
+    }
+
+    fn codegen_bin_op(
+        &mut self,
+        op: &CheckedBinOp,
+        left: &CheckedExpr,
+        right: &CheckedExpr,
+    ) -> Result<LlvmValue> {
+        let left = self.codegen_expr(left)?;
+        let right = self.codegen_expr(right)?;
+        self.codegen_bin_op_values(op, left, right)
+    }
+
+    fn codegen_bin_op_values(
+        &mut self,
+        op: &CheckedBinOp,
+        left: LlvmValue,
+        right: LlvmValue,
+    ) -> Result<LlvmValue> {
+        if op.meta.type_ == Type::Complex {
+            let left = self.codegen_to_complex(left);
+            let right = self.codegen_to_complex(right);
+            return Ok(self.codegen_bin_op_complex(op.kind, left, right));
+        }
+
+        if let Type::Matrix { .. } = op.meta.type_ {
+            let type_ = LlvmType::Builtin(op.meta.type_.clone());
+            let res = LlvmValue::new(self.alloca(&type_), type_);
+            return Ok(self.codegen_bin_op_matrix(op.kind, left, right, res));
+        }
+
+        if op.kind.is_comparison() {
+            return Ok(self.codegen_comparison(op.kind, left, right));
+        }
+
+        if let Type::Tuple { type_, .. } = &op.meta.type_ {
+            let elem_op = CheckedBinOp::new(
+                op.kind,
+                TypeInfo::new((**type_).clone(), op.meta.tok_span.clone()),
+            );
+            return self.codegen_bin_op_tuple(&elem_op, &op.meta.type_, left, right);
+        }
+
+        if op.kind == BinOpKind::Div && left.type_ == LlvmType::Builtin(Type::Integer) {
+            self.codegen_division_check(op, &left, &right);
+        }
+
+        let left_type = left.type_.clone();
+
+        //TODO: should match on operand.type_ when it's properly supported
+        match &left_type {
+            LlvmType::Builtin(type_) => {
+                let mut value = self.codegen_bin_op_builtin(type_, op.kind, left, right)?;
+
+                // the result may have a different dimension than the operands
+                value.type_ = LlvmType::Builtin(op.meta.type_.clone());
+                Ok(value)
+            }
+        }
+    }
+
+    /// Combine two tuples element-wise, or each element of a tuple with a scalar, applying `op` to
+    /// each pair of elements.
+    fn codegen_bin_op_tuple(
+        &mut self,
+        op: &CheckedBinOp,
+        type_: &Type,
+        left: LlvmValue,
+        right: LlvmValue,
+    ) -> Result<LlvmValue> {
+        let res_type = LlvmType::Builtin(type_.clone());
+        let mut reg = "undef".to_owned();
+        for index in 0..tuple_types(type_).len() {
+            let mut operands = Vec::new();
+            for value in [&left, &right] {
+                operands.push(match &value.type_ {
+                    LlvmType::Builtin(Type::Tuple { type_, .. }) => {
+                        self.codegen_extract(value, index, type_)
+                    }
+                    _ => LlvmValue::new(value.register.clone(), value.type_.clone()),
+                });
+            }
+            let right = operands.pop().unwrap();
+            let left = operands.pop().unwrap();
+            let value = self.codegen_bin_op_values(op, left, right)?;
+            reg = self.codegen_insert(&reg, &res_type, value, index);
+        }
+        Ok(LlvmValue::new(reg, res_type))
+    }
+
+    fn codegen_bin_op_builtin(
+        &mut self,
+        type_: &Type,
+        op: BinOpKind,
+        left: LlvmValue,
+        right: LlvmValue,
+    ) -> Result<LlvmValue> {
+        let opcode = match type_ {
+            Type::Integer => match op {
+                BinOpKind::Add => "add",
+                BinOpKind::Sub => "sub",
+                BinOpKind::Mul => "mul",
+                BinOpKind::Div => "sdiv",
+                BinOpKind::BitAnd => "and",
+                BinOpKind::BitOr => "or",
+                BinOpKind::BitXor => "xor",
+                BinOpKind::Shl | BinOpKind::Shr => return Ok(self.codegen_shift(op, left, right)),
+                _ => unreachable!(),
+            },
+            Type::Float | Type::Quantity(_) => match op {
+                BinOpKind::Add => "fadd",
+                BinOpKind::Sub => "fsub",
+                BinOpKind::Mul => "fmul",
+                BinOpKind::Div => "fdiv",
+                _ => unreachable!(),
+            },
+            Type::Complex => unreachable!("handled by codegen_bin_op_complex"),
+            Type::Matrix { .. } => unreachable!("handled by codegen_bin_op_matrix"),
+            Type::Tuple { .. } => unreachable!("handled by codegen_bin_op_tuple"),
+            Type::Stmt
+            | Type::Bool
+            | Type::Range
+            | Type::Seq(_)
+            | Type::Product(_)
+            | Type::Record(_)
+            | Type::Enum(_)
+            | Type::Function { .. }
+            | Type::Optional(_)
+            | Type::Symbolic => {
+                unreachable!()
+            }
+        };
+
+        let reg = self.next_reg();
+
+        self.out(format!(
+            "{} = {} {} {}, {}",
+            reg, opcode, left.type_, left.register, right.register
+        ));
+
+        Ok(LlvmValue::new(reg, left.type_))
+    }
+
+    /// Emit a comparison of two values of the same type, producing an `i1`.
+    fn codegen_comparison(
+        &mut self,
+        op: BinOpKind,
+        left: LlvmValue,
+        right: LlvmValue,
+    ) -> LlvmValue {
+        let res_type = LlvmType::Builtin(Type::Bool);
+
+        // complex numbers are equal if both of their parts are
+        let complex = LlvmType::Builtin(Type::Complex);
+        if left.type_ == complex || right.type_ == complex {
+            let left = self.codegen_to_complex(left);
+            let right = self.codegen_to_complex(right);
+            let (a, b) = self.codegen_complex_parts(&left);
+            let (c, d) = self.codegen_complex_parts(&right);
+            let (cond, combine) = match op {
+                BinOpKind::Eq => ("oeq", "and"),
+                BinOpKind::Ne => ("une", "or"),
+                _ => unreachable!("type checker should have checked this"),
+            };
+            let re = self.next_reg();
+            let im = self.next_reg();
+            let reg = self.next_reg();
+            self.out(format!("{} = fcmp {} double {}, {}", re, cond, a, c));
+            self.out(format!("{} = fcmp {} double {}, {}", im, cond, b, d));
+            self.out(format!("{} = {} i1 {}, {}", reg, combine, re, im));
+            return LlvmValue::new(reg, res_type);
+        }
+
+        // tuples are equal if all of their elements are
+        if let LlvmType::Builtin(type_ @ (Type::Tuple { .. } | Type::Product(_))) = &left.type_ {
+            let mut reg = "true".to_owned();
+            for (i, type_) in tuple_types(type_).iter().enumerate() {
+                let l = self.codegen_extract(&left, i, type_);
+                let r = self.codegen_extract(&right, i, type_);
+                let eq = self.codegen_comparison(BinOpKind::Eq, l, r);
+                let next = self.next_reg();
+                self.out(format!("{} = and i1 {}, {}", next, reg, eq.register));
+                reg = next;
+            }
+            if op == BinOpKind::Ne {
+                let next = self.next_reg();
+                self.out(format!("{} = xor i1 {}, true", next, reg));
+                reg = next;
+            }
+            return LlvmValue::new(reg, res_type);
+        }
+
+        // optionals are equal if both are none, or both hold equal values; the values of none are
+        // zeroed, so they can be compared regardless
+        if let LlvmType::Builtin(Type::Optional(inner)) = &left.type_ {
+            let inner = (**inner).clone();
+            let l_flag = self.next_reg();
+            let r_flag = self.next_reg();
+            self.out(format!(
+                "{} = extractvalue {} {}, 0",
+                l_flag, left.type_, left.register
+            ));
+            self.out(format!(
+                "{} = extractvalue {} {}, 0",
+                r_flag, right.type_, right.register
+            ));
+            let flags_eq = self.next_reg();
+            self.out(format!("{} = icmp eq i1 {}, {}", flags_eq, l_flag, r_flag));
+            // without equality on the values, one side is the `none` literal and the flags decide
+            let mut reg = flags_eq;
+            if inner.supports_equality() {
+                let l = self.codegen_extract(&left, 1, &inner);
+                let r = self.codegen_extract(&right, 1, &inner);
+                let values_eq = self.codegen_comparison(BinOpKind::Eq, l, r);
+                let no_value = self.next_reg();
+                let either = self.next_reg();
+                let both = self.next_reg();
+                self.out(format!("{} = xor i1 {}, true", no_value, l_flag));
+                self.out(format!(
+                    "{} = or i1 {}, {}",
+                    either, no_value, values_eq.register
+                ));
+                self.out(format!("{} = and i1 {}, {}", both, reg, either));
+                reg = both;
+            }
+            if op == BinOpKind::Ne {
+                let next = self.next_reg();
+                self.out(format!("{} = xor i1 {}, true", next, reg));
+                reg = next;
+            }
+            return LlvmValue::new(reg, res_type);
+        }
+
+        let (instr, cond) = match &left.type_ {
+            LlvmType::Builtin(Type::Integer | Type::Bool | Type::Enum(_)) => (
+                "icmp",
+                match op {
+                    BinOpKind::Eq => "eq",
+                    BinOpKind::Ne => "ne",
+                    BinOpKind::Lt => "slt",
+                    BinOpKind::Le => "sle",
+                    BinOpKind::Gt => "sgt",
+                    BinOpKind::Ge => "sge",
+                    _ => unreachable!(),
+                },
+            ),
+            LlvmType::Builtin(Type::Float | Type::Quantity(_)) => (
+                "fcmp",
+                match op {
+                    BinOpKind::Eq => "oeq",
+                    BinOpKind::Ne => "une",
+                    BinOpKind::Lt => "olt",
+                    BinOpKind::Le => "ole",
+                    BinOpKind::Gt => "ogt",
+                    BinOpKind::Ge => "oge",
+                    _ => unreachable!(),
+                },
+            ),
+            _ => unreachable!("type checker should have checked this"),
+        };
+
+        let reg = self.next_reg();
+        self.out(format!(
+            "{} = {} {} {} {}, {}",
+            reg, instr, cond, left.type_, left.register, right.register
+        ));
+        LlvmValue::new(reg, res_type)
+    }
+
+    /// Emit an integer shift. LLVM's `shl`/`ashr` are poison for counts outside `0..=31`, so these
+    /// are handled explicitly to match the interpreter: `<<` yields 0 and `>>` the sign fill.
+    fn codegen_shift(&mut self, op: BinOpKind, left: LlvmValue, right: LlvmValue) -> LlvmValue {
+        let oversized = self.next_reg();
+        self.out(format!(
+            "{} = icmp ugt i32 {}, 31",
+            oversized, right.register
+        ));
+
+        let reg = if op == BinOpKind::Shl {
+            let shifted = self.next_reg();
+            self.out(format!(
+                "{} = shl i32 {}, {}",
+                shifted, left.register, right.register
+            ));
+            let reg = self.next_reg();
+            self.out(format!(
+                "{} = select i1 {}, i32 0, i32 {}",
+                reg, oversized, shifted
+            ));
+            reg
+        } else {
+            let count = self.next_reg();
+            self.out(format!(
+                "{} = select i1 {}, i32 31, i32 {}",
+                count, oversized, right.register
+            ));
+            let reg = self.next_reg();
+            self.out(format!("{} = ashr i32 {}, {}", reg, left.register, count));
+            reg
+        };
+
+        LlvmValue::new(reg, left.type_)
+    }
+
+    fn codegen_bin_op_complex(
+        &mut self,
+        op: BinOpKind,
+        left: LlvmValue,
+        right: LlvmValue,
+    ) -> LlvmValue {
+        let (a, b) = self.codegen_complex_parts(&left);
+        let (c, d) = self.codegen_complex_parts(&right);
+
+        // emit `dst = opcode double x, y` and return dst
+        let mut emit = |opcode: &str, x: &str, y: &str| {
+            let reg = self.next_reg();
+            self.out(format!("{} = {} double {}, {}", reg, opcode, x, y));
+            reg
+        };
+
+        let (re, im) = match op {
+            BinOpKind::Add => (emit("fadd", &a, &c), emit("fadd", &b, &d)),
+            BinOpKind::Sub => (emit("fsub", &a, &c), emit("fsub", &b, &d)),
+            BinOpKind::Mul => {
+                // (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+                let ac = emit("fmul", &a, &c);
+                let bd = emit("fmul", &b, &d);
+                let ad = emit("fmul", &a, &d);
+                let bc = emit("fmul", &b, &c);
+                (emit("fsub", &ac, &bd), emit("fadd", &ad, &bc))
+            }
+            BinOpKind::Div => {
+                // (a + bi)/(c + di) = ((ac + bd) + (bc - ad)i) / (c² + d²)
+                let cc = emit("fmul", &c, &c);
+                let dd = emit("fmul", &d, &d);
+                let den = emit("fadd", &cc, &dd);
+                let ac = emit("fmul", &a, &c);
+                let bd = emit("fmul", &b, &d);
+                let bc = emit("fmul", &b, &c);
+                let ad = emit("fmul", &a, &d);
+                let re_num = emit("fadd", &ac, &bd);
+                let im_num = emit("fsub", &bc, &ad);
+                (emit("fdiv", &re_num, &den), emit("fdiv", &im_num, &den))
+            }
+            _ => unreachable!(),
+        };
+
+        self.codegen_complex_from_parts(&re, &im)
+    }
+
+    fn codegen_bin_op_matrix(
+        &mut self,
+        op: BinOpKind,
+        left: LlvmValue,
+        right: LlvmValue,
+        res: LlvmValue,
+    ) -> LlvmValue {
+        if op == BinOpKind::MatMul {
+            let (rows, inner, cols) = match (&left.type_, &right.type_) {
+                (
+                    LlvmType::Builtin(Type::Matrix { rows, cols: inner }),
+                    LlvmType::Builtin(Type::Matrix { cols, .. }),
+                ) => (*rows, *inner, *cols),
+                _ => unreachable!("type checker should have checked this"),
+            };
+
+            let acc = self.alloca(&LlvmType::Builtin(Type::Float));
+            self.codegen_loop(rows, |this, i| {
+                this.codegen_loop(cols, |this, j| {
+                    this.out(format!("store double 0.0, double* {}", acc));
+                    this.codegen_loop(inner, |this, k| {
+                        let left_idx = this.codegen_index(i, inner, k);
+                        let right_idx = this.codegen_index(k, cols, j);
+                        let a = this.codegen_element(&left, &left_idx);
+                        let b = this.codegen_element(&right, &right_idx);
+                        let prod = this.next_reg();
+                        let prev = this.next_reg();
+                        let sum = this.next_reg();
+                        this.out(format!("{} = fmul double {}, {}", prod, a, b));
+                        this.out(format!("{} = load double, double* {}", prev, acc));
+                        this.out(format!("{} = fadd double {}, {}", sum, prev, prod));
+                        this.out(format!("store double {}, double* {}", sum, acc));
+                    });
+                    let idx = this.codegen_index(i, cols, j);
+                    let dst = this.codegen_matrix_elem(&res, &idx);
+                    let sum = this.next_reg();
+                    this.out(format!("{} = load double, double* {}", sum, acc));
+                    this.out(format!("store double {}, double* {}", sum, dst));
+                });
+            });
+
+            return res;
+        }
+
+        let opcode = match op {
+            BinOpKind::Add => "fadd",
+            BinOpKind::Sub => "fsub",
+            BinOpKind::Mul => "fmul",
+            BinOpKind::Div => "fdiv",
+            _ => unreachable!(),
+        };
+
+        self.codegen_element_wise(&res, |this, i| {
+            let a = this.codegen_element(&left, i);
+            let b = this.codegen_element(&right, i);
+            let reg = this.next_reg();
+            this.out(format!("{} = {} double {}, {}", reg, opcode, a, b));
+            reg
+        })
+    }
+
+    /// Fill the matrix `res` by computing each of its elements with `elem`.
+    fn codegen_element_wise(
+        &mut self,
+        res: &LlvmValue,
+        elem: impl FnOnce(&mut Self, &str) -> String,
+    ) -> LlvmValue {
+        let len = match &res.type_ {
+            LlvmType::Builtin(Type::Matrix { rows, cols }) => rows * cols,
+            _ => unreachable!("element-wise operations apply to matrices"),
+        };
+
+        self.codegen_loop(len, |this, i| {
+            let value = elem(this, i);
+            let dst = this.codegen_matrix_elem(res, i);
+            this.out(format!("store double {}, double* {}", value, dst));
+        });
+
+        LlvmValue::new(res.register.clone(), res.type_.clone())
+    }
+
+    /// Load the element at the given flat index if `value` is a matrix, or use the value as is if
+    /// it is a scalar.
+    fn codegen_element(&mut self, value: &LlvmValue, index: &str) -> String {
+        match value.type_ {
+            LlvmType::Builtin(Type::Matrix { .. }) => {
+                let ptr = self.codegen_matrix_elem(value, index);
+                let reg = self.next_reg();
+                self.out(format!("{} = load double, double* {}", reg, ptr));
+                reg
+            }
+            _ => value.register.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pipeline::checker::check;
+    use crate::pipeline::expander::expand;
+    use crate::pipeline::llvm::llvm_codegen;
+    use crate::pipeline::parser::parse;
+    use crate::pipeline::tokenizer::tokenize;
+    use crate::states::{InputState, Load};
+
+    #[test]
+    fn test_llvm_codegen() {
+        let input = InputState::from("a = (1.3 + 3.2) * 45.1; b = a * 3.2; print 1 + 2 * 3;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_units() {
+        let input = InputState::from("d = 3.0 km; t = 2.0 min; print d / t;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_complex() {
+        let input = InputState::from("z = 3 + 4i; print abs(z); print z / 2.0i;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_matrix() {
+        let input = InputState::from(
+            "a = [1.0, 2.0; 3.0, 4.0]; v = [1.0; 1.0]; print a @ v * 2.0; print det(a);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_rand() {
+        let input = InputState::from("seed(42); rand(); print rand_int(1, 6) + 1;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_untyped_literals() {
+        let input = InputState::from("x = 2.5; print x * 2; print 1 + 2;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_for() {
+        let input = InputState::from(
+            "s = 0; for i in 0..10 { t = i * 2; s = s + t; } for x in (1.5, 2.5) { print x; } print s;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_bitwise() {
+        let input =
+            InputState::from("x = 12; print (x & 10) | ~x ^ 1; print x << 2; print x >> 1;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_conditional() {
+        let input = InputState::from(
+            "n = 3; print if n > 1 then n else 1; print if n == 0 then 0 else if n < 0 then -1 else 100 / n; print 1.5 >= 2.0;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_assert() {
+        let input = InputState::from("x = 3;\nassert x > 0, \"x must be positive\";\nprint x;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_exit() {
+        let input = InputState::from("n = 4; print 12 / n; print n / 2; exit(n - 1); print n;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_division() {
+        // the smallest integer divided by -1 overflows
+        let input = InputState::from("x = -2147483647 - 1; y = 0; print x / -1; print x / y;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_abs() {
+        // the absolute value of the smallest integer overflows
+        let input = InputState::from("x = -3; print abs(x); print abs(2.5);");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_record() {
+        let input = InputState::from(
+            "record Point { x float, y float } record Tagged { p Point, tag int } p = Point { y: 2.0, x: 1.0 }; t = Tagged { p: p, tag: 1 }; print t; print t.p.y;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_product() {
+        let input = InputState::from("t = (1, (2.5, true)); print t; print t.1.0; print t == t;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_errors() {
+        let codegen_err = |input: &str| {
+            let tokenized = tokenize(InputState::from(input)).unwrap();
+            let parsed = parse(tokenized).unwrap();
+            let checked = check(expand(parsed).unwrap()).unwrap();
+            llvm_codegen(&checked, &mut String::new())
+                .err()
+                .unwrap()
+                .to_string()
+        };
+
+        insta::assert_snapshot!("symbolic", codegen_err("d = deriv(x * x, x); print d;"));
+        insta::assert_snapshot!(
+            "eval",
+            codegen_err("x = 1.0; print eval(deriv(x * x, x), x = 2.0);")
+        );
+        insta::assert_snapshot!(
+            "plot",
+            codegen_err("plot y = x * x for x in 0..10, \"square.svg\";")
+        );
+    }
+
+    #[test]
+    fn test_llvm_codegen_prelude() {
+        // every item of the prelude can be lowered
+        let input = InputState::from_code(
+            "print (PI, E, TAU); print X_AXIS + Y_AXIS + Z_AXIS;".to_owned(),
+            Load::Prelude,
+        )
+        .unwrap();
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_tuple() {
+        let input = InputState::from(
+            "t = (1, 2); print t.0; print t + (3, 4); print t / 2; print (1.5, 2.5) * 2.0; print t == (1, 2); for x in t { print x; } print map(t, x => x * 3);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_match() {
+        let input = InputState::from(
+            "enum Mode { Fast, Slow } m = Mode::Slow; match m { Mode::Slow => { x = 1; print x; } Mode::Fast => {} } print m; print m == Mode::Fast;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_lambda() {
+        let input = InputState::from(
+            "k = 3.0; one = fn() int => 1; scale = fn(x float) float => x * k; print scale(2.0); print one(); print one;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_higher_order() {
+        let input = InputState::from(
+            "s = map(0..3, x => x * 2); print filter(s, x => x > 1); print fold((1, 2), 0, (a, b) => a + b);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_optional() {
+        let input =
+            InputState::from("x: int? = none; print x; x = 2; print x ?? 1; print x == none;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_module() {
+        let input =
+            InputState::from_file("testdata/modules/main.calc".into(), Load::Imports).unwrap();
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+
+        let mut output = String::new();
+        llvm_codegen(&checked, &mut output).unwrap();
+
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_llvm_codegen_let() {
+        let input = InputState::from(
+            "t = (1, (2.5, true)); let (a, (_, c)) = t; print a; print c; let (x, _, z) = (1.5, 2.5, 3.5); print x * z;",
+        );
         let tokenized = tokenize(input).unwrap();
         let parsed = parse(tokenized).unwrap();
-        let checked = check(parsed).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
 
         let mut output = String::new();
         llvm_codegen(&checked, &mut output).unwrap();
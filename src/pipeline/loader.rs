@@ -0,0 +1,229 @@
+//! The loader stage.
+//!
+//! Reads the files imported by a program, recursively, such that they can be merged into a single
+//! program. Imports are resolved relative to the importing file, and each file is only loaded
+//! once, however many times it is imported. Modules, i.e. `use geo;`, are read from `geo.calc`
+//! next to the main file, and are loaded once as well.
+
+use crate::context::source::{Source, Sources, PRELUDE};
+use crate::context::token_stream::TokenStream;
+use crate::data::token::{Token, TokenKind};
+use crate::errors::{ImportError, LoaderError, Spanned};
+use crate::pipeline::tokenizer::tokenize_source;
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, LoaderError>;
+
+/// Load the files imported by the main source, and order all of them in dependency order. The
+/// prelude, if included, comes first.
+pub(crate) fn load(main: Source, prelude: bool) -> Result<Sources> {
+    let path = std::fs::canonicalize(main.name()).ok();
+    let mut loader = Loader {
+        sources: Sources::new(main),
+        paths: vec![path],
+        stack: Vec::new(),
+        order: Vec::new(),
+    };
+    if prelude {
+        let prelude = loader.sources.add(Source::prelude());
+        loader.paths.push(None);
+        loader.load_source(prelude)?;
+    }
+    loader.load_source(0)?;
+
+    let Loader {
+        mut sources, order, ..
+    } = loader;
+    sources.set_order(order);
+    Ok(sources)
+}
+
+struct Loader {
+    sources: Sources,
+
+    /// canonical path of each source, which is unknown for the main one if not read from a file
+    paths: Vec<Option<PathBuf>>,
+
+    /// sources being loaded, i.e. importing the current one, innermost last
+    stack: Vec<usize>,
+
+    /// loaded sources, in dependency order
+    order: Vec<usize>,
+}
+
+impl Loader {
+    /// Load the files imported by a source, before the source itself.
+    fn load_source(&mut self, source: usize) -> Result<()> {
+        self.stack.push(source);
+
+        let mut token_stream = TokenStream::default();
+        tokenize_source(&self.sources, source, &mut token_stream)?;
+        for (token, dependency) in dependencies(&token_stream) {
+            let (path, module) = match dependency {
+                Dependency::Import(name) => (self.resolve(source, name), None),
+                Dependency::Module(module) if module == PRELUDE => {
+                    return Err(
+                        self.import_err(ImportError::ReservedModule(module.to_owned()), token)
+                    );
+                }
+                Dependency::Module(module) => {
+                    (self.resolve(0, &format!("{}.calc", module)), Some(module))
+                }
+            };
+            let canonical = std::fs::canonicalize(&path)
+                .map_err(|err| self.import_err(unreadable(&path, err), token))?;
+
+            // a file may be both imported and used as a module, which are distinct sources
+            match (0..self.paths.len()).find(|&s| {
+                self.paths[s].as_ref() == Some(&canonical) && self.sources.get(s).module() == module
+            }) {
+                // the import cycle is shown from the first file on it
+                Some(imported) if self.stack.contains(&imported) => {
+                    let start = self.stack.iter().position(|&s| s == imported).unwrap();
+                    let cycle: Vec<_> = self.stack[start..]
+                        .iter()
+                        .chain([&imported])
+                        .map(|&s| self.sources.get(s).name())
+                        .collect();
+                    return Err(
+                        self.import_err(ImportError::ImportCycle(cycle.join(" -> ")), token)
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    let text = std::fs::read_to_string(&path)
+                        .map_err(|err| self.import_err(unreadable(&path, err), token))?;
+                    let mut imported = Source::new(text).with_name(path.display().to_string());
+                    if let Some(module) = module {
+                        imported = imported.with_module(module);
+                    }
+                    let imported = self.sources.add(imported);
+                    self.paths.push(Some(canonical));
+                    self.load_source(imported)?;
+                }
+            }
+        }
+
+        self.stack.pop();
+        self.order.push(source);
+        Ok(())
+    }
+
+    /// Resolve an imported path relative to the directory of the importing source. Sources not
+    /// read from a file are relative to the working directory.
+    fn resolve(&self, source: usize, name: &str) -> PathBuf {
+        let importing = Path::new(self.sources.get(source).name());
+        match importing.parent() {
+            Some(dir) if self.paths[source].is_some() => dir.join(name),
+            _ => PathBuf::from(name),
+        }
+    }
+
+    fn import_err(&self, err: ImportError, token: &Token) -> LoaderError {
+        LoaderError::ImportError(err, token.to_error(&self.sources))
+    }
+}
+
+/// A file loaded by a source, which is either merged into the program or namespaced as a module.
+enum Dependency<'a> {
+    /// imported path, e.g. `import "consts.calc";`
+    Import(&'a str),
+    /// used module, e.g. `use geo;`
+    Module(&'a str),
+}
+
+/// Files imported or used by a source, along with their tokens. Imports and uses are only allowed
+/// at the top level, which the parser checks.
+fn dependencies(token_stream: &TokenStream) -> impl Iterator<Item = (&Token, Dependency<'_>)> {
+    token_stream
+        .tokens()
+        .windows(2)
+        .filter_map(|tokens| match (&tokens[0].kind, &tokens[1].kind) {
+            (TokenKind::Import, TokenKind::Str(name)) => {
+                Some((tokens[1].as_ref(), Dependency::Import(name.as_str())))
+            }
+            (TokenKind::Use, TokenKind::Name(name)) => {
+                Some((tokens[1].as_ref(), Dependency::Module(name.as_str())))
+            }
+            _ => None,
+        })
+}
+
+fn unreadable(path: &Path, err: std::io::Error) -> ImportError {
+    ImportError::UnreadableFile(path.display().to_string(), err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::Spanned;
+    use crate::pipeline::checker::check;
+    use crate::pipeline::expander::expand;
+    use crate::pipeline::interpreter::interpret;
+    use crate::pipeline::parser::parse;
+    use crate::pipeline::tokenizer::tokenize;
+    use crate::states::{InputState, Load};
+    use std::path::PathBuf;
+
+    fn load(name: &str) -> InputState {
+        InputState::from_file(PathBuf::from("testdata/imports").join(name), Load::Imports).unwrap()
+    }
+
+    #[test]
+    fn test_loader() {
+        // a file imported twice is loaded once, before the files importing it
+        let input = load("main.calc");
+        let names: Vec<_> = input
+            .sources
+            .order()
+            .iter()
+            .map(|&source| input.sources.get(source).name())
+            .collect();
+        insta::assert_debug_snapshot!(names);
+
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+        insta::assert_snapshot!("loader_output", output);
+    }
+
+    #[test]
+    fn test_loader_errors() {
+        let load_err = |name: &str| {
+            InputState::from_file(PathBuf::from("testdata/imports").join(name), Load::Imports)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        insta::assert_snapshot!("import cycle", load_err("cycle_a.calc"));
+        insta::assert_snapshot!("unreadable file", load_err("missing_import.calc"));
+
+        // the name of the prelude can't be taken by a module
+        let reserved = InputState::from_code("use prelude;".to_owned(), Load::Prelude).err();
+        insta::assert_snapshot!("reserved module", reserved.unwrap().to_string());
+
+        // errors are located in the imported file
+        let tokenized = tokenize(load("broken_main.calc")).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        insta::assert_snapshot!(
+            "imported type error",
+            check(expand(parsed).unwrap()).err().unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_loader_prelude() {
+        // the prelude comes first, and its errors are located by its name
+        let input = InputState::from_code("print TAU;".to_owned(), Load::Prelude).unwrap();
+        let tokenized = tokenize(input).unwrap();
+        let first = tokenized.tokens()[0].to_error(&tokenized.sources);
+        insta::assert_snapshot!("prelude error", first.to_string());
+
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let mut output = String::new();
+        interpret(&checked, &mut output).unwrap();
+        insta::assert_snapshot!("prelude output", output);
+    }
+}
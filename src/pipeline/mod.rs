@@ -1,6 +1,8 @@
 pub mod checker;
+pub mod expander;
 pub mod formatter;
 pub mod interpreter;
+pub mod loader;
 pub mod optimizer;
 pub mod parser;
 pub mod tokenizer;
@@ -1,20 +1,27 @@
-use crate::context::checked_ast::{CheckedExpr, CheckedStmt};
-use crate::data::ast::{BinOp, BinOpKind, Expr, ExprKind, Stmt, StmtKind};
+use crate::context::checked_ast::{CheckedExpr, CheckedStmt, CheckedVarName, Type, TypeInfo};
+use crate::data::ast::{BinOp, BinOpKind, Expr, ExprKind, Stmt, StmtKind, UnitName};
+use crate::data::builtin::Builtin;
+use crate::data::unit::Unit;
 use crate::errors::OptimizerError;
+use crate::pipeline::interpreter::{compare, eval_bitwise};
 use crate::states::CheckedState;
+use std::cell::Cell;
 use std::ops::{Add, Div, Mul, Sub};
 
 type Result<T> = std::result::Result<T, OptimizerError>;
 
 pub(crate) fn optimize(input: CheckedState) -> CheckedState {
-    let optimizer = Optimizer::new(input);
-    optimizer.run()
+    let optimizer = Optimizer::new();
+    optimizer.run(input)
 }
 
-struct Optimizer {
-    input: CheckedState,
+/// Fold the constants of a single expression, e.g. to simplify a derivative.
+pub(crate) fn fold(expr: CheckedExpr) -> CheckedExpr {
+    Optimizer::new().optimize_expr(expr)
 }
 
+struct Optimizer {}
+
 // Note:
 // As it stands, this object is useless as not local state is needed, and could be replaced by a
 // functions. Clippy rightly complains about this, thus the #[allow(only_used_in_recursion)].
@@ -22,27 +29,128 @@ struct Optimizer {
 
 #[allow(clippy::only_used_in_recursion)]
 impl Optimizer {
-    fn new(input: CheckedState) -> Self {
-        Self { input }
+    fn new() -> Self {
+        Self {}
     }
 
-    fn run(mut self) -> CheckedState {
-        let old_stmts: Vec<_> = self.input.ast.stmts_mut().drain(..).collect();
+    fn run(mut self, mut input: CheckedState) -> CheckedState {
+        let old_stmts: Vec<_> = input.ast.stmts_mut().drain(..).collect();
         old_stmts.into_iter().for_each(|stmt| {
-            let new_stmt = self.optimize_stmt(stmt);
-            self.input.ast.push_stmt(new_stmt);
+            if let Some(new_stmt) = self.optimize_stmt(stmt) {
+                input.ast.push_stmt(new_stmt);
+            }
         });
 
-        self.input
+        input
     }
 
-    fn optimize_stmt(&mut self, stmt: CheckedStmt) -> CheckedStmt {
+    /// Optimize a statement, or return `None` if it can be eliminated.
+    fn optimize_stmt(&mut self, stmt: CheckedStmt) -> Option<CheckedStmt> {
         match stmt.kind {
-            StmtKind::Expr { expr } => Stmt::expr(self.optimize_expr(expr), stmt.meta),
+            // expression statements are only useful for their side effects
+            StmtKind::Expr { expr } if !has_side_effects(&expr) => None,
+            StmtKind::Expr { expr } => Some(Stmt::expr(self.optimize_expr(expr), stmt.meta)),
             StmtKind::Assign { name, value } => {
-                Stmt::assign(name, self.optimize_expr(value), stmt.meta)
+                Some(Stmt::assign(name, self.optimize_expr(value), stmt.meta))
+            }
+            StmtKind::Let { pattern, value } => {
+                Some(Stmt::let_(pattern, self.optimize_expr(value), stmt.meta))
+            }
+            StmtKind::Declare {
+                name,
+                type_name,
+                value,
+            } => Some(Stmt::declare(
+                name,
+                type_name,
+                self.optimize_expr(value),
+                stmt.meta,
+            )),
+            StmtKind::CompoundAssign { name, op, value } => Some(Stmt::compound_assign(
+                name,
+                op,
+                self.optimize_expr(value),
+                stmt.meta,
+            )),
+            StmtKind::Print { expr } => Some(Stmt::print(self.optimize_expr(expr), stmt.meta)),
+            StmtKind::Seed { seed } => Some(Stmt::seed(self.optimize_expr(seed), stmt.meta)),
+            StmtKind::Exit { code } => Some(Stmt::exit(self.optimize_expr(code), stmt.meta)),
+            // an assertion known to hold can be eliminated, unless evaluating it may still fail
+            StmtKind::Assert { cond, message } => {
+                let removable = !has_side_effects(&cond);
+                match self.optimize_expr(cond) {
+                    Expr {
+                        kind: ExprKind::Bool(true),
+                        ..
+                    } if removable => None,
+                    new_cond => Some(Stmt::assert(new_cond, message, stmt.meta)),
+                }
+            }
+            StmtKind::For { var, iter, body } => {
+                let new_body: Vec<_> = body
+                    .into_iter()
+                    .filter_map(|stmt| self.optimize_stmt(stmt))
+                    .collect();
+
+                // a loop with nothing left to do can be eliminated altogether
+                if new_body.is_empty() && !has_side_effects(&iter) {
+                    None
+                } else {
+                    Some(Stmt::for_(
+                        var,
+                        self.optimize_expr(iter),
+                        new_body,
+                        stmt.meta,
+                    ))
+                }
+            }
+            StmtKind::Plot {
+                name,
+                value,
+                var,
+                start,
+                end,
+                path,
+            } => Some(Stmt::plot(
+                name,
+                self.optimize_expr(value),
+                var,
+                self.optimize_expr(start),
+                self.optimize_expr(end),
+                path,
+                stmt.meta,
+            )),
+            StmtKind::Match { expr, arms } => {
+                let new_arms: Vec<_> = arms
+                    .into_iter()
+                    .map(|(pattern, body)| {
+                        let new_body: Vec<_> = body
+                            .into_iter()
+                            .filter_map(|stmt| self.optimize_stmt(stmt))
+                            .collect();
+                        (pattern, new_body)
+                    })
+                    .collect();
+
+                // empty arms are kept, as the match must remain exhaustive
+                if new_arms.iter().all(|(_, body)| body.is_empty()) && !has_side_effects(&expr) {
+                    None
+                } else {
+                    Some(Stmt::match_(self.optimize_expr(expr), new_arms, stmt.meta))
+                }
             }
-            StmtKind::Print { expr } => Stmt::print(self.optimize_expr(expr), stmt.meta),
+            StmtKind::Record { name, fields } => Some(Stmt::record(name, fields, stmt.meta)),
+            StmtKind::Enum { name, variants } => Some(Stmt::enum_(name, variants, stmt.meta)),
+            StmtKind::Module { name, body } => {
+                let new_body = body
+                    .into_iter()
+                    .filter_map(|stmt| self.optimize_stmt(stmt))
+                    .collect();
+                Some(Stmt::module(name, new_body, stmt.meta))
+            }
+            StmtKind::Import { path } => Some(Stmt::import(path, stmt.meta)),
+            StmtKind::Use { module } => Some(Stmt::use_(module, stmt.meta)),
+            StmtKind::Macro { .. } => unreachable!("macros are expanded before type checking"),
         }
     }
 
@@ -55,18 +163,600 @@ impl Optimizer {
                 let new_right = self.optimize_expr(*right);
 
                 match (&new_left.kind, &new_right.kind) {
+                    (Integer(a), Integer(b)) if op.kind.is_comparison() => {
+                        Expr::bool(compare(op.kind, a, b), expr.meta)
+                    }
+                    (Float(a), Float(b)) if op.kind.is_comparison() => {
+                        Expr::bool(compare(op.kind, a, b), expr.meta)
+                    }
+                    (Bool(a), Bool(b)) => Expr::bool(compare(op.kind, a, b), expr.meta),
+                    (Variant { variant: a, .. }, Variant { variant: b, .. }) => {
+                        Expr::bool(compare(op.kind, &a.kind, &b.kind), expr.meta)
+                    }
+                    (Integer(a), Integer(b)) if op.kind.is_bitwise() => {
+                        Expr::integer(eval_bitwise(op.kind, *a, *b), expr.meta)
+                    }
+                    // division by zero and overflowing divisions are left for the runtime to report
+                    (Integer(a), Integer(b))
+                        if op.kind == BinOpKind::Div && a.checked_div(*b).is_none() =>
+                    {
+                        Expr::bin_op(op, new_left, new_right, expr.meta)
+                    }
                     (Integer(a), Integer(b)) => Expr::integer(op.eval(*a, *b), expr.meta),
                     (Float(a), Float(b)) => Expr::float(op.eval(*a, *b), expr.meta),
+                    _ if expr.meta.type_ == Type::Complex => {
+                        match (complex_const(&new_left), complex_const(&new_right)) {
+                            (Some(a), Some(b)) => complex_expr(op.eval_complex(a, b), expr.meta),
+                            _ => Expr::bin_op(op, new_left, new_right, expr.meta),
+                        }
+                    }
                     _ => Expr::bin_op(op, new_left, new_right, expr.meta),
                     //TODO: optimize tuple computation
                 }
             }
+            UnaryOp { op, operand } => Expr::unary_op(op, self.optimize_expr(*operand), expr.meta),
+            Call { name, args } => {
+                let new_args = args.into_iter().map(|e| self.optimize_expr(e)).collect();
+                fuse_call(Expr::call(name, new_args, expr.meta))
+            }
+            Apply { callee, args } => Expr::apply(
+                self.optimize_expr(*callee),
+                args.into_iter().map(|e| self.optimize_expr(e)).collect(),
+                expr.meta,
+            ),
+            Tuple(exprs) => Expr::tuple(
+                exprs.into_iter().map(|e| self.optimize_expr(e)).collect(),
+                expr.meta,
+            ),
+            Matrix(rows) => Expr::matrix(
+                rows.into_iter()
+                    .map(|row| row.into_iter().map(|e| self.optimize_expr(e)).collect())
+                    .collect(),
+                expr.meta,
+            ),
+            Record { name, fields } => Expr::record(
+                name,
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field, self.optimize_expr(value)))
+                    .collect(),
+                expr.meta,
+            ),
+            Field {
+                expr: record,
+                field,
+            } => {
+                let new_record = self.optimize_expr(*record);
+                match new_record.kind {
+                    // accessing a field of a record literal selects it, provided the other fields
+                    // can be discarded
+                    Record { fields, .. }
+                        if fields
+                            .iter()
+                            .all(|(f, value)| f.kind == field.kind || !has_side_effects(value)) =>
+                    {
+                        fields
+                            .into_iter()
+                            .find_map(|(f, value)| (f.kind == field.kind).then_some(value))
+                            .expect("checked by the type checker")
+                    }
+                    kind => Expr::field(
+                        Expr {
+                            kind,
+                            meta: new_record.meta,
+                        },
+                        field,
+                        expr.meta,
+                    ),
+                }
+            }
+            TupleIndex { expr: tuple, index } => {
+                let new_tuple = self.optimize_expr(*tuple);
+                match new_tuple.kind {
+                    // same as for record fields
+                    Tuple(mut exprs)
+                        if exprs
+                            .iter()
+                            .enumerate()
+                            .all(|(i, e)| i == index || !has_side_effects(e)) =>
+                    {
+                        exprs.swap_remove(index)
+                    }
+                    kind => Expr::tuple_index(
+                        Expr {
+                            kind,
+                            meta: new_tuple.meta,
+                        },
+                        index,
+                        expr.meta,
+                    ),
+                }
+            }
+            Quantity { value, unit } => {
+                let new_value = self.optimize_expr(*value);
+                let unit_def = Unit::from_name(&unit.kind).expect("checked by the type checker");
+
+                // convert literals to the base unit, so that no scaling is needed at runtime
+                match (&new_value.kind, unit_def.dim.base_unit()) {
+                    (Float(fl), Some(base_unit)) if unit_def.scale != 1.0 => Expr::quantity(
+                        Expr::float(fl * unit_def.scale, new_value.meta),
+                        UnitName::new(base_unit, unit.meta),
+                        expr.meta,
+                    ),
+                    _ => Expr::quantity(new_value, unit, expr.meta),
+                }
+            }
+            Range { start, end } => Expr::range(
+                self.optimize_expr(*start),
+                self.optimize_expr(*end),
+                expr.meta,
+            ),
+            Convert(operand) => {
+                let new_operand = self.optimize_expr(*operand);
+                match new_operand.kind {
+                    Integer(i) if expr.meta.type_ == Type::Float => {
+                        Expr::float(i as f64, expr.meta)
+                    }
+                    _ => Expr::convert(new_operand, expr.meta),
+                }
+            }
+            // a constant condition selects its branch, the other one is dead code
+            Conditional {
+                cond,
+                then_branch,
+                else_branch,
+            } => match self.optimize_expr(*cond) {
+                Expr {
+                    kind: Bool(true), ..
+                } => self.optimize_expr(*then_branch),
+                Expr {
+                    kind: Bool(false), ..
+                } => self.optimize_expr(*else_branch),
+                new_cond => Expr::conditional(
+                    new_cond,
+                    self.optimize_expr(*then_branch),
+                    self.optimize_expr(*else_branch),
+                    expr.meta,
+                ),
+            },
+            // the default of a known optional is either selected or dead code
+            Unwrap {
+                expr: optional,
+                default,
+            } => {
+                let new_optional = self.optimize_expr(*optional);
+                match new_optional.kind {
+                    None => self.optimize_expr(*default),
+                    Convert(value) if matches!(new_optional.meta.type_, Type::Optional(_)) => {
+                        *value
+                    }
+                    kind => Expr::unwrap(
+                        Expr {
+                            kind,
+                            meta: new_optional.meta,
+                        },
+                        self.optimize_expr(*default),
+                        expr.meta,
+                    ),
+                }
+            }
+            Lambda {
+                params,
+                ret,
+                body,
+                captures,
+            } => Expr::lambda(params, ret, self.optimize_expr(*body), captures, expr.meta),
+            Deriv { expr: body, var } => Expr::deriv(self.optimize_expr(*body), var, expr.meta),
+            Eval {
+                expr: symbolic,
+                var,
+                value,
+            } => Expr::eval(
+                self.optimize_expr(*symbolic),
+                var,
+                self.optimize_expr(*value),
+                expr.meta,
+            ),
+            // make integer literals resolved to floats by the type checker foldable
+            Integer(i) if expr.meta.type_ == Type::Float => Expr::float(i as f64, expr.meta),
             _ => expr,
         }
     }
 }
 
+/// Check if evaluating an expression has side effects (e.g. drawing a random number), which
+/// includes failing at runtime (e.g. an integer division by zero).
+///
+/// Such expressions must be neither folded nor eliminated. Folding only ever applies to literals,
+/// so this is only a concern for dead code elimination.
+fn has_side_effects(expr: &CheckedExpr) -> bool {
+    match &expr.kind {
+        // the divisor of an integer division may be zero, including for each element of a tuple
+        ExprKind::BinOp { op, .. }
+            if op.kind == BinOpKind::Div && is_integer_based(&op.meta.type_) =>
+        {
+            true
+        }
+        // the symbolic expression may be in another variable
+        ExprKind::Eval { .. } => true,
+        // the body of a closure isn't known here
+        ExprKind::Call { name, .. } if matches!(name.meta.type_, Type::Function { .. }) => true,
+        ExprKind::Apply { .. } => true,
+        // higher-order built-ins call their function argument, which only has side effects if its
+        // body does, provided it is known
+        ExprKind::Call { name, args } if is_higher_order_call(name) => {
+            args.iter().any(|arg| match &arg.kind {
+                ExprKind::Lambda { body, .. } => has_side_effects(body),
+                _ => matches!(arg.meta.type_, Type::Function { .. }) || has_side_effects(arg),
+            })
+        }
+        ExprKind::Call { name, args } => {
+            Builtin::from_name(&name.kind).is_none_or(|b| b.has_side_effects())
+                || args.iter().any(has_side_effects)
+        }
+        ExprKind::BinOp { left, right, .. }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+        }
+        | ExprKind::Unwrap {
+            expr: left,
+            default: right,
+        } => has_side_effects(left) || has_side_effects(right),
+        ExprKind::UnaryOp { operand, .. } => has_side_effects(operand),
+        ExprKind::Quantity { value, .. }
+        | ExprKind::Convert(value)
+        | ExprKind::Field { expr: value, .. }
+        | ExprKind::TupleIndex { expr: value, .. }
+        | ExprKind::Deriv { expr: value, .. } => has_side_effects(value),
+        ExprKind::Record { fields, .. } => fields.iter().any(|(_, value)| has_side_effects(value)),
+        ExprKind::Conditional {
+            cond,
+            then_branch,
+            else_branch,
+        } => {
+            has_side_effects(cond) || has_side_effects(then_branch) || has_side_effects(else_branch)
+        }
+        ExprKind::Tuple(exprs) => exprs.iter().any(has_side_effects),
+        ExprKind::Matrix(rows) => rows.iter().flatten().any(has_side_effects),
+        // creating a closure only captures variables
+        ExprKind::Variable(_)
+        | ExprKind::Variant { .. }
+        | ExprKind::Lambda { .. }
+        | ExprKind::Integer(_)
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::Imaginary(_)
+        | ExprKind::None => false,
+    }
+}
+
+fn is_integer_based(type_: &Type) -> bool {
+    match type_ {
+        Type::Tuple { type_, .. } => is_integer_based(type_),
+        type_ => *type_ == Type::Integer,
+    }
+}
+
+fn is_higher_order_call(name: &CheckedVarName) -> bool {
+    !matches!(name.meta.type_, Type::Function { .. })
+        && Builtin::from_name(&name.kind).is_some_and(|b| b.is_higher_order())
+}
+
+/// Fuse a call to `map` or `fold` applied to the result of `map` into a single call, such that no
+/// intermediate sequence is built, e.g. `map(map(t, x => x + 1), y => y * 2)` becomes
+/// `map(t, x => (x + 1) * 2)`.
+///
+/// The body of the inner lambda is substituted for the parameter of the outer one, so the parameter
+/// must be used exactly once, lest the work be duplicated, and the inner body must have no side
+/// effects and be unable to fail, as it is evaluated lazily. To keep things simple, the outer body
+/// must not contain lambdas, and neither body may use a variable which would be shadowed by the
+/// parameters of the fused lambda.
+fn fuse_call(call: CheckedExpr) -> CheckedExpr {
+    let ExprKind::Call { name, mut args } = call.kind else {
+        unreachable!("only calls are fused")
+    };
+    let fn_index = match name.kind.as_str() {
+        "map" => 1,
+        "fold" => 2,
+        _ => 0,
+    };
+    let fusable = fn_index != 0
+        && is_higher_order_call(&name)
+        && matches!(&args[0].kind, ExprKind::Call { name, args }
+            if name.kind == "map" && is_higher_order_call(name)
+                && matches!(args[1].kind, ExprKind::Lambda { .. }))
+        && matches!(args[fn_index].kind, ExprKind::Lambda { .. });
+    if !fusable {
+        return Expr::call(name, args, call.meta);
+    }
+
+    let (
+        ExprKind::Call {
+            args: inner_args, ..
+        },
+        ExprKind::Lambda {
+            params: outer_params,
+            body: outer_body,
+            ..
+        },
+    ) = (&args[0].kind, &args[fn_index].kind)
+    else {
+        unreachable!("checked above")
+    };
+    let ExprKind::Lambda {
+        params: inner_params,
+        body: inner_body,
+        ..
+    } = &inner_args[1].kind
+    else {
+        unreachable!("checked above")
+    };
+
+    // the element is the last parameter of the outer lambda, a `fold` also has an accumulator
+    let (inner_param, _) = &inner_params[0];
+    let (outer_param, _) = outer_params.last().expect("checked by the type checker");
+    let acc = outer_params
+        .first()
+        .filter(|_| outer_params.len() == 2)
+        .map(|(acc, _)| acc);
+    let shadowed = |expr: &CheckedExpr, var: &str| {
+        any_expr(expr, &|e| match &e.kind {
+            ExprKind::Variable(name) | ExprKind::Call { name, .. } => name.kind == var,
+            _ => false,
+        })
+    };
+    let uses = Cell::new(0);
+    any_expr(outer_body, &|e| {
+        if matches!(&e.kind, ExprKind::Variable(name) if name.kind == outer_param.kind) {
+            uses.set(uses.get() + 1);
+        }
+        false
+    });
+    if has_side_effects(inner_body)
+        || uses.get() != 1
+        || any_expr(outer_body, &|e| matches!(e.kind, ExprKind::Lambda { .. }))
+        || any_expr(
+            outer_body,
+            &|e| matches!(&e.kind, ExprKind::Call { name, .. } if name.kind == outer_param.kind),
+        )
+        || (inner_param.kind != outer_param.kind && shadowed(outer_body, &inner_param.kind))
+        || acc.is_some_and(|acc| acc.kind == inner_param.kind || shadowed(inner_body, &acc.kind))
+    {
+        return Expr::call(name, args, call.meta);
+    }
+
+    let ExprKind::Call {
+        args: inner_args, ..
+    } = args.remove(0).kind
+    else {
+        unreachable!("checked above")
+    };
+    let mut inner_args = inner_args.into_iter();
+    let seq = inner_args.next().expect("checked by the type checker");
+    let ExprKind::Lambda {
+        params: mut inner_params,
+        ret: inner_ret,
+        body: inner_body,
+        captures: inner_captures,
+    } = inner_args.next().expect("checked by the type checker").kind
+    else {
+        unreachable!("checked above")
+    };
+    let outer = args.pop().expect("checked by the type checker");
+    let ExprKind::Lambda {
+        params: mut outer_params,
+        ret: outer_ret,
+        body: outer_body,
+        mut captures,
+    } = outer.kind
+    else {
+        unreachable!("checked above")
+    };
+    let Type::Function { ret: ret_type, .. } = outer.meta.type_ else {
+        unreachable!("checked by the type checker")
+    };
+
+    // explicit types are only kept if both lambdas have them
+    let typed = inner_ret.is_some() && outer_ret.is_some();
+    let (outer_param, _) = outer_params.pop().expect("checked by the type checker");
+    outer_params.push(inner_params.remove(0));
+    if !typed {
+        outer_params
+            .iter_mut()
+            .for_each(|(_, type_name)| *type_name = None);
+    }
+    for capture in inner_captures {
+        if !captures.iter().any(|c| c.kind == capture.kind) {
+            captures.push(capture);
+        }
+    }
+
+    let fn_type = Type::Function {
+        params: outer_params
+            .iter()
+            .map(|(param, _)| param.meta.type_.clone())
+            .collect(),
+        ret: ret_type,
+    };
+    let body = substitute(*outer_body, &outer_param.kind, &inner_body);
+    let lambda = Expr::lambda(
+        outer_params,
+        outer_ret.filter(|_| typed),
+        body,
+        captures,
+        TypeInfo::new(fn_type, outer.meta.tok_span),
+    );
+
+    // the initial value of a `fold` stays in place
+    args.insert(0, seq);
+    args.push(lambda);
+    Expr::call(name, args, call.meta)
+}
+
+/// Check if any sub-expression of an expression, including itself, satisfies a predicate.
+fn any_expr(expr: &CheckedExpr, pred: &impl Fn(&CheckedExpr) -> bool) -> bool {
+    if pred(expr) {
+        return true;
+    }
+    match &expr.kind {
+        ExprKind::BinOp { left, right, .. }
+        | ExprKind::Range {
+            start: left,
+            end: right,
+        }
+        | ExprKind::Unwrap {
+            expr: left,
+            default: right,
+        }
+        | ExprKind::Eval {
+            expr: left,
+            value: right,
+            ..
+        } => any_expr(left, pred) || any_expr(right, pred),
+        ExprKind::UnaryOp { operand: value, .. }
+        | ExprKind::Quantity { value, .. }
+        | ExprKind::Convert(value)
+        | ExprKind::Field { expr: value, .. }
+        | ExprKind::TupleIndex { expr: value, .. }
+        | ExprKind::Lambda { body: value, .. }
+        | ExprKind::Deriv { expr: value, .. } => any_expr(value, pred),
+        ExprKind::Call { args: exprs, .. } | ExprKind::Tuple(exprs) => {
+            exprs.iter().any(|e| any_expr(e, pred))
+        }
+        ExprKind::Apply { callee, args } => {
+            any_expr(callee, pred) || args.iter().any(|e| any_expr(e, pred))
+        }
+        ExprKind::Matrix(rows) => rows.iter().flatten().any(|e| any_expr(e, pred)),
+        ExprKind::Record { fields, .. } => fields.iter().any(|(_, value)| any_expr(value, pred)),
+        ExprKind::Conditional {
+            cond,
+            then_branch,
+            else_branch,
+        } => any_expr(cond, pred) || any_expr(then_branch, pred) || any_expr(else_branch, pred),
+        ExprKind::Variable(_)
+        | ExprKind::Variant { .. }
+        | ExprKind::Integer(_)
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::Imaginary(_)
+        | ExprKind::None => false,
+    }
+}
+
+/// Replace the uses of a variable with an expression. The expression must not contain lambdas,
+/// whose captures would need to be updated.
+fn substitute(expr: CheckedExpr, var: &str, value: &CheckedExpr) -> CheckedExpr {
+    let sub = |e: Box<CheckedExpr>| substitute(*e, var, value);
+    let sub_all = |exprs: Vec<CheckedExpr>| -> Vec<CheckedExpr> {
+        exprs
+            .into_iter()
+            .map(|e| substitute(e, var, value))
+            .collect()
+    };
+    let meta = expr.meta;
+    match expr.kind {
+        ExprKind::Variable(name) if name.kind == var => value.clone(),
+        ExprKind::BinOp { op, left, right } => Expr::bin_op(op, sub(left), sub(right), meta),
+        ExprKind::UnaryOp { op, operand } => Expr::unary_op(op, sub(operand), meta),
+        ExprKind::Call { name, args } => Expr::call(name, sub_all(args), meta),
+        ExprKind::Apply { callee, args } => Expr::apply(sub(callee), sub_all(args), meta),
+        ExprKind::Tuple(exprs) => Expr::tuple(sub_all(exprs), meta),
+        ExprKind::Matrix(rows) => Expr::matrix(rows.into_iter().map(sub_all).collect(), meta),
+        ExprKind::Record { name, fields } => Expr::record(
+            name,
+            fields
+                .into_iter()
+                .map(|(field, e)| (field, substitute(e, var, value)))
+                .collect(),
+            meta,
+        ),
+        ExprKind::Field { expr, field } => Expr::field(sub(expr), field, meta),
+        ExprKind::TupleIndex { expr, index } => Expr::tuple_index(sub(expr), index, meta),
+        ExprKind::Range { start, end } => Expr::range(sub(start), sub(end), meta),
+        ExprKind::Quantity { value: e, unit } => Expr::quantity(sub(e), unit, meta),
+        ExprKind::Convert(e) => Expr::convert(sub(e), meta),
+        ExprKind::Unwrap { expr, default } => Expr::unwrap(sub(expr), sub(default), meta),
+        ExprKind::Conditional {
+            cond,
+            then_branch,
+            else_branch,
+        } => Expr::conditional(sub(cond), sub(then_branch), sub(else_branch), meta),
+        // the variable of a derivative shadows the substituted one
+        ExprKind::Deriv { expr, var: bound } if bound.kind != var => {
+            Expr::deriv(sub(expr), bound, meta)
+        }
+        ExprKind::Eval {
+            expr,
+            var: bound,
+            value: e,
+        } => Expr::eval(sub(expr), bound, sub(e), meta),
+        kind @ (ExprKind::Variable(_)
+        | ExprKind::Deriv { .. }
+        | ExprKind::Lambda { .. }
+        | ExprKind::Variant { .. }
+        | ExprKind::Integer(_)
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::Imaginary(_)
+        | ExprKind::None) => Expr { kind, meta },
+    }
+}
+
+/// Extract the value of a constant expression which can be promoted to a complex number.
+///
+/// Besides literals, this recognises the `re + im i` form produced by [`complex_expr`], so that
+/// folding can be applied repeatedly.
+fn complex_const(expr: &CheckedExpr) -> Option<(f64, f64)> {
+    match &expr.kind {
+        ExprKind::Integer(i) => Some((*i as f64, 0.0)),
+        ExprKind::Float(fl) => Some((*fl, 0.0)),
+        ExprKind::Imaginary(im) => Some((0.0, *im)),
+        ExprKind::BinOp { op, left, right } => match (&left.kind, op.kind, &right.kind) {
+            (ExprKind::Float(re), BinOpKind::Add, ExprKind::Imaginary(im)) => Some((*re, *im)),
+            (ExprKind::Float(re), BinOpKind::Sub, ExprKind::Imaginary(im)) => Some((*re, -*im)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Build the canonical `re + im i` expression for a complex constant.
+fn complex_expr((re, im): (f64, f64), meta: TypeInfo) -> CheckedExpr {
+    if re == 0.0 {
+        return Expr::imaginary(im, meta);
+    }
+
+    let (op, im) = if im.is_sign_negative() {
+        (BinOpKind::Sub, -im)
+    } else {
+        (BinOpKind::Add, im)
+    };
+    let tok_span = meta.tok_span.clone();
+
+    Expr::bin_op(
+        BinOp::new(op, TypeInfo::new(Type::Complex, tok_span.clone())),
+        Expr::float(re, TypeInfo::new(Type::Float, tok_span.clone())),
+        Expr::imaginary(im, TypeInfo::new(Type::Complex, tok_span)),
+        meta,
+    )
+}
+
 impl<M> BinOp<M> {
+    fn eval_complex(&self, (a, b): (f64, f64), (c, d): (f64, f64)) -> (f64, f64) {
+        match self.kind {
+            BinOpKind::Add => (a + c, b + d),
+            BinOpKind::Sub => (a - c, b - d),
+            BinOpKind::Mul => (a * c - b * d, a * d + b * c),
+            BinOpKind::Div => {
+                let den = c * c + d * d;
+                ((a * c + b * d) / den, (b * c - a * d) / den)
+            }
+            BinOpKind::MatMul => unreachable!("matrix multiplication of scalars"),
+            _ => unreachable!("bitwise operation of non-integers"),
+        }
+    }
+
     fn eval<T>(&self, a: T, b: T) -> T
     where
         T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>,
@@ -76,6 +766,8 @@ impl<M> BinOp<M> {
             BinOpKind::Sub => a - b,
             BinOpKind::Mul => a * b,
             BinOpKind::Div => a / b,
+            BinOpKind::MatMul => unreachable!("matrix multiplication of scalars"),
+            _ => unreachable!("bitwise operation of non-integers"),
         }
     }
 }
@@ -83,6 +775,7 @@ impl<M> BinOp<M> {
 #[cfg(test)]
 mod test {
     use crate::pipeline::checker::check;
+    use crate::pipeline::expander::expand;
     use crate::pipeline::optimizer::optimize;
     use crate::pipeline::parser::parse;
     use crate::pipeline::tokenizer::tokenize;
@@ -93,9 +786,230 @@ mod test {
         let input = InputState::from("print (1 + 4) * 3 / (3 + 2);");
         let tokenized = tokenize(input).unwrap();
         let parsed = parse(tokenized).unwrap();
-        let checked = check(parsed).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
         let optimized = optimize(checked);
 
         insta::assert_debug_snapshot!(optimized.ast);
     }
+
+    #[test]
+    fn test_optimizer_side_effects() {
+        let input = InputState::from("1 + 2; rand(); x = rand_int(1, 6) + 2 * 3; print x;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_units() {
+        let input = InputState::from("print 3.0 km / 2.0 h; print -5.0 min + 1.0 s;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_complex() {
+        let input = InputState::from("print (3 + 4i) * 2i; print 1.5 - 2i * 2;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_for() {
+        let input = InputState::from(
+            "for i in 0..2 * 3 { i + 1; print i * (2 + 2); } for i in 0..3 { 1; }",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_bitwise() {
+        let input = InputState::from("x = 3; print 1 << 4 | 2; print x & 6 ^ 1 << 33;");
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_conditional() {
+        let input = InputState::from(
+            "x = 2; print if 1 < 2 then x else 4; print (if x > 1 then x * (1 + 1) else 0) > 1 == 0.5 > 1.0;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_assert() {
+        let input = InputState::from(
+            r#"x = 1; assert 1 < 2, "always"; assert x < 2, "sometimes"; assert 2 < 1, "never";"#,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_failing_expr() {
+        let input = InputState::from(
+            r#"x = 0; 1 / x; 1.0 / 0.0; print (1, 2 / x).0; assert (1 == 1, (1, 2) / x).0, "fails";"#,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_record() {
+        let input = InputState::from(
+            "record P { x float, y float } print P { x: 1.0 + 1.0, y: 2.0 }.x; print P { x: 1.0, y: rand() }.x; p = P { x: 1.0, y: 2.0 }; print p.y;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_product() {
+        let input = InputState::from(
+            "print (1, 2.0 * 2.0, true).1; print (rand(), 2.0).1; t = (1, 2.0); print t.0;",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_match() {
+        let input = InputState::from(
+            "enum E { A, B } e = E::A; match e { E::A => { print E::A == E::B; } E::B => { 1 + 2; } } match e { E::B => {} E::A => { 3; } }",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_lambda() {
+        let input = InputState::from(
+            "f = fn(x int) int => x * (2 + 3); f(1); fn(x int) int => x; print f(2);",
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_higher_order() {
+        // chains are fused, unless the body of the inner lambda has side effects or may fail, its
+        // result isn't used exactly once, or a variable would be shadowed
+        let input = InputState::from(
+            r###"
+                k = 3;
+                x = 10;
+                print map(map(map(0..4, x => x + 1), y => y * k), z => z - 1);
+                print fold(map(0..4, x => x * x), 0, (acc, y) => acc + y);
+                print map(map(0..4, x => rand_int(0, x)), y => y * 2);
+                print map(map(0..4, x => x + 1), y => y * x);
+                print map(map(0..4, x => x + 1), y => y * y);
+                print map(map(0..4, x => 10 / x), y => 1);
+                map(0..4, x => x * 2);
+                map(0..4, x => rand_int(0, x));
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
+
+    #[test]
+    fn test_optimizer_optional() {
+        // the default is dropped when the optional is known to hold a value
+        let input = InputState::from(
+            r###"
+                x: int? = 2;
+                print (if true then none else 4) ?? 5;
+                print (if false then none else 4) ?? rand_int(0, 3);
+                print x ?? 1 + 2;
+            "###,
+        );
+        let tokenized = tokenize(input).unwrap();
+        let parsed = parse(tokenized).unwrap();
+        let checked = check(expand(parsed).unwrap()).unwrap();
+        let optimized = optimize(checked);
+
+        let mut output = String::new();
+        optimized.ast.format(&mut output).unwrap();
+        insta::assert_snapshot!(output);
+    }
 }
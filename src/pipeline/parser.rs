@@ -1,5 +1,7 @@
 use crate::context::ast::Ast;
-use crate::data::ast::{BinOp, Expr, Stmt, UnaryOp, VarName};
+use crate::data::ast::{
+    BinOp, BinOpKind, Expr, Pattern, Stmt, StmtKind, TypeName, UnaryOp, VarName,
+};
 use crate::data::token::{Token, TokenKind};
 use crate::data::token_span::TokSpan;
 use crate::errors::{ParserError, Spanned, SyntaxError};
@@ -12,12 +14,46 @@ pub(crate) fn parse(input: TokenizedState) -> Result<ParsedState> {
     let mut parser = Parser::new(input);
     parser.run()?;
     Ok(ParsedState {
-        source: parser.input.source,
+        sources: parser.input.sources,
         token_stream: parser.input.token_stream,
         raw_ast: parser.ast,
     })
 }
 
+/// Turn the last statement of the main source, which must be an assignment such as `y = x * x`,
+/// into a plot of the assigned expression over an interval, as done by `calc plot`.
+pub(crate) fn plot(
+    mut input: ParsedState,
+    var: &str,
+    (start, end): (f64, f64),
+    path: &str,
+) -> Result<ParsedState> {
+    let stmts = input.raw_ast.stmts_mut();
+    match stmts.pop() {
+        Some(Stmt {
+            kind: StmtKind::Assign { name, value },
+            meta,
+        }) if meta.span().start.source == 0 => {
+            let (start, end) = (
+                Expr::float(start, meta.clone()),
+                Expr::float(end, meta.clone()),
+            );
+            let var = VarName::new(var, meta.clone());
+            stmts.push(Stmt::plot(name, value, var, start, end, path, meta));
+            Ok(input)
+        }
+        // the prelude, which comes first, has no statement to plot
+        Some(stmt) if stmt.span().start.source == 0 => Err(ParserError::SyntaxError(
+            SyntaxError::NothingToPlot,
+            stmt.to_error(&input.sources),
+        )),
+        _ => Err(ParserError::SyntaxError(
+            SyntaxError::NothingToPlot,
+            Default::default(),
+        )),
+    }
+}
+
 /// Returns the next token if it matches and advances the parser, or returns an error.
 macro_rules! expect {
     ($self:ident, $kind:pat) => {{
@@ -31,7 +67,7 @@ macro_rules! expect {
         } else {
             Err(ParserError::SyntaxError(
                 SyntaxError::UnexpectedToken(token.kind.clone()),
-                token.to_error(&$self.input.source),
+                token.to_error(&$self.input.sources),
             ))
         }
     }};
@@ -63,8 +99,15 @@ struct Parser {
     // state
     pos: usize,
 
+    /// end of the tokens of the current source, as statements don't span several sources
+    end: usize,
+
     /// stack to easily keep track of start/end tokens to compute TokSpan
     token_stack: Vec<Rc<Token>>,
+
+    /// whether a record literal may be parsed, which it can't right before a block, as
+    /// `for x in t { y: int? = x; }` would be mistaken for one
+    records_allowed: bool,
 }
 
 impl Parser {
@@ -73,13 +116,15 @@ impl Parser {
             input,
             ast: Ast::new(),
             pos: 0,
+            end: 0,
             token_stack: Vec::new(),
+            records_allowed: true,
         }
     }
 
     #[inline]
     fn tokens(&self) -> &[Rc<Token>] {
-        self.input.tokens()
+        &self.input.tokens()[..self.end]
     }
 
     fn peek(&self) -> Option<&TokenKind> {
@@ -102,6 +147,18 @@ impl Parser {
             .ok_or(ParserError::InternalError)
     }
 
+    /// Run a parsing function with record literals allowed or not.
+    fn with_records<R>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        let outer = std::mem::replace(&mut self.records_allowed, allowed);
+        let res = parse(self);
+        self.records_allowed = outer;
+        res
+    }
+
     fn mark_start(&mut self) -> Result<()> {
         self.token_stack.push(self.cur_tok()?.clone());
         Ok(())
@@ -112,19 +169,107 @@ impl Parser {
         Ok(TokSpan::new(start, self.prev_tok()?.clone()))
     }
 
+    /// Parse the sources one after the other, merging their statements.
     fn run(&mut self) -> Result<()> {
-        while self.pos < self.tokens().len() {
-            let stmt = self.parse_stmt()?;
-            self.ast.push_stmt(stmt);
+        while self.pos < self.input.tokens().len() {
+            let tokens = &self.input.tokens()[self.pos..];
+            let source = tokens[0].span().start.source;
+            self.end = self.pos
+                + tokens
+                    .iter()
+                    .take_while(|token| token.span().start.source == source)
+                    .count();
+
+            let start = self.cur_tok()?.clone();
+            let mut stmts = Vec::new();
+            while self.pos < self.end {
+                // imported sources and modules were loaded before this one by the loader
+                match self.peek() {
+                    Some(TokenKind::Import) => stmts.push(self.parse_import_stmt()?),
+                    Some(TokenKind::Use) => stmts.push(self.parse_use_stmt()?),
+                    Some(TokenKind::Macro) => stmts.push(self.parse_macro_stmt()?),
+                    _ => stmts.push(self.parse_stmt()?),
+                }
+            }
+
+            match self.input.sources.get(source).module() {
+                Some(module) => {
+                    let span = TokSpan::new(start, self.prev_tok()?.clone());
+                    self.ast.push_stmt(Stmt::module(module, stmts, span));
+                }
+                None => stmts.into_iter().for_each(|stmt| self.ast.push_stmt(stmt)),
+            }
         }
 
         Ok(())
     }
 
+    /// Parse an import, e.g. `import "consts.calc";`, which is only allowed at the top level.
+    fn parse_import_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+        expect!(self, TokenKind::Import)?;
+        let path = match &expect!(self, TokenKind::Str(_))?.kind {
+            TokenKind::Str(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        expect!(self, TokenKind::Semi)?;
+        Ok(Stmt::import(path, self.mark_end()?))
+    }
+
+    /// Parse a module use, e.g. `use geo;`, which is only allowed at the top level.
+    fn parse_use_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+        expect!(self, TokenKind::Use)?;
+        let module = match &expect!(self, TokenKind::Name(_))?.kind {
+            TokenKind::Name(name) => name.clone(),
+            _ => unreachable!(),
+        };
+        expect!(self, TokenKind::Semi)?;
+        Ok(Stmt::use_(module, self.mark_end()?))
+    }
+
+    /// Parse a macro definition, e.g. `macro square(x) => (x) * (x);`, which is only allowed at the
+    /// top level.
+    fn parse_macro_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+        expect!(self, TokenKind::Macro)?;
+        let name = self.parse_var_name()?;
+        expect!(self, TokenKind::LParen)?;
+        let mut params = vec![];
+        if accept!(self, TokenKind::RParen).is_none() {
+            loop {
+                params.push(self.parse_var_name()?);
+                if accept!(self, TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            expect!(self, TokenKind::RParen)?;
+        }
+        expect!(self, TokenKind::FatArrow)?;
+        let body = self.parse_expr()?;
+        expect!(self, TokenKind::Semi)?;
+
+        Ok(Stmt::macro_(name, params, body, self.mark_end()?))
+    }
+
     fn parse_stmt(&mut self) -> Result<Stmt<TokSpan>> {
         let start_pos = self.pos;
         match self.peek() {
             Some(TokenKind::Print) => self.parse_print_stmt(),
+            Some(TokenKind::Seed) => self.parse_seed_stmt(),
+            Some(TokenKind::For) => self.parse_for_stmt(),
+            Some(TokenKind::Assert) => self.parse_assert_stmt(),
+            Some(TokenKind::Exit) => self.parse_exit_stmt(),
+            Some(TokenKind::Record) => self.parse_record_stmt(),
+            Some(TokenKind::Enum) => self.parse_enum_stmt(),
+            Some(TokenKind::Match) => self.parse_match_stmt(),
+            Some(TokenKind::Let) => self.parse_let_stmt(),
+            Some(TokenKind::Plot) => self.parse_plot_stmt(),
+            Some(TokenKind::Name(_))
+                if self.tokens().get(self.pos + 1).map(|t| &t.kind) == Some(&TokenKind::Colon) =>
+            {
+                self.parse_declare_stmt()
+            }
             Some(TokenKind::Name(_)) => {
                 // here an expr stmt could be confused with an assignment stmt
                 let res = self.parse_assign_stmt();
@@ -150,68 +295,312 @@ impl Parser {
         Ok(Stmt::print(expr, self.mark_end()?))
     }
 
-    fn parse_assign_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+    fn parse_seed_stmt(&mut self) -> Result<Stmt<TokSpan>> {
         self.mark_start()?;
 
-        let name = self.parse_var_name()?;
-        expect!(self, TokenKind::Assign)?;
+        expect!(self, TokenKind::Seed)?;
+        expect!(self, TokenKind::LParen)?;
         let expr = self.parse_expr()?;
+        expect!(self, TokenKind::RParen)?;
         expect!(self, TokenKind::Semi)?;
 
-        Ok(Stmt::assign(name, expr, self.mark_end()?))
+        Ok(Stmt::seed(expr, self.mark_end()?))
     }
 
-    fn parse_expr_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+    fn parse_exit_stmt(&mut self) -> Result<Stmt<TokSpan>> {
         self.mark_start()?;
 
+        expect!(self, TokenKind::Exit)?;
+        expect!(self, TokenKind::LParen)?;
         let expr = self.parse_expr()?;
+        expect!(self, TokenKind::RParen)?;
         expect!(self, TokenKind::Semi)?;
 
-        Ok(Stmt::expr(expr, self.mark_end()?))
+        Ok(Stmt::exit(expr, self.mark_end()?))
     }
 
-    fn parse_expr(&mut self) -> Result<Expr<TokSpan>> {
-        self.parse_add_term()
+    fn parse_assert_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Assert)?;
+        let cond = self.parse_expr()?;
+        expect!(self, TokenKind::Comma)?;
+        let message = match &expect!(self, TokenKind::Str(_))?.kind {
+            TokenKind::Str(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        expect!(self, TokenKind::Semi)?;
+
+        Ok(Stmt::assert(cond, message, self.mark_end()?))
+    }
+
+    fn parse_for_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::For)?;
+        let var = self.parse_var_name()?;
+        expect!(self, TokenKind::In)?;
+        let iter = self.with_records(false, Self::parse_expr)?;
+        let body = self.parse_block()?;
+
+        Ok(Stmt::for_(var, iter, body, self.mark_end()?))
+    }
+
+    /// Parse a plot, e.g. `plot y = x * x for x in 0..10, "square.svg";`. The bounds of the interval
+    /// are parsed separately, as they are floats unlike those of a range.
+    fn parse_plot_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Plot)?;
+        let name = self.parse_var_name()?;
+        expect!(self, TokenKind::Assign)?;
+        let value = self.parse_expr()?;
+        expect!(self, TokenKind::For)?;
+        let var = self.parse_var_name()?;
+        expect!(self, TokenKind::In)?;
+        let start = self.parse_unwrap_expr()?;
+        expect!(self, TokenKind::DotDot)?;
+        let end = self.parse_unwrap_expr()?;
+        expect!(self, TokenKind::Comma)?;
+        let path = match &expect!(self, TokenKind::Str(_))?.kind {
+            TokenKind::Str(s) => s.clone(),
+            _ => unreachable!(),
+        };
+        expect!(self, TokenKind::Semi)?;
+
+        Ok(Stmt::plot(
+            name,
+            value,
+            var,
+            start,
+            end,
+            path,
+            self.mark_end()?,
+        ))
+    }
+
+    /// Parse a record declaration, e.g. `record Point { x float, y float }`.
+    fn parse_record_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Record)?;
+        let name = self.parse_named_type()?;
+        expect!(self, TokenKind::LBrace)?;
+        let mut fields = vec![];
+        loop {
+            let field = self.parse_var_name()?;
+            let type_ = self.parse_type_name()?;
+            fields.push((field, type_));
+
+            // a trailing comma is allowed
+            if accept!(self, TokenKind::Comma).is_none()
+                || matches!(self.peek(), Some(TokenKind::RBrace))
+            {
+                break;
+            }
+        }
+        expect!(self, TokenKind::RBrace)?;
+
+        Ok(Stmt::record(name, fields, self.mark_end()?))
+    }
+
+    /// Parse an enum declaration, e.g. `enum Mode { Fast, Slow }`.
+    fn parse_enum_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Enum)?;
+        let name = self.parse_named_type()?;
+        expect!(self, TokenKind::LBrace)?;
+        let mut variants = vec![];
+        loop {
+            variants.push(self.parse_var_name()?);
+
+            // a trailing comma is allowed
+            if accept!(self, TokenKind::Comma).is_none()
+                || matches!(self.peek(), Some(TokenKind::RBrace))
+            {
+                break;
+            }
+        }
+        expect!(self, TokenKind::RBrace)?;
+
+        Ok(Stmt::enum_(name, variants, self.mark_end()?))
+    }
+
+    /// Parse a match statement, e.g. `match m { Mode::Fast => { ... } Mode::Slow => { ... } }`.
+    fn parse_match_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Match)?;
+        let expr = self.with_records(false, Self::parse_expr)?;
+        expect!(self, TokenKind::LBrace)?;
+        let mut arms = vec![];
+        while accept!(self, TokenKind::RBrace).is_none() {
+            let pattern = self.parse_variant()?;
+            expect!(self, TokenKind::FatArrow)?;
+            let body = self.parse_block()?;
+            arms.push((pattern, body));
+        }
+
+        Ok(Stmt::match_(expr, arms, self.mark_end()?))
+    }
+
+    fn parse_block(&mut self) -> Result<Vec<Stmt<TokSpan>>> {
+        expect!(self, TokenKind::LBrace)?;
+        let mut stmts = Vec::new();
+        while accept!(self, TokenKind::RBrace).is_none() {
+            stmts.push(self.parse_stmt()?);
+        }
+
+        Ok(stmts)
     }
 
-    fn parse_add_term(&mut self) -> Result<Expr<TokSpan>> {
-        let mut start = self.cur_tok()?.clone();
-        let mut lhs = self.parse_mul_term()?;
-        while let Some(op_token) = accept!(self, TokenKind::Plus | TokenKind::Minus) {
+    fn parse_assign_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        let name = self.parse_var_name()?;
+        let op_token = expect!(
+            self,
+            TokenKind::Assign
+                | TokenKind::PlusAssign
+                | TokenKind::MinusAssign
+                | TokenKind::StarAssign
+                | TokenKind::SlashAssign
+        )?;
+        let expr = self.parse_expr()?;
+        expect!(self, TokenKind::Semi)?;
+
+        if op_token.kind == TokenKind::Assign {
+            Ok(Stmt::assign(name, expr, self.mark_end()?))
+        } else {
             let op = BinOp::new(
                 &op_token.kind,
                 TokSpan::new(op_token.clone(), op_token.clone()),
             );
+            Ok(Stmt::compound_assign(name, op, expr, self.mark_end()?))
+        }
+    }
 
-            let rhs = self.parse_mul_term()?;
+    /// Parse an assignment with a type annotation, e.g. `x: int? = none;`.
+    fn parse_declare_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
 
-            lhs = Expr::bin_op(
-                op,
+        let name = self.parse_var_name()?;
+        expect!(self, TokenKind::Colon)?;
+        let type_name = self.parse_type_name()?;
+        expect!(self, TokenKind::Assign)?;
+        let expr = self.parse_expr()?;
+        expect!(self, TokenKind::Semi)?;
+
+        Ok(Stmt::declare(name, type_name, expr, self.mark_end()?))
+    }
+
+    /// Parse a destructuring assignment, e.g. `let (a, (b, _)) = t;`.
+    fn parse_let_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Let)?;
+        let pattern = self.parse_pattern()?;
+        expect!(self, TokenKind::Assign)?;
+        let expr = self.parse_expr()?;
+        expect!(self, TokenKind::Semi)?;
+
+        Ok(Stmt::let_(pattern, expr, self.mark_end()?))
+    }
+
+    /// Parse a pattern, where `_` ignores a value. As for expressions, `(a)` is a grouping and
+    /// `(a,)` is a tuple.
+    fn parse_pattern(&mut self) -> Result<Pattern<TokSpan>> {
+        self.mark_start()?;
+
+        if accept!(self, TokenKind::LParen).is_none() {
+            let name = self.parse_var_name()?;
+            let span = self.mark_end()?;
+            return Ok(if name.kind == "_" {
+                Pattern::wildcard(span)
+            } else {
+                Pattern::name(name, span)
+            });
+        }
+
+        let mut patterns = vec![];
+        let mut is_tuple = false;
+        loop {
+            patterns.push(self.parse_pattern()?);
+            if accept!(self, TokenKind::Comma).is_none() {
+                break;
+            }
+            is_tuple = true;
+
+            // handle the trailing comma pattern
+            if let Some(TokenKind::RParen) = self.peek() {
+                break;
+            }
+        }
+        expect!(self, TokenKind::RParen)?;
+        let span = self.mark_end()?;
+
+        if is_tuple {
+            Ok(Pattern::tuple(patterns, span))
+        } else {
+            Ok(patterns.pop().unwrap())
+        }
+    }
+
+    fn parse_expr_stmt(&mut self) -> Result<Stmt<TokSpan>> {
+        self.mark_start()?;
+
+        let expr = self.parse_expr()?;
+        expect!(self, TokenKind::Semi)?;
+
+        Ok(Stmt::expr(expr, self.mark_end()?))
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr<TokSpan>> {
+        let start = self.cur_tok()?.clone();
+        let lhs = self.parse_unwrap_expr()?;
+        if accept!(self, TokenKind::DotDot).is_some() {
+            let rhs = self.parse_unwrap_expr()?;
+            Ok(Expr::range(
                 lhs,
                 rhs,
-                TokSpan::new(start.clone(), self.prev_tok()?.clone()),
-            );
+                TokSpan::new(start, self.prev_tok()?.clone()),
+            ))
+        } else {
+            Ok(lhs)
+        }
+    }
 
-            start = self.cur_tok()?.clone();
+    /// Parse an unwrapped optional with a default, e.g. `x ?? 0`, which is right-associative.
+    fn parse_unwrap_expr(&mut self) -> Result<Expr<TokSpan>> {
+        let start = self.cur_tok()?.clone();
+        let expr = self.parse_bin_op_expr(0)?;
+        if accept!(self, TokenKind::QuestionQuestion).is_some() {
+            let default = self.parse_unwrap_expr()?;
+            Ok(Expr::unwrap(
+                expr,
+                default,
+                TokSpan::new(start, self.prev_tok()?.clone()),
+            ))
+        } else {
+            Ok(expr)
         }
-        Ok(lhs)
     }
 
-    fn parse_mul_term(&mut self) -> Result<Expr<TokSpan>> {
-        // Note:
-        // This code is 100% duplicated from parse_add_term, except for:
-        // - the operators pattern
-        // - the parse sub-function called
-        // This should be cleaned up with a macro if we were to add more stages
-        let mut start = self.cur_tok()?.clone();
+    /// Parse a chain of binary operators using precedence climbing, only consuming operators whose
+    /// precedence is at least `min_precedence`. All binary operators are left-associative.
+    fn parse_bin_op_expr(&mut self, min_precedence: u8) -> Result<Expr<TokSpan>> {
+        let start = self.cur_tok()?.clone();
         let mut lhs = self.parse_factor()?;
-        while let Some(op_token) = accept!(self, TokenKind::Star | TokenKind::Slash) {
-            let op = BinOp::new(
-                &op_token.kind,
-                TokSpan::new(op_token.clone(), op_token.clone()),
-            );
+        while let Some(kind) = self.peek().and_then(BinOpKind::from_token) {
+            if kind.precedence() < min_precedence {
+                break;
+            }
+
+            let op_token = self.next().expect("peek means a token exists");
+            let op = BinOp::new(kind, TokSpan::new(op_token.clone(), op_token));
 
-            let rhs = self.parse_factor()?;
+            let rhs = self.parse_bin_op_expr(kind.precedence() + 1)?;
 
             lhs = Expr::bin_op(
                 op,
@@ -219,37 +608,105 @@ impl Parser {
                 rhs,
                 TokSpan::new(start.clone(), self.prev_tok()?.clone()),
             );
-
-            start = self.cur_tok()?.clone();
         }
         Ok(lhs)
     }
 
+    /// Parse a factor, followed by any number of field or tuple element accesses and calls, e.g.
+    /// `s.start.x`, `t.0` or `mk(2)(3)`.
     fn parse_factor(&mut self) -> Result<Expr<TokSpan>> {
+        let start = self.cur_tok()?.clone();
+        let mut expr = self.parse_primary()?;
+        loop {
+            if accept!(self, TokenKind::LParen).is_some() {
+                let args = self.parse_args()?;
+                expr = Expr::apply(
+                    expr,
+                    args,
+                    TokSpan::new(start.clone(), self.prev_tok()?.clone()),
+                );
+                continue;
+            }
+            if accept!(self, TokenKind::Dot).is_none() {
+                break;
+            }
+            if let Some(TokenKind::Int(index)) = self.peek() {
+                let index = *index as usize;
+                self.next();
+                expr = Expr::tuple_index(
+                    expr,
+                    index,
+                    TokSpan::new(start.clone(), self.prev_tok()?.clone()),
+                );
+            } else {
+                let field = self.parse_var_name()?;
+                expr = Expr::field(
+                    expr,
+                    field,
+                    TokSpan::new(start.clone(), self.prev_tok()?.clone()),
+                );
+            }
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr<TokSpan>> {
         let start_pos = self.pos;
         match self.peek() {
-            Some(TokenKind::Int(_)) => self.parse_integer(),
-            Some(TokenKind::Float(_)) => self.parse_float(),
-            Some(TokenKind::Name(_)) => self.parse_variable(),
-            Some(TokenKind::Minus) | Some(TokenKind::Plus) => self.parse_unary_factor(),
-            Some(TokenKind::LParen) => {
+            Some(TokenKind::Int(_)) | Some(TokenKind::Float(_)) => self.parse_number(),
+            Some(TokenKind::Imaginary(_)) => self.parse_imaginary(),
+            Some(TokenKind::True) | Some(TokenKind::False) => self.parse_bool(),
+            Some(TokenKind::If) => self.parse_conditional(),
+            Some(TokenKind::Fn) => self.parse_lambda(),
+            Some(TokenKind::None) => {
+                self.mark_start()?;
+                expect!(self, TokenKind::None)?;
+                Ok(Expr::none(self.mark_end()?))
+            }
+            Some(TokenKind::Name(_)) => {
+                let lookahead = |n: usize| self.tokens().get(self.pos + n).map(|t| &t.kind);
+                match (lookahead(1), lookahead(2), lookahead(3)) {
+                    (Some(TokenKind::LParen), _, _) => self.parse_call(),
+                    (Some(TokenKind::Dot), Some(TokenKind::Name(_)), Some(TokenKind::LParen)) => {
+                        self.parse_call()
+                    }
+                    (Some(TokenKind::FatArrow), _, _) => self.parse_short_lambda(),
+                    (Some(TokenKind::ColonColon), _, _) => self.parse_variant(),
+                    // checking for `name:` avoids confusing `for i in n { ... }` with a record, and
+                    // so does disallowing records right before a block, where the block may start
+                    // with a declaration, e.g. `for i in n { x: int = i; }`
+                    (Some(TokenKind::LBrace), Some(TokenKind::Name(_)), Some(TokenKind::Colon))
+                        if self.records_allowed =>
+                    {
+                        self.parse_record()
+                    }
+                    _ => self.parse_variable(),
+                }
+            }
+            Some(TokenKind::Minus) | Some(TokenKind::Plus) | Some(TokenKind::Tilde) => {
+                self.parse_unary_factor()
+            }
+            // records are allowed again within delimiters
+            Some(TokenKind::LBracket) => self.with_records(true, Self::parse_matrix),
+            Some(TokenKind::LParen) if self.is_short_lambda() => self.parse_short_lambda(),
+            Some(TokenKind::LParen) => self.with_records(true, |this| {
                 // tuple or grouping? We start with grouping to emulate Python's behavior:
                 // - (1, 2) is a tuple
                 // - (1) is a grouping
                 // - (1,) is a tuple
-                let res = self.parse_grouping();
+                let res = this.parse_grouping();
                 if res.is_ok() {
                     res
                 } else {
-                    self.pos = start_pos;
-                    self.parse_tuple()
+                    this.pos = start_pos;
+                    this.parse_tuple()
                 }
-            }
+            }),
             Some(token) => Err(ParserError::SyntaxError(
                 SyntaxError::UnexpectedToken(token.clone()),
                 self.next()
                     .expect("peek means a token exists")
-                    .to_error(&self.input.source),
+                    .to_error(&self.input.sources),
             )),
             None => Err(self.end_of_file_err()),
         }
@@ -276,6 +733,51 @@ impl Parser {
         Ok(Expr::tuple(exprs, self.mark_end()?))
     }
 
+    /// Parse a matrix literal, where rows are separated by semicolons, e.g. `[1.0, 2.0; 3.0, 4.0]`.
+    fn parse_matrix(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::LBracket)?;
+        let mut rows = vec![];
+        loop {
+            let mut row = vec![];
+            loop {
+                row.push(self.parse_expr()?);
+                if accept!(self, TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            rows.push(row);
+
+            if accept!(self, TokenKind::Semi).is_none() {
+                break;
+            }
+        }
+        expect!(self, TokenKind::RBracket)?;
+
+        Ok(Expr::matrix(rows, self.mark_end()?))
+    }
+
+    /// Parse a numeric literal, optionally followed by a unit (e.g. `3.0 km`).
+    fn parse_number(&mut self) -> Result<Expr<TokSpan>> {
+        let start = self.cur_tok()?.clone();
+        let value = match self.peek() {
+            Some(TokenKind::Int(_)) => self.parse_integer()?,
+            _ => self.parse_float()?,
+        };
+
+        if let Some(TokenKind::Name(_)) = self.peek() {
+            let unit = self.parse_var_name()?;
+            Ok(Expr::quantity(
+                value,
+                unit,
+                TokSpan::new(start, self.prev_tok()?.clone()),
+            ))
+        } else {
+            Ok(value)
+        }
+    }
+
     fn parse_integer(&mut self) -> Result<Expr<TokSpan>> {
         self.mark_start()?;
         let tok = expect!(self, TokenKind::Int(_))?;
@@ -296,6 +798,207 @@ impl Parser {
         }
     }
 
+    fn parse_imaginary(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+        let tok = expect!(self, TokenKind::Imaginary(_))?;
+        if let TokenKind::Imaginary(ref n) = tok.kind {
+            Ok(Expr::imaginary(*n, self.mark_end()?))
+        } else {
+            unreachable!()
+        }
+    }
+
+    fn parse_bool(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+        let tok = expect!(self, TokenKind::True | TokenKind::False)?;
+        Ok(Expr::bool(tok.kind == TokenKind::True, self.mark_end()?))
+    }
+
+    /// Parse `if cond then expr else expr`. The else branch extends as far as possible, so a
+    /// conditional used as an operand must be parenthesized.
+    fn parse_conditional(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::If)?;
+        let cond = self.parse_expr()?;
+        expect!(self, TokenKind::Then)?;
+        let then_branch = self.parse_expr()?;
+        expect!(self, TokenKind::Else)?;
+        let else_branch = self.parse_expr()?;
+
+        Ok(Expr::conditional(
+            cond,
+            then_branch,
+            else_branch,
+            self.mark_end()?,
+        ))
+    }
+
+    /// Parse an anonymous function, e.g. `fn(x int, y int) int => x * y`.
+    fn parse_lambda(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        expect!(self, TokenKind::Fn)?;
+        expect!(self, TokenKind::LParen)?;
+        let mut params = vec![];
+        if accept!(self, TokenKind::RParen).is_none() {
+            loop {
+                let param = self.parse_var_name()?;
+                let type_ = self.parse_type_name()?;
+                params.push((param, Some(type_)));
+                if accept!(self, TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            expect!(self, TokenKind::RParen)?;
+        }
+        let ret = self.parse_type_name()?;
+        expect!(self, TokenKind::FatArrow)?;
+        let body = self.parse_expr()?;
+
+        Ok(Expr::lambda(
+            params,
+            Some(ret),
+            body,
+            vec![],
+            self.mark_end()?,
+        ))
+    }
+
+    /// Parse an anonymous function whose types are inferred, e.g. `x => x * 2` or
+    /// `(a, b) => a + b`.
+    fn parse_short_lambda(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        let mut params = vec![];
+        if accept!(self, TokenKind::LParen).is_some() {
+            loop {
+                params.push((self.parse_var_name()?, None));
+                if accept!(self, TokenKind::Comma).is_none() {
+                    break;
+                }
+            }
+            expect!(self, TokenKind::RParen)?;
+        } else {
+            params.push((self.parse_var_name()?, None));
+        }
+        expect!(self, TokenKind::FatArrow)?;
+        let body = self.parse_expr()?;
+
+        Ok(Expr::lambda(params, None, body, vec![], self.mark_end()?))
+    }
+
+    /// Whether a parenthesized list of names followed by `=>` is coming, i.e. the parameters of a
+    /// short lambda rather than a tuple.
+    fn is_short_lambda(&self) -> bool {
+        let mut tokens = self.tokens()[self.pos..].iter().map(|t| &t.kind);
+        if tokens.next() != Some(&TokenKind::LParen) {
+            return false;
+        }
+        loop {
+            if !matches!(tokens.next(), Some(TokenKind::Name(_))) {
+                return false;
+            }
+            match tokens.next() {
+                Some(TokenKind::Comma) => continue,
+                Some(TokenKind::RParen) => return tokens.next() == Some(&TokenKind::FatArrow),
+                _ => return false,
+            }
+        }
+    }
+
+    /// Parse a function call, whose name may be qualified with a module, e.g. `geo.area(2.0)`.
+    fn parse_call(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        let mut name = self.parse_var_name()?;
+        if accept!(self, TokenKind::Dot).is_some() {
+            let member = self.parse_var_name()?;
+            name = VarName::new(
+                format!("{}.{}", name.kind, member.kind),
+                TokSpan::new(name.meta.start.clone(), member.meta.end.clone()),
+            );
+        }
+        expect!(self, TokenKind::LParen)?;
+
+        // the variable of a symbolic expression is a name rather than an argument
+        match name.kind.as_str() {
+            "deriv" => {
+                let expr = self.parse_expr()?;
+                expect!(self, TokenKind::Comma)?;
+                let var = self.parse_var_name()?;
+                expect!(self, TokenKind::RParen)?;
+                return Ok(Expr::deriv(expr, var, self.mark_end()?));
+            }
+            "eval" => {
+                let expr = self.parse_expr()?;
+                expect!(self, TokenKind::Comma)?;
+                let var = self.parse_var_name()?;
+                expect!(self, TokenKind::Assign)?;
+                let value = self.parse_expr()?;
+                expect!(self, TokenKind::RParen)?;
+                return Ok(Expr::eval(expr, var, value, self.mark_end()?));
+            }
+            _ => {}
+        }
+
+        let args = self.parse_args()?;
+        Ok(Expr::call(name, args, self.mark_end()?))
+    }
+
+    /// Parse the arguments of a call, up to the closing parenthesis.
+    fn parse_args(&mut self) -> Result<Vec<Expr<TokSpan>>> {
+        self.with_records(true, |this| {
+            let mut args = vec![];
+            if accept!(this, TokenKind::RParen).is_none() {
+                loop {
+                    args.push(this.parse_expr()?);
+                    if accept!(this, TokenKind::Comma).is_none() {
+                        break;
+                    }
+                }
+                expect!(this, TokenKind::RParen)?;
+            }
+            Ok(args)
+        })
+    }
+
+    /// Parse a record construction, e.g. `Point { x: 1.0, y: 2.0 }`.
+    fn parse_record(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        let name = self.parse_named_type()?;
+        expect!(self, TokenKind::LBrace)?;
+        let mut fields = vec![];
+        loop {
+            let field = self.parse_var_name()?;
+            expect!(self, TokenKind::Colon)?;
+            let value = self.parse_expr()?;
+            fields.push((field, value));
+
+            // a trailing comma is allowed
+            if accept!(self, TokenKind::Comma).is_none()
+                || matches!(self.peek(), Some(TokenKind::RBrace))
+            {
+                break;
+            }
+        }
+        expect!(self, TokenKind::RBrace)?;
+
+        Ok(Expr::record(name, fields, self.mark_end()?))
+    }
+
+    /// Parse an enum variant, e.g. `Mode::Fast`.
+    fn parse_variant(&mut self) -> Result<Expr<TokSpan>> {
+        self.mark_start()?;
+
+        let name = self.parse_named_type()?;
+        expect!(self, TokenKind::ColonColon)?;
+        let variant = self.parse_var_name()?;
+
+        Ok(Expr::variant(name, variant, self.mark_end()?))
+    }
+
     fn parse_variable(&mut self) -> Result<Expr<TokSpan>> {
         self.mark_start()?;
         let name = self.parse_var_name()?;
@@ -304,7 +1007,7 @@ impl Parser {
 
     fn parse_unary_factor(&mut self) -> Result<Expr<TokSpan>> {
         self.mark_start()?;
-        let op_token = expect!(self, TokenKind::Plus | TokenKind::Minus)?;
+        let op_token = expect!(self, TokenKind::Plus | TokenKind::Minus | TokenKind::Tilde)?;
 
         // create binop node
         let op = UnaryOp::new(
@@ -334,6 +1037,60 @@ impl Parser {
         }
     }
 
+    /// Parse a type, e.g. `int`, `Point?`, `fn(int, float) bool` or `(int, float)`. A single
+    /// parenthesized type is only grouped, such that `(fn(int) int)?` is an optional function
+    /// whereas `fn(int) int?` returns an optional.
+    fn parse_type_name(&mut self) -> Result<TypeName<TokSpan>> {
+        self.mark_start()?;
+        let type_name = match self.peek() {
+            Some(TokenKind::Fn) => {
+                self.mark_start()?;
+                expect!(self, TokenKind::Fn)?;
+                expect!(self, TokenKind::LParen)?;
+                let mut params = vec![];
+                if accept!(self, TokenKind::RParen).is_none() {
+                    loop {
+                        params.push(self.parse_type_name()?);
+                        if accept!(self, TokenKind::Comma).is_none() {
+                            break;
+                        }
+                    }
+                    expect!(self, TokenKind::RParen)?;
+                }
+                let ret = self.parse_type_name()?;
+                TypeName::fn_(params, ret, self.mark_end()?)
+            }
+            Some(TokenKind::LParen) => {
+                self.mark_start()?;
+                expect!(self, TokenKind::LParen)?;
+                let mut type_names = vec![self.parse_type_name()?];
+                while accept!(self, TokenKind::Comma).is_some() {
+                    type_names.push(self.parse_type_name()?);
+                }
+                expect!(self, TokenKind::RParen)?;
+                let span = self.mark_end()?;
+                match type_names.len() {
+                    1 => type_names.pop().unwrap(),
+                    _ => TypeName::tuple(type_names, span),
+                }
+            }
+            _ => self.parse_named_type()?,
+        };
+
+        if accept!(self, TokenKind::Question).is_some() {
+            Ok(TypeName::optional(type_name, self.mark_end()?))
+        } else {
+            self.mark_end()?;
+            Ok(type_name)
+        }
+    }
+
+    /// Parse the name of a built-in or declared type, e.g. `int` or `Point`.
+    fn parse_named_type(&mut self) -> Result<TypeName<TokSpan>> {
+        let name = self.parse_var_name()?;
+        Ok(TypeName::named(name.kind, name.meta))
+    }
+
     /// Create an EOF error.
     ///
     /// For this, we create a span based on the end location of the last token.
@@ -341,7 +1098,7 @@ impl Parser {
         let msg = self
             .tokens()
             .last()
-            .map(|t| t.to_error(&self.input.source))
+            .map(|t| t.to_error(&self.input.sources))
             .unwrap_or_default();
 
         ParserError::SyntaxError(SyntaxError::UnexpectedEndOfFile, msg)
@@ -374,4 +1131,129 @@ mod tests {
         insta::assert_debug_snapshot!("grouping", parse("a = (1);").raw_ast);
         insta::assert_debug_snapshot!("1-tuple trailing", parse("a = (1,);").raw_ast);
     }
+
+    #[test]
+    fn test_parser_for() {
+        let parsed = parse("for i in 0..n + 1 { print i; }");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_bitwise() {
+        let parsed = parse("print a | b ^ c & ~d << 1 + 2; print a - b - c;");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_conditional() {
+        let parsed = parse("m = if a > b then a else b + 1; print 1 + (if a == b then 1 else 2);");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_assert() {
+        let parsed = parse(r#"assert x + 1 > 0, "x must be positive";"#);
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_record() {
+        let parsed = parse(
+            "record Point { x float, y float, } p = Point { x: 1.0, y: -p.y }; for i in n { print -s.end.x; }",
+        );
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_product() {
+        let parsed = parse("t = (1, 2.5); print t.0.1 + -(t).1;");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_match() {
+        let parsed = parse(
+            "enum Mode { Fast, Slow, } match m { Mode::Fast => { print 1; } Mode::Slow => {} }",
+        );
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_lambda() {
+        let parsed = parse("sq = fn(x int) int => x * x; one = fn() float => 1.0; print sq(4);");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_function_type() {
+        let parsed = parse("t = fn(f fn(int, float) bool) int => 1; print t.0(1); print mk(2)(3);");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_type_name() {
+        // `?` applies to the return type of a function type, unless the function is parenthesized
+        let parsed =
+            parse("t: (int, float)? = none; f: (fn(int) int)? = none; g: fn(int) int? = h;");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_higher_order() {
+        let parsed =
+            parse("print map(t, x => x * 2); print fold(t, 0, (a, b) => a + b); p = (a, b);");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_optional() {
+        let parsed =
+            parse("x: int? = none; print x ?? y ?? 0; f = fn(v float?) float => v ?? 1.0;");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_block_declaration() {
+        // a block starting with a typed declaration isn't a record literal
+        let parsed = parse("for x in t { y: int? = x; } match m { E::A => { z: int = 1; } }");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_macro() {
+        let parsed = parse("macro twice(f, x) => f(f(x)); print twice(inc, 1);");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_let() {
+        let parsed = parse("let (a, (b, _)) = t; let (x,) = (1,); let (y) = 2;");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_symbolic() {
+        let parsed = parse("d = deriv(x * x + 3 * x, x); print eval(d, x = 2.0);");
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+    }
+
+    #[test]
+    fn test_parser_plot() {
+        let parsed = parse(r#"plot y = a * x for x in -1..2.5, "line.svg";"#);
+        insta::assert_debug_snapshot!(parsed.raw_ast);
+
+        // `calc plot` turns the last assignment into a plot
+        let plotted = plot(parse("a = 2.0; y = a * x;"), "x", (0.0, 10.0), "plot.svg").unwrap();
+        let mut output = String::new();
+        plotted.raw_ast.format(&mut output).unwrap();
+        insta::assert_snapshot!("plot of assignment", output);
+
+        let plot_err = |input: &str| {
+            plot(parse(input), "x", (0.0, 1.0), "plot.svg")
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        insta::assert_snapshot!("nothing to plot", plot_err("y = 2.0; print y;"));
+    }
 }
@@ -2,6 +2,7 @@
 //!
 //! Transforms a `RawInput` into a `TokenizedInput`.
 
+use crate::context::source::Sources;
 use crate::context::token_stream::TokenStream;
 use crate::data::span::{Loc, Span};
 use crate::data::token::{Token, TokenKind};
@@ -11,17 +12,33 @@ use crate::states::{InputState, TokenizedState};
 type Result<T> = std::result::Result<T, TokenizerError>;
 
 pub(crate) fn tokenize(input: InputState) -> Result<TokenizedState> {
-    let mut tokenizer = Tokenizer::new(input);
-    tokenizer.run()?;
+    // the tokens of all sources are merged in dependency order
+    let mut token_stream = TokenStream::default();
+    for &source in input.sources.order() {
+        tokenize_source(&input.sources, source, &mut token_stream)?;
+    }
     Ok(TokenizedState {
-        source: tokenizer.input.source,
-        token_stream: tokenizer.token_stream,
+        sources: input.sources,
+        token_stream,
     })
 }
 
+/// Tokenize a single source, appending its tokens to the stream.
+pub(crate) fn tokenize_source(
+    sources: &Sources,
+    source: usize,
+    token_stream: &mut TokenStream,
+) -> Result<()> {
+    let mut tokenizer = Tokenizer::new(sources, source, token_stream);
+    tokenizer.run()
+}
+
 /// Implement the tokenizer stage.
-struct Tokenizer {
-    input: InputState,
+struct Tokenizer<'a> {
+    sources: &'a Sources,
+
+    /// index of the tokenized source
+    source: usize,
 
     /// current position in the input, updated by [`next()`]
     pos: usize,
@@ -33,22 +50,27 @@ struct Tokenizer {
     start_loc: Loc,
 
     /// current stream of token
-    token_stream: TokenStream,
+    token_stream: &'a mut TokenStream,
 }
 
-impl Tokenizer {
-    fn new(input: InputState) -> Self {
+impl<'a> Tokenizer<'a> {
+    fn new(sources: &'a Sources, source: usize, token_stream: &'a mut TokenStream) -> Self {
+        let loc = Loc {
+            source,
+            ..Loc::default()
+        };
         Self {
-            input,
+            sources,
+            source,
             pos: 0,
-            loc: Loc::default(),
-            start_loc: Loc::default(),
-            token_stream: TokenStream::default(),
+            loc,
+            start_loc: loc,
+            token_stream,
         }
     }
 
     fn source(&self) -> &str {
-        self.input.as_ref()
+        self.sources.get(self.source).source()
     }
 
     /// Push a token into the token context.
@@ -83,6 +105,12 @@ impl Tokenizer {
         self.source().chars().nth(self.pos)
     }
 
+    /// Return the character after the next one in the input stream without updating the current
+    /// location.
+    fn peek_next(&self) -> Option<char> {
+        self.source().chars().nth(self.pos + 1)
+    }
+
     /// Return the next character in the input stream if it matches `c` and update the current
     /// location.
     fn accept(&mut self, c: char) -> bool {
@@ -96,7 +124,7 @@ impl Tokenizer {
 
     fn err<T>(&self, err: SyntaxError) -> Result<T> {
         let span = Span::new(self.loc, self.loc);
-        let new_err = TokenizerError::SyntaxError(err, span.to_error(&self.input.source));
+        let new_err = TokenizerError::SyntaxError(err, span.to_error(self.sources));
 
         Err(new_err)
     }
@@ -119,22 +147,32 @@ impl Tokenizer {
                             break;
                         }
                     }
-                    if let Some(c) = self.peek() {
-                        if c == '.' {
-                            num.push(c);
-                            self.next();
-                            while let Some(c) = self.peek() {
-                                if c.is_ascii_digit() {
-                                    num.push(c);
-                                    self.next();
-                                } else {
-                                    break;
-                                }
+                    // a dot followed by another dot is a range, e.g. `0..10`, and a number following
+                    // a dot is a tuple index, e.g. `t.0.1`
+                    let is_index = matches!(
+                        self.token_stream.tokens().last().map(|t| &t.kind),
+                        Some(TokenKind::Dot)
+                    );
+                    let is_float =
+                        !is_index && self.peek() == Some('.') && self.peek_next() != Some('.');
+                    if is_float {
+                        self.next();
+                        num.push('.');
+                        while let Some(c) = self.peek() {
+                            if c.is_ascii_digit() {
+                                num.push(c);
+                                self.next();
+                            } else {
+                                break;
                             }
-                            self.push(TokenKind::Float(num.parse().unwrap()));
-                        } else {
-                            self.push(TokenKind::Int(num.parse().unwrap()));
                         }
+                    }
+
+                    // imaginary literals have an `i` suffix, e.g. `4i` or `2.5i`
+                    if self.accept('i') {
+                        self.push(TokenKind::Imaginary(num.parse().unwrap()));
+                    } else if is_float {
+                        self.push(TokenKind::Float(num.parse().unwrap()));
                     } else {
                         self.push(TokenKind::Int(num.parse().unwrap()));
                     }
@@ -153,18 +191,77 @@ impl Tokenizer {
                     match name.as_str() {
                         // keywords
                         "print" => self.push(TokenKind::Print),
+                        "seed" => self.push(TokenKind::Seed),
+                        "for" => self.push(TokenKind::For),
+                        "in" => self.push(TokenKind::In),
+                        "if" => self.push(TokenKind::If),
+                        "then" => self.push(TokenKind::Then),
+                        "else" => self.push(TokenKind::Else),
+                        "true" => self.push(TokenKind::True),
+                        "false" => self.push(TokenKind::False),
+                        "assert" => self.push(TokenKind::Assert),
+                        "exit" => self.push(TokenKind::Exit),
+                        "record" => self.push(TokenKind::Record),
+                        "enum" => self.push(TokenKind::Enum),
+                        "match" => self.push(TokenKind::Match),
+                        "fn" => self.push(TokenKind::Fn),
+                        "none" => self.push(TokenKind::None),
+                        "import" => self.push(TokenKind::Import),
+                        "use" => self.push(TokenKind::Use),
+                        "macro" => self.push(TokenKind::Macro),
+                        "let" => self.push(TokenKind::Let),
+                        "plot" => self.push(TokenKind::Plot),
                         _ => self.push(TokenKind::Name(name)),
                     }
                 }
+                // strings, which don't support escape sequences
+                '"' => {
+                    let mut s = String::new();
+                    loop {
+                        match self.next() {
+                            Some('"') => break,
+                            Some(c) => s.push(c),
+                            None => return self.err(SyntaxError::UnterminatedString),
+                        }
+                    }
+                    self.push(TokenKind::Str(s));
+                }
                 // misc
                 ';' => self.push(TokenKind::Semi),
                 '(' => self.push(TokenKind::LParen),
                 ')' => self.push(TokenKind::RParen),
+                '[' => self.push(TokenKind::LBracket),
+                ']' => self.push(TokenKind::RBracket),
+                '{' => self.push(TokenKind::LBrace),
+                '}' => self.push(TokenKind::RBrace),
                 ',' => self.push(TokenKind::Comma),
+                ':' if self.accept(':') => self.push(TokenKind::ColonColon),
+                ':' => self.push(TokenKind::Colon),
+                '=' if self.accept('=') => self.push(TokenKind::EqEq),
+                '=' if self.accept('>') => self.push(TokenKind::FatArrow),
                 '=' => self.push(TokenKind::Assign),
+                '!' if self.accept('=') => self.push(TokenKind::NotEq),
+                '+' if self.accept('=') => self.push(TokenKind::PlusAssign),
                 '+' => self.push(TokenKind::Plus),
+                '-' if self.accept('=') => self.push(TokenKind::MinusAssign),
                 '-' => self.push(TokenKind::Minus),
+                '*' if self.accept('=') => self.push(TokenKind::StarAssign),
                 '*' => self.push(TokenKind::Star),
+                '@' => self.push(TokenKind::At),
+                '&' => self.push(TokenKind::Ampersand),
+                '|' => self.push(TokenKind::Pipe),
+                '^' => self.push(TokenKind::Caret),
+                '~' => self.push(TokenKind::Tilde),
+                '<' if self.accept('<') => self.push(TokenKind::ShiftLeft),
+                '<' if self.accept('=') => self.push(TokenKind::LtEq),
+                '<' => self.push(TokenKind::Lt),
+                '>' if self.accept('>') => self.push(TokenKind::ShiftRight),
+                '>' if self.accept('=') => self.push(TokenKind::GtEq),
+                '>' => self.push(TokenKind::Gt),
+                '.' if self.accept('.') => self.push(TokenKind::DotDot),
+                '.' => self.push(TokenKind::Dot),
+                '?' if self.accept('?') => self.push(TokenKind::QuestionQuestion),
+                '?' => self.push(TokenKind::Question),
                 '/' => {
                     if self.accept('/') {
                         while let Some(c) = self.next() {
@@ -179,6 +276,8 @@ impl Tokenizer {
                                 break;
                             }
                         }
+                    } else if self.accept('=') {
+                        self.push(TokenKind::SlashAssign);
                     } else {
                         self.push(TokenKind::Slash);
                     }
@@ -195,6 +294,7 @@ impl Tokenizer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::states::Load;
 
     #[test]
     fn test_tokenize() {
@@ -203,4 +303,73 @@ mod tests {
 
         insta::assert_debug_snapshot!(tokenized.token_stream);
     }
+
+    #[test]
+    fn test_tokenize_compound_assign() {
+        let input = InputState::from("a += 1; a -= -1; a *= 2; a /= 2 /* comment */; a = a/2;");
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+    }
+
+    #[test]
+    fn test_tokenize_bitwise() {
+        let input = InputState::from("a = ~b & 15 | c ^ 1 << 2 >> 3;");
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+    }
+
+    #[test]
+    fn test_tokenize_conditional() {
+        let input = InputState::from("m = if a <= b then a < b else a >= b != true == false > a;");
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+    }
+
+    #[test]
+    fn test_tokenize_assert() {
+        let input = InputState::from(r#"assert x > 0, "x must be positive";"#);
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+
+        let err = tokenize(InputState::from(r#"assert x > 0, "oops;"#))
+            .err()
+            .unwrap();
+        insta::assert_snapshot!("unterminated string", err.to_string());
+    }
+
+    #[test]
+    fn test_tokenize_match() {
+        let input = InputState::from("match m { Mode::Fast => {} }");
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+    }
+
+    #[test]
+    fn test_tokenize_optional() {
+        let input = InputState::from("x: int? = none; print x ?? 0;");
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+    }
+
+    #[test]
+    fn test_tokenize_import() {
+        let input = InputState::from("import \"consts.calc\";");
+        let tokenized = tokenize(input).unwrap();
+
+        insta::assert_debug_snapshot!(tokenized.token_stream);
+
+        // the dumped tokens of each file come after its path
+        let input =
+            InputState::from_file("testdata/imports/lib/geometry.calc".into(), Load::Imports)
+                .unwrap();
+        let mut dump = String::new();
+        tokenize(input).unwrap().dump(&mut dump).unwrap();
+        insta::assert_snapshot!("import dump", dump);
+    }
 }
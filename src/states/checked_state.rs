@@ -4,16 +4,15 @@
 
 use crate::context::ast::Ast;
 use crate::context::checked_ast::CheckedAst;
-use crate::context::source::Source;
+use crate::context::source::Sources;
 use crate::context::token_stream::TokenStream;
 use crate::data::token_span::TokSpan;
-use crate::errors::InterpreterError;
+use crate::errors::{InterpreterError, LlvmError};
 use crate::pipeline;
-use std::fmt;
 use std::fmt::Write;
 
 pub struct CheckedState {
-    pub(crate) source: Source,
+    pub(crate) sources: Sources,
     pub(crate) token_stream: TokenStream,
     pub(crate) raw_ast: Ast<TokSpan>,
     pub(crate) ast: CheckedAst,
@@ -24,11 +23,12 @@ impl CheckedState {
         pipeline::optimizer::optimize(self)
     }
 
-    pub fn interpret<W: Write>(&self, writer: &mut W) -> Result<(), InterpreterError> {
+    /// Run the program and return its exit status.
+    pub fn interpret<W: Write>(&self, writer: &mut W) -> Result<i32, InterpreterError> {
         pipeline::interpreter::interpret(self, writer)
     }
 
-    pub fn llvm_codegen<W: Write>(&self, writer: &mut W) -> Result<(), fmt::Error> {
+    pub fn llvm_codegen<W: Write>(&self, writer: &mut W) -> Result<(), LlvmError> {
         pipeline::llvm::llvm_codegen(self, writer)
     }
 }
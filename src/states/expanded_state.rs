@@ -0,0 +1,28 @@
+//! State after the macro expander.
+//!
+//! In this state, the AST holds no macro definitions nor calls, such that it can be type checked.
+
+use crate::context::ast::Ast;
+use crate::context::source::Sources;
+use crate::context::token_stream::TokenStream;
+use crate::data::token_span::TokSpan;
+use crate::errors::CheckerError;
+use crate::pipeline;
+use crate::states::CheckedState;
+
+pub struct ExpandedState {
+    pub(crate) sources: Sources,
+    pub(crate) token_stream: TokenStream,
+    pub(crate) raw_ast: Ast<TokSpan>,
+}
+
+impl ExpandedState {
+    pub fn check(self) -> Result<CheckedState, CheckerError> {
+        pipeline::checker::check(self)
+    }
+
+    /// Type check with implicit int-to-float promotion.
+    pub fn check_promote(self) -> Result<CheckedState, CheckerError> {
+        pipeline::checker::check_promote(self)
+    }
+}
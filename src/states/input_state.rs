@@ -1,10 +1,11 @@
 //! Model of the raw input state.
 //!
-//! This is basically just some raw text stored in a `String`.
+//! This is basically just some raw text stored in a `String`, along with that of the files it
+//! imports and of the prelude.
 
-use crate::context::source::Source;
+use crate::context::source::{Source, Sources};
 
-use crate::errors::TokenizerError;
+use crate::errors::{LoaderError, TokenizerError};
 use crate::pipeline;
 use crate::states::TokenizedState;
 use std::io;
@@ -12,7 +13,18 @@ use std::io::Read;
 use std::path::PathBuf;
 
 pub struct InputState {
-    pub(crate) source: Source,
+    pub(crate) sources: Sources,
+}
+
+/// Files read along with the main one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Load {
+    /// Only the main file, whose imports are left unresolved, e.g. to format it
+    MainOnly,
+    /// The main file and the files it imports
+    Imports,
+    /// The main file, the files it imports, and the prelude
+    Prelude,
 }
 
 impl InputState {
@@ -21,41 +33,63 @@ impl InputState {
     }
 }
 
+/// Input without imports, which are left unresolved, nor prelude.
 impl From<String> for InputState {
     fn from(text: String) -> Self {
         Self {
-            source: Source::new(text),
+            sources: Sources::new(Source::new(text)),
         }
     }
 }
 
 impl From<&str> for InputState {
     fn from(text: &str) -> Self {
-        Self {
-            source: Source::new(text.to_owned()),
-        }
+        Self::from(text.to_owned())
     }
 }
 
 impl AsRef<str> for InputState {
     fn as_ref(&self) -> &str {
-        self.source.source()
+        self.source()
     }
 }
 
 impl InputState {
-    pub fn from_file(path: PathBuf) -> io::Result<Self> {
-        let text = std::fs::read_to_string(path)?;
-        Ok(Self::from(text))
+    /// Read a file, along with the files it imports and the prelude as requested.
+    pub fn from_file(path: PathBuf, load: Load) -> Result<Self, LoaderError> {
+        let name = path.display().to_string();
+        let text = std::fs::read_to_string(&path)
+            .map_err(|err| LoaderError::IOError(name.clone(), err.to_string()))?;
+        Self::load(Source::new(text).with_name(name), load)
     }
 
-    pub fn from_stdin() -> io::Result<Self> {
+    /// Read the standard input, along with the files it imports relative to the working
+    /// directory and the prelude as requested.
+    pub fn from_stdin(load: Load) -> Result<Self, LoaderError> {
         let mut buffer = String::new();
-        io::stdin().read_to_string(&mut buffer)?;
-        Ok(Self::from(buffer))
+        io::stdin()
+            .read_to_string(&mut buffer)
+            .map_err(|err| LoaderError::IOError("<stdin>".to_owned(), err.to_string()))?;
+        Self::load(Source::new(buffer).with_name("<stdin>"), load)
+    }
+
+    /// Take some code, along with the files it imports relative to the working directory and
+    /// the prelude as requested.
+    pub fn from_code(code: String, load: Load) -> Result<Self, LoaderError> {
+        Self::load(Source::new(code), load)
+    }
+
+    fn load(main: Source, load: Load) -> Result<Self, LoaderError> {
+        let sources = match load {
+            Load::MainOnly => Sources::new(main),
+            Load::Imports => pipeline::loader::load(main, false)?,
+            Load::Prelude => pipeline::loader::load(main, true)?,
+        };
+        Ok(Self { sources })
     }
 
+    /// Text of the main source.
     pub fn source(&self) -> &str {
-        self.source.source()
+        self.sources.main().source()
     }
 }
@@ -5,11 +5,13 @@
 //! [`ParsedState`]).
 
 pub mod checked_state;
+pub mod expanded_state;
 pub mod input_state;
 pub mod parsed_state;
 pub mod tokenized_state;
 
 pub use checked_state::*;
+pub use expanded_state::*;
 pub use input_state::*;
 pub use parsed_state::*;
 pub use tokenized_state::*;
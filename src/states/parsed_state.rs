@@ -3,21 +3,32 @@
 //! Using the "context" terminology to disambiguate from the "state machine" states.
 
 use crate::context::ast::Ast;
-use crate::context::source::Source;
+use crate::context::source::Sources;
 use crate::context::token_stream::TokenStream;
 use crate::data::token_span::TokSpan;
-use crate::errors::CheckerError;
+use crate::errors::{ExpanderError, ParserError};
 use crate::pipeline;
-use crate::states::CheckedState;
+use crate::states::ExpandedState;
 
 pub struct ParsedState {
-    pub(crate) source: Source,
+    pub(crate) sources: Sources,
     pub(crate) token_stream: TokenStream,
     pub(crate) raw_ast: Ast<TokSpan>,
 }
 
 impl ParsedState {
-    pub fn check(self) -> Result<CheckedState, CheckerError> {
-        pipeline::checker::check(self)
+    /// Plot the expression assigned by the last statement over an interval of the variable.
+    pub fn plot(
+        self,
+        var: &str,
+        interval: (f64, f64),
+        path: &str,
+    ) -> Result<ParsedState, ParserError> {
+        pipeline::parser::plot(self, var, interval, path)
+    }
+
+    /// Replace macro calls by the macro bodies.
+    pub fn expand(self) -> Result<ExpandedState, ExpanderError> {
+        pipeline::expander::expand(self)
     }
 }
@@ -1,6 +1,6 @@
 //! This is the state after the tokenization step.
 
-use crate::context::source::Source;
+use crate::context::source::Sources;
 use crate::context::token_stream::TokenStream;
 use crate::data::token::{Token, TokenKind};
 use crate::errors::ParserError;
@@ -10,7 +10,7 @@ use std::fmt::Write;
 use std::rc::Rc;
 
 pub struct TokenizedState {
-    pub(crate) source: Source,
+    pub(crate) sources: Sources,
     pub(crate) token_stream: TokenStream,
 }
 
@@ -26,13 +26,24 @@ impl TokenizedState {
 
 impl TokenizedState {
     pub fn dump<W: Write>(&self, writer: &mut W) -> Result<(), std::fmt::Error> {
+        // the tokens of several sources are preceded by the path of their source
+        let mut source = None;
         for token in self.token_stream.tokens() {
+            if self.sources.order().len() > 1 && source != Some(token.span().start.source) {
+                source = Some(token.span().start.source);
+                writeln!(
+                    writer,
+                    "{}:",
+                    self.sources.get(token.span().start.source).name()
+                )?;
+            }
             let span = format!("{}", token.span());
 
             let kind_str = match token.kind {
                 TokenKind::Name(ref s) => format!("{:10} {:?}", "Name", s),
                 TokenKind::Int(i) => format!("{:10} {}", "Int", i),
                 TokenKind::Float(fl) => format!("{:10} {:?}", "Float", fl),
+                TokenKind::Imaginary(fl) => format!("{:10} {:?}", "Imaginary", fl),
                 _ => format!("{:?}", token.kind),
             };
 
@@ -0,0 +1,32 @@
+//! Tests of the command line, for behaviors spanning several pipeline stages.
+
+use std::process::{Command, Output};
+
+fn calc(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_calc"))
+        .args(args)
+        .output()
+        .expect("calc runs")
+}
+
+#[test]
+fn test_cli_missing_import() {
+    // formatting and tokenizing only read the given file, so an import needn't exist
+    let path = "testdata/imports/missing_import.calc";
+
+    let formatted = calc(&["format", path]);
+    assert!(formatted.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&formatted.stdout).trim(),
+        "import \"nowhere.calc\";"
+    );
+
+    let tokenized = calc(&["tokenize", path]);
+    assert!(tokenized.status.success());
+    assert!(String::from_utf8_lossy(&tokenized.stdout).contains("Import"));
+
+    // checking does need it
+    let checked = calc(&["check", path]);
+    assert!(!checked.status.success());
+    assert!(String::from_utf8_lossy(&checked.stderr).contains("cannot read"));
+}